@@ -0,0 +1,178 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use rocket::data::ByteUnit;
+use rocket::figment::providers::{Env, Format, Serialized, Toml};
+use rocket::figment::{self, Figment};
+use serde::{Deserialize, Serialize};
+
+use crate::db::import;
+
+/// Prefix of the environment variables that override the config file.
+pub const ENV_PREFIX: &str = "SCHILLER_DB_";
+
+/// Server configuration.
+///
+/// Layered from the defaults, the TOML config file, the `SCHILLER_DB_*`
+/// environment variables and the command line arguments, where later
+/// sources take precedence.
+/// `SCHILLER_DB_USER` and `SCHILLER_DB_PASSWORD` define the admin
+/// and are not part of the configuration.
+#[derive(Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct Config {
+    pub address: String,
+    pub port: u16,
+    /// Project database
+    pub database: PathBuf,
+    /// Key of the encrypted database, requires the `sqlcipher` feature
+    pub key: Option<String>,
+    /// Log file
+    pub log: PathBuf,
+    /// User data that is imported when the database is created
+    pub benutzer: PathBuf,
+    /// Ranks the roles of accounts that are imported more than once,
+    /// see [`import::users`]
+    pub role_precedence: Vec<String>,
+    /// Logins that are imported when the database is created
+    pub logins: PathBuf,
+    /// Defines the admin, if not already set in the environment
+    pub admin_env: PathBuf,
+    /// Directory of the built frontend
+    pub static_dir: PathBuf,
+    /// Maximum size of JSON request bodies
+    pub json_limit: ByteUnit,
+    /// Directory of the scheduled snapshots
+    pub backups: PathBuf,
+    /// Minutes between the scheduled snapshots, 0 disables them
+    pub backup_interval: u64,
+    /// Number of scheduled snapshots that are kept
+    pub backup_retention: usize,
+    /// Maximum number of database connections
+    pub pool_size: u32,
+    /// Seconds to wait for a free database connection
+    pub pool_timeout: u64,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            address: "0.0.0.0".into(),
+            port: 80,
+            database: "./schiller-db.db".into(),
+            key: None,
+            log: "log.txt".into(),
+            benutzer: "./benutzer.txt".into(),
+            role_precedence: import::default_role_precedence(),
+            logins: "./logins.txt".into(),
+            admin_env: "admin.env".into(),
+            static_dir: "build".into(),
+            json_limit: ByteUnit::Kibibyte(32),
+            backups: "./backups".into(),
+            backup_interval: 0,
+            backup_retention: 10,
+            pool_size: 16,
+            pool_timeout: 5,
+        }
+    }
+}
+
+impl fmt::Debug for Config {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Config")
+            .field("address", &self.address)
+            .field("port", &self.port)
+            .field("database", &self.database)
+            .field("encrypted", &self.key.is_some())
+            .field("log", &self.log)
+            .field("benutzer", &self.benutzer)
+            .field("role_precedence", &self.role_precedence)
+            .field("logins", &self.logins)
+            .field("admin_env", &self.admin_env)
+            .field("static_dir", &self.static_dir)
+            .field("json_limit", &self.json_limit)
+            .field("backups", &self.backups)
+            .field("backup_interval", &self.backup_interval)
+            .field("backup_retention", &self.backup_retention)
+            .field("pool_size", &self.pool_size)
+            .field("pool_timeout", &self.pool_timeout)
+            .finish()
+    }
+}
+
+impl Config {
+    /// Loads the configuration from `file`, the environment and `overrides`.
+    ///
+    /// A missing config file is treated as empty.
+    pub fn load<T: Serialize>(file: &Path, overrides: &T) -> Result<Self, Box<figment::Error>> {
+        Ok(Self::figment(file, overrides).extract()?)
+    }
+
+    fn figment<T: Serialize>(file: &Path, overrides: &T) -> Figment {
+        Figment::from(Serialized::defaults(Config::default()))
+            .merge(Toml::file(file))
+            .merge(Env::prefixed(ENV_PREFIX).ignore(&["user", "password"]))
+            .merge(Serialized::defaults(overrides))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use figment::Jail;
+    use rocket::data::ByteUnit;
+    use serde::Serialize;
+
+    use super::Config;
+
+    #[derive(Serialize)]
+    struct Args {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        port: Option<u16>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pool_timeout: Option<u64>,
+    }
+
+    #[test]
+    #[allow(clippy::result_large_err)]
+    fn layers() {
+        Jail::expect_with(|jail| {
+            assert_eq!(
+                Config::load(
+                    Path::new("missing.toml"),
+                    &Args {
+                        port: None,
+                        pool_timeout: None
+                    }
+                )
+                .map_err(|e| *e)?,
+                Config::default()
+            );
+
+            jail.create_file(
+                "test.toml",
+                "port = 8080\ndatabase = \"test.db\"\njson_limit = \"64 KiB\"\npool_timeout = 10",
+            )?;
+            jail.set_env("SCHILLER_DB_DATABASE", "env.db");
+            jail.set_env("SCHILLER_DB_POOL_TIMEOUT", "20");
+            jail.set_env("SCHILLER_DB_USER", "admin");
+            let args = Args {
+                port: Some(8081),
+                pool_timeout: None,
+            };
+            let config = Config::load(Path::new("test.toml"), &args).map_err(|e| *e)?;
+            assert_eq!(config.port, 8081);
+            assert_eq!(config.database, PathBuf::from("env.db"));
+            assert_eq!(config.json_limit, ByteUnit::Kibibyte(64));
+            assert_eq!(config.pool_timeout, 20);
+            let args = Args {
+                port: None,
+                pool_timeout: Some(30),
+            };
+            let config = Config::load(Path::new("test.toml"), &args).map_err(|e| *e)?;
+            assert_eq!(config.pool_timeout, 30);
+            Ok(())
+        });
+    }
+}
@@ -0,0 +1,366 @@
+use std::str::FromStr;
+
+use chrono::{DateTime, Utc};
+use rusqlite::types::{FromSql, FromSqlError, FromSqlResult, ToSql, ToSqlOutput, ValueRef};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::db::project::{DBIter, Database, Error, FromRow, Result};
+
+/// Actor of changes that are not made by a login, like the initial import.
+pub const SYSTEM: &str = "system";
+
+/// Table of an audited record.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Entity {
+    User,
+    Workless,
+    Criminal,
+    Login,
+    Role,
+    Hearing,
+}
+
+impl Entity {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Entity::User => "user",
+            Entity::Workless => "workless",
+            Entity::Criminal => "criminal",
+            Entity::Login => "login",
+            Entity::Role => "role",
+            Entity::Hearing => "hearing",
+        }
+    }
+}
+
+impl FromStr for Entity {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "user" => Ok(Entity::User),
+            "workless" => Ok(Entity::Workless),
+            "criminal" => Ok(Entity::Criminal),
+            "login" => Ok(Entity::Login),
+            "role" => Ok(Entity::Role),
+            "hearing" => Ok(Entity::Hearing),
+            _ => Err(Error::Arguments),
+        }
+    }
+}
+
+impl FromSql for Entity {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        value
+            .as_str()?
+            .parse()
+            .map_err(|_| FromSqlError::InvalidType)
+    }
+}
+
+impl ToSql for Entity {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        Ok(self.as_str().into())
+    }
+}
+
+/// Kind of change.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Operation {
+    Insert,
+    Update,
+    /// Moved into the trash bin
+    Delete,
+    /// Restored from the trash bin
+    Restore,
+    /// Removed from the trash bin
+    Purge,
+}
+
+impl FromSql for Operation {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        match value.as_str()? {
+            "insert" => Ok(Operation::Insert),
+            "update" => Ok(Operation::Update),
+            "delete" => Ok(Operation::Delete),
+            "restore" => Ok(Operation::Restore),
+            "purge" => Ok(Operation::Purge),
+            _ => Err(FromSqlError::InvalidType),
+        }
+    }
+}
+
+impl ToSql for Operation {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        Ok(match self {
+            Operation::Insert => "insert",
+            Operation::Update => "update",
+            Operation::Delete => "delete",
+            Operation::Restore => "restore",
+            Operation::Purge => "purge",
+        }
+        .into())
+    }
+}
+
+/// Record whose changes are written to the audit trail.
+pub trait Audited: Serialize {
+    const ENTITY: Entity;
+    /// Primary key, multiple columns are joined by '/'.
+    fn key(&self) -> String;
+}
+
+/// Entry of the audit trail.
+#[derive(Serialize, Debug, Clone, ToSchema)]
+pub struct Audit {
+    pub id: i64,
+    pub time: DateTime<Utc>,
+    /// Login that made the change
+    pub actor: String,
+    pub entity: Entity,
+    pub key: String,
+    pub operation: Operation,
+    /// The record before the change, if it existed
+    #[schema(value_type = Option<Object>)]
+    pub before: Option<serde_json::Value>,
+    /// The record after the change, if it still exists
+    #[schema(value_type = Option<Object>)]
+    pub after: Option<serde_json::Value>,
+}
+
+impl FromRow for Audit {
+    fn from_row(row: &rusqlite::Row) -> rusqlite::Result<Audit> {
+        Ok(Audit {
+            id: row.get("id")?,
+            time: row.get("time")?,
+            actor: row.get("actor")?,
+            entity: row.get("entity")?,
+            key: row.get("key")?,
+            operation: row.get("operation")?,
+            before: image(row, "before")?,
+            after: image(row, "after")?,
+        })
+    }
+}
+
+fn image(row: &rusqlite::Row, column: &str) -> rusqlite::Result<Option<serde_json::Value>> {
+    let Some(text) = row.get::<_, Option<String>>(column)? else {
+        return Ok(None);
+    };
+    serde_json::from_str(&text)
+        .map(Some)
+        .map_err(|e| FromSqlError::Other(Box::new(e)).into())
+}
+
+fn record<T: Audited>(
+    db: &Database,
+    actor: &str,
+    operation: Operation,
+    before: Option<&T>,
+    after: Option<&T>,
+) -> Result<()> {
+    let Some(key) = after.or(before).map(Audited::key) else {
+        return Ok(());
+    };
+    let before = before.map(serde_json::to_string).transpose()?;
+    let after = after.map(serde_json::to_string).transpose()?;
+    db.con.execute(
+        "insert into audit (time, actor, entity, key, operation, before, after) \
+        values (?, ?, ?, ?, ?, ?, ?)",
+        rusqlite::params![Utc::now(), actor, T::ENTITY, key, operation, before, after],
+    )?;
+    Ok(())
+}
+
+/// Records the insertion of `after`.
+pub fn insert<T: Audited>(db: &Database, actor: &str, after: &T) -> Result<()> {
+    record(db, actor, Operation::Insert, None, Some(after))
+}
+
+/// Records the change from `before` to `after`.
+pub fn update<T: Audited>(db: &Database, actor: &str, before: &T, after: &T) -> Result<()> {
+    record(db, actor, Operation::Update, Some(before), Some(after))
+}
+
+/// Records the deletion of `before`.
+pub fn delete<T: Audited>(db: &Database, actor: &str, before: &T) -> Result<()> {
+    record(db, actor, Operation::Delete, Some(before), None)
+}
+
+/// Records that `after` was restored from the trash bin.
+pub fn restore<T: Audited>(db: &Database, actor: &str, after: &T) -> Result<()> {
+    record(db, actor, Operation::Restore, None, Some(after))
+}
+
+/// Records that `before` was removed from the trash bin.
+pub fn purge<T: Audited>(db: &Database, actor: &str, before: &T) -> Result<()> {
+    record(db, actor, Operation::Purge, Some(before), None)
+}
+
+/// Parameters for searching the audit trail
+#[derive(Debug, Clone, Default)]
+pub struct AuditSearch<'a> {
+    /// Entities that may be returned
+    pub entities: &'a [Entity],
+    /// Matches the whole key or its first part, e.g. the account
+    pub key: Option<&'a str>,
+    pub actor: Option<&'a str>,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+}
+
+/// Returns the matching entries of the audit trail, the newest first.
+pub fn search(db: &Database, params: AuditSearch, limit: usize) -> Result<Vec<Audit>> {
+    let entities = params
+        .entities
+        .iter()
+        .map(|e| e.as_str())
+        .collect::<Vec<_>>()
+        .join(",");
+    let mut stmt = db.con.prepare(
+        "select \
+        id, time, actor, entity, key, operation, before, after \
+        from audit \
+        where instr(',' || ?1 || ',', ',' || entity || ',') \
+        and (?2 is null or key = ?2 or substr(key, 1, length(?2) + 1) = ?2 || '/') \
+        and (?3 is null or actor = ?3) \
+        and (?4 is null or time >= ?4) \
+        and (?5 is null or time <= ?5) \
+        order by id desc \
+        limit ?6",
+    )?;
+    let rows = stmt.query(rusqlite::params![
+        entities,
+        params.key.map(str::trim),
+        params.actor.map(str::trim),
+        params.from,
+        params.to,
+        limit
+    ])?;
+    DBIter::new(rows).collect()
+}
+
+/// Version 4: Audit trail of all changes.
+pub fn migrate(db: &Database) -> Result<()> {
+    db.con.execute_batch(
+        "create table audit ( \
+            id integer primary key, \
+            time text not null, \
+            actor text not null, \
+            entity text not null, \
+            key text not null, \
+            operation text not null, \
+            before text, \
+            after text); \
+        create index audit_entity_key on audit(entity, key);",
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::db::audit::{self, AuditSearch, Entity, Operation};
+    use crate::db::criminal::{self, Criminal};
+    use crate::db::project::{create, Database};
+    use crate::db::role::{self, Role, RoleKind};
+    use crate::db::user::{self, OnDelete, User};
+
+    #[test]
+    fn audit_trail() {
+        let db = Database::memory().unwrap();
+        create(&db).unwrap();
+        role::add(&db, "test", &Role::new("Demo", RoleKind::Other)).unwrap();
+
+        let user = User {
+            account: "foo.bar".into(),
+            forename: "Foo".into(),
+            surname: "Bar".into(),
+            role: "Demo".into(),
+        };
+        user::add(&db, "admin", &user).unwrap();
+        let criminal = Criminal {
+            account: user.account.clone(),
+            kind: "Theft".into(),
+            verdict: "Guilty".into(),
+            ..Default::default()
+        };
+        let criminal = criminal::add(&db, "judge", &criminal).unwrap();
+        criminal::update(
+            &db,
+            "judge",
+            criminal.id,
+            &Criminal {
+                verdict: "Innocent".into(),
+                ..criminal.clone()
+            },
+        )
+        .unwrap();
+        user::delete(&db, "admin", &user.account, OnDelete::Cascade).unwrap();
+
+        let all = [
+            Entity::User,
+            Entity::Workless,
+            Entity::Criminal,
+            Entity::Login,
+        ];
+        let result = audit::search(
+            &db,
+            AuditSearch {
+                entities: &all,
+                key: Some("foo.bar"),
+                ..Default::default()
+            },
+            100,
+        )
+        .unwrap();
+        let ops = result
+            .iter()
+            .map(|a| (a.entity, a.operation))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            ops,
+            [
+                (Entity::User, Operation::Delete),
+                (Entity::Criminal, Operation::Delete),
+                (Entity::Criminal, Operation::Update),
+                (Entity::Criminal, Operation::Insert),
+                (Entity::User, Operation::Insert),
+            ]
+        );
+
+        // who changed the verdict and what was it before
+        let result = audit::search(
+            &db,
+            AuditSearch {
+                entities: &[Entity::Criminal],
+                key: Some(&format!("foo.bar/{}", criminal.id)),
+                actor: Some("judge"),
+                ..Default::default()
+            },
+            100,
+        )
+        .unwrap();
+        let change = result
+            .iter()
+            .find(|a| a.operation == Operation::Update)
+            .unwrap();
+        assert_eq!(change.actor, "judge");
+        assert_eq!(change.before.as_ref().unwrap()["verdict"], "Guilty");
+        assert_eq!(change.after.as_ref().unwrap()["verdict"], "Innocent");
+
+        // entities without permission are hidden
+        let result = audit::search(
+            &db,
+            AuditSearch {
+                entities: &[Entity::User],
+                key: Some(&format!("foo.bar/{}", criminal.id)),
+                ..Default::default()
+            },
+            100,
+        )
+        .unwrap();
+        assert!(result.is_empty());
+    }
+}
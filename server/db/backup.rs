@@ -0,0 +1,128 @@
+use std::{
+    borrow::Cow,
+    fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use chrono::Local;
+use log::warn;
+use rusqlite::backup::Backup;
+
+use crate::db::project::{Database, Error, Result};
+
+/// Writes a consistent snapshot of the database to `path`.
+///
+/// This uses SQLite's online backup, so the database can be modified
+/// concurrently while the snapshot is taken.
+/// Snapshots of an encrypted database are encrypted with the same key.
+pub fn backup(db: &Database, path: &Path) -> Result<()> {
+    let mut target = Database::create(Cow::from(path), db.key())?;
+    copy(db, &mut target)
+}
+
+/// Copies all pages of `source` into `target`.
+fn copy(source: &Database, target: &mut Database) -> Result<()> {
+    Backup::new(&source.con, &mut target.con)?.run_to_completion(
+        64,
+        Duration::from_millis(50),
+        None,
+    )?;
+    Ok(())
+}
+
+/// Checks that `path` contains an intact project database, which can be
+/// unlocked with `key`, and migrates it to the current version.
+pub fn validate(path: &Path, key: Option<&str>) -> Result<Database> {
+    let (db, _) = Database::open(Cow::from(path), key).map_err(|e| match e {
+        // not a database at all
+        Error::SQL(_) => Error::InvalidFormat,
+        e => e,
+    })?;
+    let check: String = db
+        .con
+        .query_row("pragma quick_check", [], |row| row.get(0))?;
+    if check != "ok" {
+        warn!("Corrupted backup {path:?}: {check}");
+        return Err(Error::InvalidFormat);
+    }
+    Ok(db)
+}
+
+/// Replaces the contents of the database with the backup at `path`.
+///
+/// The backup is validated first, backups of older versions are migrated.
+/// It has to be encrypted with the same key as the database.
+/// Other connections to the database see the restored contents with their
+/// next statement.
+pub fn restore(db: &mut Database, path: &Path) -> Result<()> {
+    let backup = validate(path, db.key())?;
+    copy(&backup, db)
+}
+
+/// Writes a timestamped snapshot into `dir`,
+/// keeping only the `retention` most recent ones.
+///
+/// Returns the path of the new snapshot.
+pub fn snapshot(db: &Database, dir: &Path, retention: usize) -> Result<PathBuf> {
+    fs::create_dir_all(dir)?;
+    let name = format!("schiller-db-{}.db", Local::now().format("%Y%m%d-%H%M%S"));
+    let path = dir.join(name);
+    backup(db, &path)?;
+
+    let mut snapshots = fs::read_dir(dir)?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<std::io::Result<Vec<_>>>()?;
+    snapshots.retain(|p| {
+        p.file_name()
+            .and_then(|n| n.to_str())
+            .is_some_and(|n| n.starts_with("schiller-db-") && n.ends_with(".db"))
+    });
+    // the timestamps are sorted lexicographically
+    snapshots.sort();
+    let outdated = snapshots.len().saturating_sub(retention);
+    for old in &snapshots[..outdated] {
+        fs::remove_file(old)?;
+    }
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+
+    use crate::db::backup;
+    use crate::db::project::{create, Database};
+    use crate::db::role::{self, Role, RoleKind};
+    use crate::db::user::{self, User};
+
+    #[test]
+    fn backup_restore() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = Database::create(Cow::from(dir.path().join("schiller-db.db")), None).unwrap();
+        create(&db).unwrap();
+        role::add(&db, "test", &Role::new("Demo", RoleKind::Other)).unwrap();
+
+        let user = User {
+            account: "foo.bar".into(),
+            forename: "Foo".into(),
+            surname: "Bar".into(),
+            role: "Demo".into(),
+        };
+        user::add(&db, "test", &user).unwrap();
+
+        let path = backup::snapshot(&db, &dir.path().join("backups"), 1).unwrap();
+        user::delete(&db, "test", &user.account, user::OnDelete::Cascade).unwrap();
+        assert!(user::fetch(&db, &user.account).is_err());
+
+        let mut db = db;
+        backup::restore(&mut db, &path).unwrap();
+        assert_eq!(user::fetch(&db, &user.account).unwrap(), user);
+
+        // invalid files are refused
+        let invalid = dir.path().join("invalid.db");
+        std::fs::write(&invalid, "no database").unwrap();
+        assert!(backup::restore(&mut db, &invalid).is_err());
+        assert_eq!(user::fetch(&db, &user.account).unwrap(), user);
+    }
+}
@@ -0,0 +1,306 @@
+use chrono::{DateTime, Datelike, Local, Timelike};
+use pdf_writer::{Content, Date, Name, Pdf, Rect, Ref, Str, TextStr};
+
+use crate::db::criminal::{self, Criminal};
+use crate::db::project::{Database, Result};
+use crate::db::user::{self, User};
+
+/// A4 in points
+const WIDTH: f32 = 595.0;
+const HEIGHT: f32 = 842.0;
+const MARGIN: f32 = 56.0;
+
+/// Fonts of the page resources, the standard fonts need no embedding.
+const REGULAR: Name = Name(b"F1");
+const BOLD: Name = Name(b"F2");
+
+/// Certificate of good conduct ("Führungszeugnis") of a user.
+#[derive(Debug, Clone)]
+pub struct Certificate {
+    pub user: User,
+    /// Criminal records of the user, ordered by kind
+    pub criminals: Vec<Criminal>,
+    pub issued_at: DateTime<Local>,
+    /// Login that issued the certificate
+    pub issuer: String,
+}
+
+impl Certificate {
+    /// Gathers the criminal records of the user with the given `account`.
+    pub fn new(db: &Database, account: &str, issuer: &str) -> Result<Certificate> {
+        Ok(Certificate {
+            user: user::fetch(db, account)?,
+            criminals: criminal::of_account(db, account)?,
+            issued_at: Local::now(),
+            issuer: issuer.into(),
+        })
+    }
+
+    /// Renders the certificate as printable PDF with A4 pages.
+    pub fn pdf(&self) -> Vec<u8> {
+        let mut layout = Layout::new();
+        layout.text(BOLD, 20.0, "Führungszeugnis");
+        layout.space(12.0);
+        layout.text(
+            REGULAR,
+            11.0,
+            &format!("Name: {} {}", self.user.forename, self.user.surname),
+        );
+        layout.text(REGULAR, 11.0, &format!("Konto: {}", self.user.account));
+        layout.text(REGULAR, 11.0, &format!("Rolle: {}", self.user.role));
+        layout.space(12.0);
+        layout.text(BOLD, 13.0, "Inhalt");
+
+        if self.criminals.is_empty() {
+            layout.text(REGULAR, 11.0, "Keine Eintragung.");
+        }
+        for (i, criminal) in self.criminals.iter().enumerate() {
+            layout.space(6.0);
+            layout.text(BOLD, 11.0, &format!("{}. {}", i + 1, criminal.kind));
+            let fields = [
+                ("Aktenzeichen", &criminal.case_number),
+                ("Anzeiger", &criminal.accuser),
+                ("Sachberater Polizei", &criminal.police_consultant),
+                ("Anwalt des Beschuldigten", &criminal.lawyer_culprit),
+                ("Anwalt des Anzeigers", &criminal.lawyer_accuser),
+                ("Tatbestand", &criminal.facts),
+                ("Zeitpunkt der Tat", &criminal.time_of_crime),
+                ("Ort der Tat", &criminal.location_of_crime),
+                ("Urteil", &criminal.verdict),
+                ("Kommentar", &criminal.note),
+            ];
+            for (label, value) in fields {
+                if !value.trim().is_empty() {
+                    layout.text(REGULAR, 11.0, &format!("{label}: {}", value.trim()));
+                }
+            }
+        }
+
+        layout.space(24.0);
+        layout.text(
+            REGULAR,
+            11.0,
+            &format!(
+                "Ausgestellt am {} von {}",
+                self.issued_at.format("%d.%m.%Y um %H:%M Uhr"),
+                self.issuer
+            ),
+        );
+        layout.finish(
+            &format!("Führungszeugnis {}", self.user.account),
+            self.issued_at,
+        )
+    }
+}
+
+/// Writes paragraphs from top to bottom, starting new pages as needed.
+struct Layout {
+    pages: Vec<Content>,
+    y: f32,
+}
+
+impl Layout {
+    fn new() -> Self {
+        let mut layout = Layout {
+            pages: Vec::new(),
+            y: 0.0,
+        };
+        layout.page();
+        layout
+    }
+
+    fn page(&mut self) {
+        self.pages.push(Content::new());
+        self.y = HEIGHT - MARGIN;
+    }
+
+    fn space(&mut self, height: f32) {
+        self.y -= height;
+    }
+
+    /// Writes the `text` wrapped at the margins.
+    fn text(&mut self, font: Name, size: f32, text: &str) {
+        let leading = size * 1.4;
+        // the average width of a character is about half the font size
+        let columns = ((WIDTH - 2.0 * MARGIN) / (size * 0.55)) as usize;
+        for line in wrap(text, columns) {
+            if self.y - leading < MARGIN {
+                self.page();
+            }
+            self.y -= leading;
+            let content = self.pages.last_mut().unwrap();
+            content
+                .begin_text()
+                .set_font(font, size)
+                .next_line(MARGIN, self.y)
+                .show(Str(&win_ansi(&line)))
+                .end_text();
+        }
+    }
+
+    /// Adds page numbers and returns the PDF file.
+    fn finish(self, title: &str, date: DateTime<Local>) -> Vec<u8> {
+        let catalog = Ref::new(1);
+        let tree = Ref::new(2);
+        let regular = Ref::new(3);
+        let bold = Ref::new(4);
+        let info = Ref::new(5);
+        let count = self.pages.len();
+        let page_ids = (0..count)
+            .map(|i| Ref::new(6 + 2 * i as i32))
+            .collect::<Vec<_>>();
+
+        let mut pdf = Pdf::new();
+        pdf.catalog(catalog).pages(tree);
+        pdf.pages(tree)
+            .kids(page_ids.iter().copied())
+            .count(count as i32);
+        pdf.type1_font(regular)
+            .base_font(Name(b"Helvetica"))
+            .encoding_predefined(Name(b"WinAnsiEncoding"));
+        pdf.type1_font(bold)
+            .base_font(Name(b"Helvetica-Bold"))
+            .encoding_predefined(Name(b"WinAnsiEncoding"));
+        pdf.document_info(info)
+            .title(TextStr(title))
+            .creator(TextStr("schiller-db"))
+            .creation_date(
+                Date::new(date.year() as u16)
+                    .month(date.month() as u8)
+                    .day(date.day() as u8)
+                    .hour(date.hour() as u8)
+                    .minute(date.minute() as u8)
+                    .second(date.second() as u8),
+            );
+
+        for (i, (mut content, id)) in self.pages.into_iter().zip(page_ids).enumerate() {
+            content
+                .begin_text()
+                .set_font(REGULAR, 9.0)
+                .next_line(WIDTH - MARGIN - 60.0, MARGIN / 2.0)
+                .show(Str(&win_ansi(&format!("Seite {} von {count}", i + 1))))
+                .end_text();
+            let content_id = Ref::new(id.get() + 1);
+            let mut page = pdf.page(id);
+            page.parent(tree)
+                .media_box(Rect::new(0.0, 0.0, WIDTH, HEIGHT))
+                .contents(content_id);
+            page.resources()
+                .fonts()
+                .pair(REGULAR, regular)
+                .pair(BOLD, bold);
+            drop(page);
+            pdf.stream(content_id, &content.finish());
+        }
+        pdf.finish()
+    }
+}
+
+/// Splits the `text` into lines of at most `columns` characters.
+///
+/// Lines are broken between words, longer words are split.
+fn wrap(text: &str, columns: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    for paragraph in text.lines() {
+        let mut line = String::new();
+        for word in paragraph.split_whitespace() {
+            let mut word = word.chars().collect::<Vec<_>>();
+            let len = line.chars().count();
+            if len > 0 && len + 1 + word.len() > columns {
+                lines.push(std::mem::take(&mut line));
+            }
+            while word.len() > columns {
+                let rest = word.split_off(columns);
+                lines.push(word.into_iter().collect());
+                word = rest;
+            }
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.extend(word);
+        }
+        lines.push(line);
+    }
+    lines
+}
+
+/// Encodes the `text` for the standard fonts.
+///
+/// Latin-1 is a subset of WinAnsi, other characters are replaced.
+fn win_ansi(text: &str) -> Vec<u8> {
+    text.chars()
+        .map(|c| match c {
+            ' '..='~' | '\u{a0}'..='\u{ff}' => c as u8,
+            '€' => 0x80,
+            '„' => 0x84,
+            '–' => 0x96,
+            '—' => 0x97,
+            '“' => 0x93,
+            '”' => 0x94,
+            _ => b'?',
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::db::certificate::{wrap, Certificate};
+    use crate::db::criminal::{self, Criminal};
+    use crate::db::project::{create, Database, Error};
+    use crate::db::user::{self, User};
+
+    fn contains(pdf: &[u8], text: &str) -> bool {
+        pdf.windows(text.len()).any(|w| w == text.as_bytes())
+    }
+    use crate::db::role::{self, Role, RoleKind};
+
+    #[test]
+    fn certificate() {
+        let db = Database::memory().unwrap();
+        create(&db).unwrap();
+        role::add(&db, "test", &Role::new("Demo", RoleKind::Other)).unwrap();
+
+        let user = User {
+            account: "foo.bar".into(),
+            forename: "Foo".into(),
+            surname: "Bar".into(),
+            role: "Demo".into(),
+        };
+        user::add(&db, "test", &user).unwrap();
+        assert_eq!(
+            Certificate::new(&db, "baz", "judge").unwrap_err(),
+            Error::NothingFound
+        );
+
+        let certificate = Certificate::new(&db, &user.account, "judge").unwrap();
+        assert!(certificate.criminals.is_empty());
+        let pdf = certificate.pdf();
+        assert!(pdf.starts_with(b"%PDF-"));
+        assert!(contains(&pdf, "(Name: Foo Bar)"));
+        assert!(contains(&pdf, "(Keine Eintragung.)"));
+        assert!(contains(&pdf, " von judge)"));
+
+        let facts = "Took the bar. ".repeat(100);
+        for kind in ["Theft", "Fraud"] {
+            let criminal = Criminal {
+                account: user.account.clone(),
+                kind: kind.into(),
+                facts: facts.clone(),
+                verdict: "Guilty".into(),
+                ..Default::default()
+            };
+            criminal::add(&db, "test", &criminal).unwrap();
+        }
+        let pdf = Certificate::new(&db, &user.account, "judge").unwrap().pdf();
+        assert!(contains(&pdf, "(1. Fraud)"));
+        assert!(contains(&pdf, "(2. Theft)"));
+        assert!(contains(&pdf, "(Urteil: Guilty)"));
+        assert!(!contains(&pdf, "Keine Eintragung"));
+        assert!(contains(&pdf, "(Seite 2 von 2)"));
+
+        assert_eq!(
+            wrap("aaa bbb ccc\n\ndddddddd", 7),
+            ["aaa bbb", "ccc", "", "ddddddd", "d"]
+        );
+    }
+}
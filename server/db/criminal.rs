@@ -1,14 +1,31 @@
-use std::collections::HashSet;
-
+use chrono::{DateTime, Datelike, Local, Utc};
+use rusqlite::ToSql;
 use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
 
-use crate::db::project::{DBIter, Database, Error, FromRow, Result};
+use crate::db::audit::{self, Audited, Entity};
+use crate::db::fulltext::{self, Hit, HIGHLIGHT};
+use crate::db::hearing;
+use crate::db::history::Version;
+use crate::db::page::{paginate, Page, Paging};
+use crate::db::project::{unknown_account, DBIter, Database, Error, FieldError, FromRow, Result};
+use crate::db::repository::{self, Repository};
+use crate::db::sort::{Sort, SortColumns};
+use crate::db::status::{self, CaseStatus, Transition};
+use crate::db::trash::Trashed;
+use crate::db::user;
+use crate::db::validate::{self, Field, Rule, Validate, Value};
 
 /// Data object for a criminal.
-#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
-#[cfg_attr(test, derive(PartialEq, Default))]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, ToSchema)]
+#[cfg_attr(test, derive(Default))]
 pub struct Criminal {
+    /// Surrogate key of the case, assigned when it is added
+    #[serde(default)]
+    pub id: i64,
+    /// Human-readable case number like `2026-0042`, assigned when it is added
+    #[serde(default)]
+    pub case_number: String,
     pub account: String,
     pub kind: String,
     pub accuser: String,
@@ -20,20 +37,65 @@ pub struct Criminal {
     pub location_of_crime: String,
     pub note: String,
     pub verdict: String,
+    /// Only changed by [`transition`], new cases are reported
+    #[serde(default)]
+    pub status: CaseStatus,
+}
+
+impl Validate for Criminal {
+    const FIELDS: &'static [Field] = &[
+        Field::new("account", validate::ACCOUNT),
+        Field::new("kind", KIND),
+        Field::new("accuser", validate::SHORT_TEXT),
+        Field::new("police_consultant", validate::SHORT_TEXT),
+        Field::new("lawyer_culprit", validate::SHORT_TEXT),
+        Field::new("lawyer_accuser", validate::SHORT_TEXT),
+        Field::new("facts", validate::LONG_TEXT),
+        Field::new("time_of_crime", validate::SHORT_TEXT),
+        Field::new("location_of_crime", validate::SHORT_TEXT),
+        Field::new("note", validate::LONG_TEXT),
+        Field::new("verdict", validate::LONG_TEXT),
+    ];
+    fn fields(&self) -> Vec<Value<'_>> {
+        vec![
+            Value::Text(&self.account),
+            Value::Text(&self.kind),
+            Value::Text(&self.accuser),
+            Value::Text(&self.police_consultant),
+            Value::Text(&self.lawyer_culprit),
+            Value::Text(&self.lawyer_accuser),
+            Value::Text(&self.facts),
+            Value::Text(&self.time_of_crime),
+            Value::Text(&self.location_of_crime),
+            Value::Text(&self.note),
+            Value::Text(&self.verdict),
+        ]
+    }
+    fn invalid(details: Vec<FieldError>) -> Error {
+        Error::InvalidCriminal(details)
+    }
 }
 
-impl Criminal {
-    pub fn is_valid(&self) -> bool {
-        !self.account.trim().is_empty()
-            && self.account.starts_with(char::is_alphabetic)
-            && !self.kind.trim().is_empty()
-            && self.kind.starts_with(char::is_alphanumeric)
+const KIND: &[Rule] = &[
+    Rule::Required,
+    Rule::StartsWithAlphanumeric,
+    Rule::MaxLength(128),
+];
+
+impl Audited for Criminal {
+    const ENTITY: Entity = Entity::Criminal;
+    /// The account is kept in front of the id,
+    /// so the audit trail of a user includes its cases.
+    fn key(&self) -> String {
+        format!("{}/{}", self.account, self.id)
     }
 }
 
 impl FromRow for Criminal {
     fn from_row(row: &rusqlite::Row) -> rusqlite::Result<Criminal> {
         Ok(Criminal {
+            id: row.get("id")?,
+            case_number: row.get("case_number")?,
             account: row.get("account")?,
             kind: row.get("kind")?,
             accuser: row.get("accuser")?,
@@ -45,14 +107,130 @@ impl FromRow for Criminal {
             location_of_crime: row.get("location_of_crime")?,
             note: row.get("note")?,
             verdict: row.get("verdict")?,
+            status: row.get("status")?,
+        })
+    }
+}
+
+impl Repository for Criminal {
+    const TABLE: &'static str = "criminal";
+    const COLUMNS: &'static [&'static str] = &[
+        "id",
+        "case_number",
+        "account",
+        "kind",
+        "accuser",
+        "police_consultant",
+        "lawyer_culprit",
+        "lawyer_accuser",
+        "facts",
+        "time_of_crime",
+        "location_of_crime",
+        "note",
+        "verdict",
+        "status",
+    ];
+    const KEY: &'static [&'static str] = &["id"];
+    const ORDER: &'static str = "account asc, kind asc, id asc";
+
+    type Key = i64;
+
+    fn id(&self) -> i64 {
+        self.id
+    }
+    fn bind(key: &i64) -> Vec<&dyn ToSql> {
+        vec![key]
+    }
+    fn values(&self) -> Vec<&dyn ToSql> {
+        vec![
+            &self.id,
+            &self.case_number,
+            &self.account,
+            &self.kind,
+            &self.accuser,
+            &self.police_consultant,
+            &self.lawyer_culprit,
+            &self.lawyer_accuser,
+            &self.facts,
+            &self.time_of_crime,
+            &self.location_of_crime,
+            &self.note,
+            &self.verdict,
+            &self.status,
+        ]
+    }
+    fn trimmed(&self) -> Criminal {
+        Criminal {
+            id: self.id,
+            case_number: self.case_number.trim().into(),
+            account: self.account.trim().into(),
+            kind: self.kind.trim().into(),
+            accuser: self.accuser.trim().into(),
+            police_consultant: self.police_consultant.trim().into(),
+            lawyer_culprit: self.lawyer_culprit.trim().into(),
+            lawyer_accuser: self.lawyer_accuser.trim().into(),
+            facts: self.facts.trim().into(),
+            time_of_crime: self.time_of_crime.trim().into(),
+            location_of_crime: self.location_of_crime.trim().into(),
+            note: self.note.trim().into(),
+            verdict: self.verdict.trim().into(),
+            status: self.status,
+        }
+    }
+    /// Ids are never reused, the case numbers count the cases of each year.
+    /// Given keys are kept, like the ones of a dump.
+    fn assign(self, db: &Database) -> Result<Criminal> {
+        let id = if self.id != 0 {
+            self.id
+        } else {
+            // the history also knows the purged cases
+            db.con.query_row(
+                "select max( \
+                    coalesce((select max(id) from criminal), 0), \
+                    coalesce((select max(id) from criminal_history), 0)) + 1",
+                [],
+                |row| row.get(0),
+            )?
+        };
+        let case_number = if !self.case_number.is_empty() {
+            self.case_number
+        } else {
+            let year = Local::now().year().to_string();
+            let number: i64 = db.con.query_row(
+                "select coalesce(max(cast(substr(case_number, length(?1) + 2) as integer)), 0) + 1 \
+                from (select case_number from criminal \
+                    union all select case_number from criminal_history) \
+                where case_number like ?1 || '-%'",
+                [&year],
+                |row| row.get(0),
+            )?;
+            format!("{year}-{number:04}")
+        };
+        Ok(Criminal {
+            id,
+            case_number,
+            ..self
         })
     }
+    fn check(&self, db: &Database) -> Result<()> {
+        user::ensure_exists(db, &self.account)
+    }
+    fn map_err(e: rusqlite::Error) -> Error {
+        unknown_account(e)
+    }
 }
 
-/// Returns the criminal with the given `account`.
-pub fn fetch(db: &Database, account: &str, kind: &str) -> Result<Criminal> {
-    Ok(db.con.query_row(
+/// Returns the criminal case with the given `id`.
+pub fn fetch(db: &Database, id: i64) -> Result<Criminal> {
+    repository::fetch(db, &id)
+}
+
+/// Returns all criminals of the given `account`.
+pub fn of_account(db: &Database, account: &str) -> Result<Vec<Criminal>> {
+    let mut stmt = db.con.prepare(
         "select \
+        id, \
+        case_number, \
         account, \
         kind, \
         accuser, \
@@ -63,92 +241,104 @@ pub fn fetch(db: &Database, account: &str, kind: &str) -> Result<Criminal> {
         time_of_crime, \
         location_of_crime, \
         note, \
-        verdict \
+        verdict, \
+        status \
         \
         from criminal \
-        where account=?",
-        rusqlite::params![account, kind],
-        Criminal::from_row,
-    )?)
+        where account=? and deleted_at is null \
+        order by kind, id",
+    )?;
+    let rows = stmt.query([account])?;
+    DBIter::new(rows).collect()
 }
 
 /// Returns all accounts from the criminal table without duplicates
 pub fn all_accounts(db: &Database) -> Result<Vec<String>> {
-    let mut stmt = db.con.prepare(
+    repository::distinct(
+        db,
         "select \
         account \
         from criminal \
+        where deleted_at is null \
         order by account asc",
-    )?;
-
-    let mut rows = stmt.query([])?;
-    let mut accounts = Vec::new();
-    let mut seen_accounts = HashSet::new();
-
-    while let Some(row) = rows.next()? {
-        let account: String = row.get(0).unwrap();
-
-        // Check if the account has already been seen
-        if seen_accounts.contains(&account) {
-            continue; // Skip the duplicate account
-        }
-
-        accounts.push(account.clone());
-        seen_accounts.insert(account);
-    }
-
-    Ok(accounts)
+        [],
+    )
 }
 
 /// Returns all roles from the criminal table without duplicates
 pub fn all_roles(db: &Database, name: &str) -> Result<Vec<String>> {
-    let mut stmt = db.con.prepare(
+    repository::distinct(
+        db,
         "SELECT \
         DISTINCT user.role \
         FROM criminal \
         INNER JOIN user ON criminal.account = user.account \
-        where criminal.account like '%'||?1||'%' \
+        where criminal.deleted_at is null \
+        and criminal.account like '%'||?1||'%' \
         ORDER BY user.role ASC",
-    )?;
-
-    let mut rows = stmt.query(rusqlite::params![name.trim()])?;
-    let mut roles = Vec::new();
-    let mut seen_roles = HashSet::new();
-
-    while let Some(row) = rows.next()? {
-        let role: String = row.get(0).unwrap();
-
-        // Check if the role has already been seen
-        if seen_roles.contains(&role) {
-            continue; // Skip the duplicate role
-        }
-
-        roles.push(role.clone());
-        seen_roles.insert(role);
-    }
-
-    Ok(roles)
+        [name.trim()],
+    )
 }
 
 /// Searches with roles etc. from the criminal table
-pub fn search_role(db: &Database, name: &str, role: &str, limit: usize) -> Result<Vec<Criminal>> {
-    let mut stmt = db.con.prepare(
-        "SELECT criminal.*
-        FROM criminal
-        INNER JOIN user ON criminal.account = user.account
-        WHERE criminal.account LIKE '%' || ?1 || '%'
-        AND user.role LIKE ?2
-        ORDER BY CASE
-            WHEN criminal.account LIKE ?1 || '%' THEN 0
-            ELSE 1
-        END ASC, criminal.account ASC
-        LIMIT ?3",
-    )?;
-
-    let rows = stmt.query(rusqlite::params![name.trim(), role.trim(), limit])?;
-    DBIter::new(rows).collect()
+pub fn search_role(
+    db: &Database,
+    name: &str,
+    role: &str,
+    sort: &Sort,
+    paging: Paging,
+) -> Result<Page<Criminal>> {
+    let order = sort.order_by(
+        "CASE WHEN criminal.account LIKE ?1 || '%' THEN 0 ELSE 1 END ASC, criminal.account ASC",
+    );
+    paginate(
+        db,
+        &format!(
+            "SELECT
+            criminal.id,
+            criminal.case_number,
+            criminal.account,
+            criminal.kind,
+            criminal.accuser,
+            criminal.police_consultant,
+            criminal.lawyer_culprit,
+            criminal.lawyer_accuser,
+            criminal.facts,
+            criminal.time_of_crime,
+            criminal.location_of_crime,
+            criminal.note,
+            criminal.verdict,
+            criminal.status
+            FROM criminal
+            INNER JOIN user ON criminal.account = user.account
+            WHERE criminal.deleted_at IS NULL
+            AND criminal.account LIKE '%' || ?1 || '%'
+            AND user.role LIKE ?2
+            {order}"
+        ),
+        rusqlite::params![name.trim(), role.trim()],
+        paging,
+    )
 }
 
+/// Columns the criminal searches can be sorted by, including the names of the user.
+pub const SORT_COLUMNS: SortColumns = &[
+    ("case_number", "criminal.case_number"),
+    ("account", "criminal.account"),
+    ("kind", "criminal.kind"),
+    ("accuser", "criminal.accuser"),
+    ("police_consultant", "criminal.police_consultant"),
+    ("lawyer_culprit", "criminal.lawyer_culprit"),
+    ("lawyer_accuser", "criminal.lawyer_accuser"),
+    ("time_of_crime", "criminal.time_of_crime"),
+    ("location_of_crime", "criminal.location_of_crime"),
+    ("verdict", "criminal.verdict"),
+    ("status", "criminal.status"),
+    ("forename", "user.forename"),
+    ("surname", "user.surname"),
+    ("role", "user.role"),
+];
+
 /// Parameters for the advanced search
 ///
 /// Adding the '%' char allows every number of every character in this place
@@ -157,6 +347,8 @@ pub struct CriminalSearch<'a> {
     pub name: &'a str,
     pub kind: &'a str,
     pub account: &'a str,
+    /// Only cases with this status, if any
+    pub status: Option<CaseStatus>,
 }
 
 impl<'a> CriminalSearch<'a> {
@@ -165,142 +357,355 @@ impl<'a> CriminalSearch<'a> {
             name,
             account,
             kind,
+            status: None,
         }
     }
 }
 
 /// Performes a simple criminal search with the given `text`.
-pub fn search(db: &Database, params: CriminalSearch, limit: usize) -> Result<Vec<Criminal>> {
-    let mut stmt = db.con.prepare(
-        "select \
-        account, \
-        kind, \
-        accuser, \
-        police_consultant, \
-        lawyer_culprit, \
-        lawyer_accuser, \
-        facts, \
-        time_of_crime, \
-        location_of_crime, \
-        note, \
-        verdict \
-        \
-        from criminal \
-        where account like '%'||?1||'%' \
-        and account like ?2 \
-        and kind like ?3 \
-        order by case \
-            when account like ?1 || '%' then 0 \
-            else 1 \
-        end asc, account asc \
-        limit ?4",
-    )?;
-    let rows = stmt.query(rusqlite::params![
-        params.name.trim(),
-        params.account.trim(),
-        params.kind.trim(),
-        limit
-    ])?;
-    DBIter::new(rows).collect()
-}
-
-/// Adds a new criminal.
-pub fn add(db: &Database, criminal: &Criminal) -> Result<()> {
-    if !criminal.is_valid() {
-        return Err(Error::InvalidCriminal);
-    }
-    db.con.execute(
-        "INSERT INTO criminal VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+pub fn search(
+    db: &Database,
+    params: CriminalSearch,
+    sort: &Sort,
+    paging: Paging,
+) -> Result<Page<Criminal>> {
+    let order = sort.order_by(
+        "case when criminal.account like ?1 || '%' then 0 else 1 end asc, criminal.account asc",
+    );
+    paginate(
+        db,
+        &format!(
+            "select \
+            criminal.id, \
+            criminal.case_number, \
+            criminal.account, \
+            criminal.kind, \
+            criminal.accuser, \
+            criminal.police_consultant, \
+            criminal.lawyer_culprit, \
+            criminal.lawyer_accuser, \
+            criminal.facts, \
+            criminal.time_of_crime, \
+            criminal.location_of_crime, \
+            criminal.note, \
+            criminal.verdict, \
+            criminal.status \
+            \
+            from criminal \
+            join user on user.account = criminal.account \
+            where criminal.deleted_at is null \
+            and criminal.account like '%'||?1||'%' \
+            and criminal.account like ?2 \
+            and criminal.kind like ?3 \
+            and (?4 is null or criminal.status = ?4) \
+            {order}"
+        ),
         rusqlite::params![
-            criminal.account.trim(),
-            criminal.kind.trim(),
-            criminal.accuser.trim(),
-            criminal.police_consultant.trim(),
-            criminal.lawyer_culprit.trim(),
-            criminal.lawyer_accuser.trim(),
-            criminal.facts.trim(),
-            criminal.time_of_crime.trim(),
-            criminal.location_of_crime.trim(),
-            criminal.note.trim(),
-            criminal.verdict.trim(),
+            params.name.trim(),
+            params.account.trim(),
+            params.kind.trim(),
+            params.status
         ],
-    )?;
-    Ok(())
+        paging,
+    )
 }
 
-/// Updates the criminal.
-/// This includes all its data.
-pub fn update(
+/// Searches all texts of the criminal records for the words of `text`.
+///
+/// The hits are ordered by relevance.
+pub fn search_text(
     db: &Database,
-    previous_account: &str,
-    previous_kind: &str,
-    criminal: &Criminal,
-) -> Result<()> {
-    let previous_account = previous_account.trim();
-    if previous_account.is_empty() || !criminal.is_valid() {
-        return Err(Error::InvalidCriminal);
-    }
-    let previous_kind = previous_kind.trim();
-    if previous_kind.is_empty() {
-        return Err(Error::InvalidKind);
-    }
+    text: &str,
+    sort: &Sort,
+    paging: Paging,
+) -> Result<Page<Hit<Criminal>>> {
+    let Some(query) = fulltext::query(text) else {
+        return Ok(Page::empty());
+    };
+    let order = sort.order_by("rank");
+    paginate(
+        db,
+        &format!(
+            "select \
+            criminal.id, \
+            criminal.case_number, \
+            criminal.account, \
+            criminal.kind, \
+            criminal.accuser, \
+            criminal.police_consultant, \
+            criminal.lawyer_culprit, \
+            criminal.lawyer_accuser, \
+            criminal.facts, \
+            criminal.time_of_crime, \
+            criminal.location_of_crime, \
+            criminal.note, \
+            criminal.verdict, \
+            criminal.status, \
+            criminal_fts.rank as rank, \
+            snippet(criminal_fts, -1, ?2, ?3, '…', 12) as snippet \
+            \
+            from criminal_fts \
+            join criminal on criminal.id = criminal_fts.id \
+            join user on user.account = criminal.account \
+            where criminal_fts match ?1 and criminal.deleted_at is null \
+            {order}"
+        ),
+        rusqlite::params![query, HIGHLIGHT.0, HIGHLIGHT.1],
+        paging,
+    )
+}
 
-    let transaction = db.transaction()?;
-    // update date
-    transaction.execute(
-        "update criminal set account=?, kind=?, accuser=?, police_consultant=?, lawyer_culprit=?, lawyer_accuser=?, facts=?, time_of_crime=?, location_of_crime=?, note=?, verdict=? where account=? and kind=?",
-        rusqlite::params![
-            criminal.account.trim(),
-            criminal.kind.trim(),
-            criminal.accuser.trim(),
-            criminal.police_consultant.trim(),
-            criminal.lawyer_culprit.trim(),
-            criminal.lawyer_accuser.trim(),
-            criminal.facts.trim(),
-            criminal.time_of_crime.trim(),
-            criminal.location_of_crime.trim(),
-            criminal.note.trim(),
-            criminal.verdict.trim(),
-            previous_account,
-            previous_kind
-        ],
-    )?;
+/// Adds a new criminal case and returns it with its id and case number.
+///
+/// New cases are always reported, their id, case number and status are ignored.
+pub fn add(db: &Database, actor: &str, criminal: &Criminal) -> Result<Criminal> {
+    let criminal = Criminal {
+        id: 0,
+        case_number: String::new(),
+        status: CaseStatus::Reported,
+        ..criminal.clone()
+    };
+    db.transaction(|db| {
+        let criminal = repository::add(db, actor, &criminal)?;
+        status::record(db, actor, criminal.id, None, criminal.status)?;
+        Ok(criminal)
+    })
+}
+
+/// Updates the criminal case.
+/// This includes all its data, except for the id, case number and status.
+pub fn update(db: &Database, actor: &str, id: i64, criminal: &Criminal) -> Result<()> {
+    criminal.validate()?;
+    db.transaction(|db| {
+        let before = fetch(db, id)?;
+        let criminal = Criminal {
+            id,
+            case_number: before.case_number,
+            status: before.status,
+            ..criminal.clone()
+        };
+        repository::update(db, actor, &id, &criminal)
+    })
+}
+
+/// Changes the status of the criminal case and returns the changed case.
+///
+/// Fails with [`Error::InvalidTransition`] if the current status
+/// can not change to the new one, see [`CaseStatus::next`].
+pub fn transition(db: &Database, actor: &str, id: i64, next: CaseStatus) -> Result<Criminal> {
+    db.transaction(|db| {
+        let before = fetch(db, id)?;
+        if !before.status.allows(next) {
+            return Err(Error::InvalidTransition(before.status, next));
+        }
+        db.con.execute(
+            "update criminal set status=? where id=?",
+            rusqlite::params![next, id],
+        )?;
+        status::record(db, actor, id, Some(before.status), next)?;
+        let after = fetch(db, id)?;
+        audit::update(db, actor, &before, &after)?;
+        Ok(after)
+    })
+}
 
-    transaction.commit()?;
+/// Returns the status changes of the criminal case, the oldest first.
+pub fn transitions(db: &Database, id: i64) -> Result<Vec<Transition>> {
+    fetch(db, id)?;
+    status::of_case(db, id)
+}
+
+/// Moves the criminal case into the trash bin.
+pub fn delete(db: &Database, actor: &str, id: i64) -> Result<()> {
+    repository::delete::<Criminal>(db, actor, &id)
+}
+
+/// Returns the criminals in the trash bin, the most recently deleted first.
+pub fn trash(db: &Database, limit: usize) -> Result<Vec<Trashed<Criminal>>> {
+    repository::trash(db, limit)
+}
+
+/// Restores the criminal case from the trash bin.
+///
+/// Fails with [`Error::UnknownAccount`] if its user is in the trash bin too.
+pub fn restore(db: &Database, actor: &str, id: i64) -> Result<()> {
+    repository::restore::<Criminal>(db, actor, &id)?;
     Ok(())
 }
 
-/// Deletes the criminal.
-/// This includes all its data.
-pub fn delete(db: &Database, account: &str, kind: &str) -> Result<()> {
-    let account = account.trim();
-    if account.is_empty() {
-        return Err(Error::InvalidCriminal);
-    }
-    let kind = kind.trim();
-    if kind.is_empty() {
-        return Err(Error::InvalidKind);
-    }
-    let transaction = db.transaction()?;
-    // remove date and presenters
-    transaction.execute(
-        "delete from criminal where account=? and kind=?",
-        rusqlite::params![account, kind],
-    )?;
-    transaction.commit()?;
+/// Permanently deletes the criminal case from the trash bin,
+/// including its hearings.
+pub fn purge(db: &Database, actor: &str, id: i64) -> Result<()> {
+    db.transaction(|db| {
+        hearing::purge(db, actor, id)?;
+        repository::purge::<Criminal>(db, actor, &id)
+    })
+}
+
+/// Returns all revisions of the criminal case, the oldest first.
+pub fn history(db: &Database, id: i64) -> Result<Vec<Version<Criminal>>> {
+    repository::history(db, &id)
+}
+
+/// Returns the criminals as they were at the given `time`.
+pub fn as_of(db: &Database, time: DateTime<Utc>, limit: usize) -> Result<Vec<Criminal>> {
+    repository::as_of(db, time, limit)
+}
+
+/// Version 8: Criminal cases get a surrogate id and a case number,
+/// so a user can have several cases of the same kind.
+///
+/// The existing cases are numbered in the order they were added, followed by
+/// the purged ones that are only left in the history. Their case numbers are
+/// counted in the year of the migration. The full-text index and the history
+/// reference the cases by their id, and the audit entries use the new keys.
+pub fn migrate(db: &Database) -> Result<()> {
+    const MIGRATION: &str = "\
+    create temp table criminal_key ( \
+        id integer primary key, \
+        account text not null, \
+        kind text not null); \
+    insert into criminal_key (account, kind) \
+        select account, kind from criminal order by rowid; \
+    insert into criminal_key (account, kind) \
+        select account, kind from criminal_history h \
+        where not exists (select 1 from criminal_key k \
+            where k.account = h.account and k.kind = h.kind) \
+        group by account, kind \
+        order by min(rowid); \
+    \
+    create table criminal_new ( \
+        id integer primary key, \
+        case_number text not null unique, \
+        account text not null references user(account) \
+            on update cascade on delete cascade, \
+        kind text not null, \
+        accuser text not null, \
+        police_consultant text not null, \
+        lawyer_culprit text not null, \
+        lawyer_accuser text not null, \
+        facts text not null, \
+        time_of_crime text not null, \
+        location_of_crime text not null, \
+        note text not null, \
+        verdict text not null, \
+        deleted_at text); \
+    insert into criminal_new select \
+        k.id, strftime('%Y', 'now') || '-' || printf('%04d', k.id), \
+        c.account, c.kind, c.accuser, c.police_consultant, c.lawyer_culprit, \
+        c.lawyer_accuser, c.facts, c.time_of_crime, c.location_of_crime, c.note, \
+        c.verdict, c.deleted_at \
+        from criminal c \
+        join criminal_key k on k.account = c.account and k.kind = c.kind; \
+    drop table criminal; \
+    alter table criminal_new rename to criminal; \
+    \
+    alter table criminal_history add column id integer; \
+    alter table criminal_history add column case_number text; \
+    update criminal_history set \
+        id = (select k.id from criminal_key k \
+            where k.account = criminal_history.account and k.kind = criminal_history.kind), \
+        case_number = (select strftime('%Y', 'now') || '-' || printf('%04d', k.id) \
+            from criminal_key k \
+            where k.account = criminal_history.account and k.kind = criminal_history.kind); \
+    drop index criminal_history_key; \
+    create index criminal_history_key on criminal_history(id, valid_from); \
+    update audit set key = ( \
+            select k.account || '/' || k.id from criminal_key k \
+            where k.account || '/' || k.kind = audit.key) \
+        where entity = 'criminal' \
+        and key in (select account || '/' || kind from criminal_key); \
+    drop table criminal_key; \
+    \
+    create trigger criminal_history_insert after insert on criminal begin \
+        insert into criminal_history (id, case_number, account, kind, accuser, \
+            police_consultant, lawyer_culprit, lawyer_accuser, facts, time_of_crime, \
+            location_of_crime, note, verdict, deleted_at, valid_from, valid_to) \
+        values (new.id, new.case_number, new.account, new.kind, new.accuser, \
+            new.police_consultant, new.lawyer_culprit, new.lawyer_accuser, new.facts, \
+            new.time_of_crime, new.location_of_crime, new.note, new.verdict, \
+            new.deleted_at, strftime('%Y-%m-%d %H:%M:%f+00:00', 'now'), null); \
+    end; \
+    create trigger criminal_history_update after update on criminal begin \
+        update criminal_history set valid_to=strftime('%Y-%m-%d %H:%M:%f+00:00', 'now') \
+        where id=old.id and valid_to is null; \
+        insert into criminal_history (id, case_number, account, kind, accuser, \
+            police_consultant, lawyer_culprit, lawyer_accuser, facts, time_of_crime, \
+            location_of_crime, note, verdict, deleted_at, valid_from, valid_to) \
+        values (new.id, new.case_number, new.account, new.kind, new.accuser, \
+            new.police_consultant, new.lawyer_culprit, new.lawyer_accuser, new.facts, \
+            new.time_of_crime, new.location_of_crime, new.note, new.verdict, \
+            new.deleted_at, strftime('%Y-%m-%d %H:%M:%f+00:00', 'now'), null); \
+    end; \
+    create trigger criminal_history_delete after delete on criminal begin \
+        update criminal_history set valid_to=strftime('%Y-%m-%d %H:%M:%f+00:00', 'now') \
+        where id=old.id and valid_to is null; \
+    end; \
+    \
+    drop table criminal_fts; \
+    create virtual table criminal_fts using fts5( \
+        id unindexed, case_number, account, kind, accuser, police_consultant, \
+        lawyer_culprit, lawyer_accuser, facts, time_of_crime, location_of_crime, \
+        note, verdict, \
+        tokenize = 'unicode61 remove_diacritics 2'); \
+    insert into criminal_fts select \
+        id, case_number, account, kind, accuser, police_consultant, lawyer_culprit, \
+        lawyer_accuser, facts, time_of_crime, location_of_crime, note, verdict \
+        from criminal; \
+    \
+    create trigger criminal_fts_insert after insert on criminal begin \
+        insert into criminal_fts values (new.id, new.case_number, new.account, \
+            new.kind, new.accuser, new.police_consultant, new.lawyer_culprit, \
+            new.lawyer_accuser, new.facts, new.time_of_crime, new.location_of_crime, \
+            new.note, new.verdict); \
+    end; \
+    create trigger criminal_fts_update after update on criminal begin \
+        update criminal_fts set id=new.id, case_number=new.case_number, \
+            account=new.account, kind=new.kind, accuser=new.accuser, \
+            police_consultant=new.police_consultant, lawyer_culprit=new.lawyer_culprit, \
+            lawyer_accuser=new.lawyer_accuser, facts=new.facts, \
+            time_of_crime=new.time_of_crime, location_of_crime=new.location_of_crime, \
+            note=new.note, verdict=new.verdict \
+        where id=old.id; \
+    end; \
+    create trigger criminal_fts_delete after delete on criminal begin \
+        delete from criminal_fts where id=old.id; \
+    end; \
+    ";
+    db.con.execute_batch(MIGRATION)?;
     Ok(())
 }
 
 #[cfg(test)]
 mod tests {
+    use chrono::{Datelike, Local};
+
+    use crate::db::audit::{self, AuditSearch, Entity};
     use crate::db::criminal::{self, Criminal};
-    use crate::db::project::{create, Database};
+    use crate::db::page::Paging;
+    use crate::db::project::{create, migrate, migrate_to, Database, CREATE_TABLES};
+    use crate::db::role::{self, Role, RoleKind};
+    use crate::db::sort::Sort;
+    use crate::db::status::CaseStatus;
+    use crate::db::user::{self, User};
+
     #[test]
     fn add_update_remove_criminal() {
         let db = Database::memory().unwrap();
         create(&db).unwrap();
+        role::add(&db, "test", &Role::new("Demo", RoleKind::Other)).unwrap();
+
+        let user = User {
+            account: "foo".into(),
+            forename: "Foo".into(),
+            surname: "Bar".into(),
+            role: "Demo".into(),
+        };
+        user::add(&db, "test", &user).unwrap();
 
         let criminal = Criminal {
+            id: 0,
+            case_number: String::new(),
             account: "foo".to_string(),
             kind: "Destroy".to_string(),
             accuser: "bar".to_string(),
@@ -312,32 +717,146 @@ mod tests {
             location_of_crime: "nowhere".to_string(),
             note: "yes!".to_string(),
             verdict: "definitely guilty".to_string(),
+            status: CaseStatus::Closed,
         };
-        criminal::add(&db, &criminal).unwrap();
+        let criminal = criminal::add(&db, "test", &criminal).unwrap();
+        let year = Local::now().year();
+        assert_eq!(criminal.case_number, format!("{year}-0001"));
+        assert_eq!(criminal.status, CaseStatus::Reported);
 
-        let result =
-            criminal::search(&db, criminal::CriminalSearch::new("%", "%", "%"), 200).unwrap();
+        // another case of the same kind
+        let second = criminal::add(
+            &db,
+            "test",
+            &Criminal {
+                id: 0,
+                case_number: String::new(),
+                ..criminal.clone()
+            },
+        )
+        .unwrap();
+        assert_eq!(second.id, criminal.id + 1);
+        assert_eq!(second.case_number, format!("{year}-0002"));
+        criminal::delete(&db, "test", second.id).unwrap();
+        criminal::purge(&db, "test", second.id).unwrap();
+
+        let result = criminal::search(
+            &db,
+            criminal::CriminalSearch::new("%", "%", "%"),
+            &Sort::default(),
+            Paging::default(),
+        )
+        .unwrap()
+        .items;
         assert_eq!(result.len(), 1);
         assert_eq!(result[0], criminal);
 
         criminal::update(
             &db,
-            &criminal.account,
-            &criminal.kind,
+            "test",
+            criminal.id,
             &Criminal {
                 facts: "some".to_string(),
+                case_number: "changed".to_string(),
                 ..criminal.clone()
             },
         )
         .unwrap();
-        let result =
-            criminal::search(&db, criminal::CriminalSearch::new("%", "%", "%"), 200).unwrap();
+        let result = criminal::search(
+            &db,
+            criminal::CriminalSearch::new("%", "%", "%"),
+            &Sort::default(),
+            Paging::default(),
+        )
+        .unwrap()
+        .items;
         assert_eq!(result.len(), 1);
         assert_eq!(result[0].facts, "some".to_string());
+        assert_eq!(result[0].case_number, criminal.case_number);
 
-        criminal::delete(&db, &criminal.account, &criminal.kind).unwrap();
-        let result =
-            criminal::search(&db, criminal::CriminalSearch::new("%", "%", "%"), 200).unwrap();
+        criminal::delete(&db, "test", criminal.id).unwrap();
+        let result = criminal::search(
+            &db,
+            criminal::CriminalSearch::new("%", "%", "%"),
+            &Sort::default(),
+            Paging::default(),
+        )
+        .unwrap()
+        .items;
         assert_eq!(result.len(), 0);
+
+        // purged ids and case numbers are not reused, given ones are ignored
+        let third = criminal::add(&db, "test", &second).unwrap();
+        assert_eq!(third.id, second.id + 1);
+        assert_eq!(third.case_number, format!("{year}-0003"));
+        let fourth = criminal::add(
+            &db,
+            "test",
+            &Criminal {
+                id: 4242,
+                case_number: "whatever".into(),
+                ..second
+            },
+        )
+        .unwrap();
+        assert_eq!(fourth.id, third.id + 1);
+        assert_eq!(fourth.case_number, format!("{year}-0004"));
+    }
+
+    #[test]
+    fn migration() {
+        let db = Database::memory().unwrap();
+        db.con.execute_batch(CREATE_TABLES).unwrap();
+        db.con
+            .execute_batch(
+                "insert into user values ('foo', 'Foo', 'Bar', 'Demo'); \
+                insert into criminal (account, kind, accuser, police_consultant, \
+                    lawyer_culprit, lawyer_accuser, facts, time_of_crime, \
+                    location_of_crime, note, verdict) values \
+                    ('foo', 'Theft', '', '', '', '', 'a cake', '', '', '', ''), \
+                    ('foo', 'Fraud', '', '', '', '', 'a cheque', '', '', '', '');",
+            )
+            .unwrap();
+        // audited before the cases had ids
+        migrate_to(&db, 7).unwrap();
+        db.con
+            .execute(
+                "insert into audit (time, actor, entity, key, operation) \
+                values ('2023-06-01T00:00:00Z', 'test', 'criminal', 'foo/Theft', 'insert')",
+                [],
+            )
+            .unwrap();
+        migrate(&db).unwrap();
+
+        let year = Local::now().year();
+        let cases = criminal::of_account(&db, "foo").unwrap();
+        let keys = cases
+            .iter()
+            .map(|c| (c.id, c.case_number.as_str(), c.kind.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            keys,
+            [
+                (2, format!("{year}-0002").as_str(), "Fraud"),
+                (1, format!("{year}-0001").as_str(), "Theft")
+            ]
+        );
+        assert_eq!(criminal::history(&db, 1).unwrap()[0].item, cases[1]);
+        let hits = criminal::search_text(&db, "cheque", &Sort::default(), Paging::default())
+            .unwrap()
+            .items;
+        assert_eq!(hits[0].item, cases[0]);
+        let audit = audit::search(
+            &db,
+            AuditSearch {
+                entities: &[Entity::Criminal],
+                key: Some("foo/1"),
+                ..Default::default()
+            },
+            10,
+        )
+        .unwrap();
+        assert_eq!(audit.len(), 1);
+        assert_eq!(audit[0].key, "foo/1");
     }
 }
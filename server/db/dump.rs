@@ -0,0 +1,362 @@
+use std::{borrow::Cow, fs, io::Read, path::Path};
+
+use chrono::{DateTime, Utc};
+use rusqlite::{params_from_iter, ToSql};
+use serde::{Deserialize, Serialize};
+
+use crate::db::criminal::Criminal;
+use crate::db::hearing::{self, Hearing};
+use crate::db::login::Login;
+use crate::db::project::{self, DBIter, Database, Error, FromRow, Result, VERSION};
+use crate::db::repository::Repository;
+use crate::db::role::{self, Role, RoleKind};
+use crate::db::status::CaseStatus;
+use crate::db::user::User;
+use crate::db::workless::Workless;
+
+/// Contents of the whole database as human-readable archive.
+///
+/// The audit trail, the history and the status changes of the cases
+/// are not part of the dump.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct Dump {
+    /// Schema version of the dumped database
+    pub version: u32,
+    /// Missing in dumps of version 6 and older
+    #[serde(default)]
+    pub roles: Vec<Role>,
+    pub users: Vec<Entry<User>>,
+    pub workless: Vec<Entry<Workless>>,
+    pub criminals: Vec<Entry<Criminal>>,
+    /// Missing in dumps of version 9 and older
+    #[serde(default)]
+    pub hearings: Vec<Hearing>,
+    /// Logins with their password hashes
+    pub logins: Vec<Login>,
+}
+
+/// Dumped record, which may be in the trash bin.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct Entry<T> {
+    #[serde(flatten)]
+    pub item: T,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deleted_at: Option<DateTime<Utc>>,
+}
+
+impl<T: FromRow> FromRow for Entry<T> {
+    fn from_row(row: &rusqlite::Row) -> rusqlite::Result<Self> {
+        Ok(Entry {
+            item: T::from_row(row)?,
+            deleted_at: row.get("deleted_at")?,
+        })
+    }
+}
+
+fn all<T: FromRow>(db: &Database, sql: &str) -> Result<Vec<T>> {
+    let mut stmt = db.con.prepare(sql)?;
+    let rows = stmt.query([])?;
+    DBIter::new(rows).collect()
+}
+
+/// Returns every record of the database, including the trash bin.
+pub fn dump(db: &Database) -> Result<Dump> {
+    Ok(Dump {
+        version: project::version(db)?,
+        roles: all(db, "select * from role order by name")?,
+        users: all(db, "select * from user order by account")?,
+        workless: all(
+            db,
+            "select * from workless order by account, old_company, date_of_dismiss",
+        )?,
+        criminals: all(db, "select * from criminal order by id")?,
+        hearings: all(db, "select * from hearing order by id")?,
+        logins: all(db, "select * from login order by user")?,
+    })
+}
+
+/// Creates a new database at `path` with the contents of the JSON dump.
+///
+/// Dumps of older versions are accepted, as long as their records
+/// can still be read. On failure, the new database is removed again.
+pub fn load(path: &Path, key: Option<&str>, reader: impl Read) -> Result<Database> {
+    let dump: Dump = serde_json::from_reader(reader)?;
+    if dump.version > VERSION {
+        return Err(Error::UnsupportedProjectVersion);
+    }
+
+    let db = Database::create(Cow::from(path), key)?;
+    let result = project::create(&db).and_then(|_| db.transaction(|db| insert(db, &dump)));
+    if let Err(e) = result {
+        drop(db);
+        fs::remove_file(path).ok();
+        return Err(e);
+    }
+    Ok(db)
+}
+
+/// Inserts the records as they are, without validation or audit entries.
+fn insert(db: &Database, dump: &Dump) -> Result<()> {
+    // the parents and teachers are set last, they may be inserted later
+    for role in &dump.roles {
+        insert_role(db, role)?;
+    }
+    // older dumps have no roles, they are added like by the migration
+    let users = dump
+        .users
+        .iter()
+        .map(|Entry { item, deleted_at }| {
+            let role = match role::resolve(db, &item.role) {
+                Err(Error::UnknownRole) => {
+                    let role = Role::new(&role::canonical(&item.role), RoleKind::guess(&item.role));
+                    insert_role(db, &role)?;
+                    role.name
+                }
+                result => result?,
+            };
+            Ok(Entry {
+                item: User {
+                    role,
+                    ..item.clone()
+                },
+                deleted_at: *deleted_at,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    insert_entries(db, &users)?;
+    insert_entries(db, &dump.workless)?;
+    // cases of dumps before version 8 have no ids yet,
+    // they are assigned one by one to count the inserted cases
+    for Entry { item, deleted_at } in &dump.criminals {
+        let mut item = item.clone().assign(db)?;
+        // cases of dumps before version 9 have no status, it is set like by the migration
+        if dump.version < 9 && !item.verdict.trim().is_empty() {
+            item.status = CaseStatus::Verdict;
+        }
+        let entry = Entry {
+            item,
+            deleted_at: *deleted_at,
+        };
+        insert_entries(db, &[entry])?;
+    }
+    // the status changes start again with the current status, like by the migration
+    db.con.execute(
+        "insert into criminal_status select \
+        id, null, status, 'system', strftime('%Y-%m-%d %H:%M:%f+00:00', 'now') \
+        from criminal",
+        [],
+    )?;
+    for hearing in &dump.hearings {
+        hearing::insert(db, hearing)?;
+    }
+    for role in &dump.roles {
+        db.con.execute(
+            "update role set parent=?, teacher=? where name=?",
+            rusqlite::params![role.parent, role.teacher, role.name],
+        )?;
+    }
+    for login in &dump.logins {
+        db.con.execute(
+            "insert into login (user, hash, salt, access_user, access_workless, access_criminal) \
+            values (?, ?, ?, ?, ?, ?)",
+            rusqlite::params![
+                login.user,
+                login.hash,
+                login.salt,
+                login.access_user,
+                login.access_workless,
+                login.access_criminal
+            ],
+        )?;
+    }
+    Ok(())
+}
+
+/// Inserts the role without its parent and teacher.
+fn insert_role(db: &Database, role: &Role) -> Result<()> {
+    db.con.execute(
+        "insert into role (name, display_name, kind) values (?, ?, ?)",
+        rusqlite::params![role.name, role.display_name, role.kind],
+    )?;
+    Ok(())
+}
+
+fn insert_entries<T: Repository>(db: &Database, entries: &[Entry<T>]) -> Result<()> {
+    let sql = format!(
+        "insert into {} ({}, deleted_at) values ({})",
+        T::TABLE,
+        T::COLUMNS.join(", "),
+        vec!["?"; T::COLUMNS.len() + 1].join(", ")
+    );
+    for Entry { item, deleted_at } in entries {
+        let params = item.values().into_iter().chain([deleted_at as &dyn ToSql]);
+        db.con
+            .execute(&sql, params_from_iter(params))
+            .map_err(T::map_err)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Datelike, Local, NaiveDate};
+
+    use crate::db::criminal::{self, Criminal};
+    use crate::db::dump;
+    use crate::db::hearing::{self, Hearing};
+    use crate::db::login::{self, NewLogin, Permission};
+    use crate::db::project::{create, Database, Error, VERSION};
+    use crate::db::role::{self, Role, RoleKind};
+    use crate::db::status::CaseStatus;
+    use crate::db::user::{self, OnDelete, User};
+    use crate::db::workless::{self, Workless};
+
+    #[test]
+    fn dump_load() {
+        let db = Database::memory().unwrap();
+        create(&db).unwrap();
+
+        let user = User {
+            account: "foo.bar".into(),
+            forename: "Foo".into(),
+            surname: "Bar".into(),
+            role: "Demo".into(),
+        };
+        let class = Role {
+            parent: Some("Jahrgang 5".into()),
+            teacher: Some("foo.bar".into()),
+            ..Role::new("Demo", RoleKind::Class)
+        };
+        role::add(&db, "test", &Role::new("Jahrgang 5", RoleKind::Class)).unwrap();
+        role::add(&db, "test", &Role::new("Demo", RoleKind::Class)).unwrap();
+        user::add(&db, "test", &user).unwrap();
+        role::update(&db, "test", "Demo", &class).unwrap();
+        let trashed = User {
+            account: "baz.bar".into(),
+            ..user.clone()
+        };
+        user::add(&db, "test", &trashed).unwrap();
+        let workless = Workless {
+            account: user.account.clone(),
+            old_company: "bars".into(),
+            date_of_dismiss: NaiveDate::from_ymd_opt(2023, 6, 1).unwrap(),
+            ..Default::default()
+        };
+        workless::add(&db, "test", &workless).unwrap();
+        let criminal = Criminal {
+            account: trashed.account.clone(),
+            kind: "Theft".into(),
+            ..Default::default()
+        };
+        let criminal = criminal::add(&db, "test", &criminal).unwrap();
+        criminal::add(
+            &db,
+            "test",
+            &Criminal {
+                kind: "Fraud".into(),
+                verdict: "Guilty".into(),
+                ..criminal.clone()
+            },
+        )
+        .unwrap();
+        let day = NaiveDate::from_ymd_opt(2026, 7, 1).unwrap();
+        let hearing = Hearing {
+            criminal: criminal.id,
+            starts_at: day.and_hms_opt(9, 0, 0).unwrap(),
+            ends_at: day.and_hms_opt(10, 0, 0).unwrap(),
+            room: "Aula".into(),
+            judge: "Judge".into(),
+            ..Default::default()
+        };
+        hearing::add(&db, "test", &hearing).unwrap();
+        user::delete(&db, "test", &trashed.account, OnDelete::Cascade).unwrap();
+        login::add(
+            &db,
+            "test",
+            NewLogin {
+                user: "foo".into(),
+                password: "bar".into(),
+                access_user: Permission::Write,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        let dumped = dump::dump(&db).unwrap();
+        assert_eq!(dumped.version, VERSION);
+        assert!(dumped.criminals[0].deleted_at.is_some());
+        assert_eq!(dumped.hearings.len(), 1);
+        let json = serde_json::to_string(&dumped).unwrap();
+        assert!(!json.contains("\"bar\""));
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("schiller-db.db");
+        let loaded = dump::load(&path, None, json.as_bytes()).unwrap();
+        assert_eq!(dump::dump(&loaded).unwrap(), dumped);
+        assert_eq!(user::fetch(&loaded, &user.account).unwrap(), user);
+        assert_eq!(user::trash(&loaded, 10).unwrap()[0].item, trashed);
+        assert!(login::fetch(&loaded, "foo").unwrap().check_password("bar"));
+        assert_eq!(role::fetch(&loaded, "Demo").unwrap(), class);
+
+        // older dumps have no roles, they are added for the users
+        let old = serde_json::to_string(&dump::Dump {
+            roles: Vec::new(),
+            ..dump::dump(&db).unwrap()
+        })
+        .unwrap();
+        let old_path = dir.path().join("old.db");
+        let loaded = dump::load(&old_path, None, old.as_bytes()).unwrap();
+        assert_eq!(
+            role::fetch(&loaded, "Demo").unwrap(),
+            Role::new("Demo", RoleKind::Other)
+        );
+
+        // cases of dumps before version 8 have no ids and case numbers
+        let mut old = serde_json::to_value(dump::Dump {
+            hearings: Vec::new(),
+            ..dump::dump(&db).unwrap()
+        })
+        .unwrap();
+        old["version"] = 7.into();
+        for case in old["criminals"].as_array_mut().unwrap() {
+            let case = case.as_object_mut().unwrap();
+            case.remove("id");
+            case.remove("case_number");
+            case.remove("status");
+        }
+        let old_path = dir.path().join("old-cases.db");
+        let loaded = dump::load(&old_path, None, old.to_string().as_bytes()).unwrap();
+        let year = Local::now().year();
+        let cases = dump::dump(&loaded)
+            .unwrap()
+            .criminals
+            .into_iter()
+            .map(|c| (c.item.id, c.item.case_number, c.item.status))
+            .collect::<Vec<_>>();
+        // and cases with a verdict start with the verdict status
+        assert_eq!(
+            cases,
+            [
+                (1, format!("{year}-0001"), CaseStatus::Reported),
+                (2, format!("{year}-0002"), CaseStatus::Verdict)
+            ]
+        );
+
+        // the database has to be new
+        assert_eq!(
+            dump::load(&path, None, json.as_bytes()).unwrap_err(),
+            Error::FileOpen
+        );
+        let newer = json.replacen(
+            &format!("\"version\":{VERSION}"),
+            &format!("\"version\":{}", VERSION + 1),
+            1,
+        );
+        let other = dir.path().join("other.db");
+        assert_eq!(
+            dump::load(&other, None, newer.as_bytes()).unwrap_err(),
+            Error::UnsupportedProjectVersion
+        );
+        assert!(!other.exists());
+    }
+}
@@ -0,0 +1,117 @@
+use std::{borrow::Cow, fs, path::Path};
+
+use rusqlite::DatabaseName;
+
+use crate::db::project::{version, Database, Error, Result};
+
+/// Encrypts the plaintext database at `path` with `key`.
+///
+/// The database is exported into an encrypted copy,
+/// which then replaces the original file.
+pub fn encrypt(path: &Path, key: &str) -> Result<()> {
+    if !cfg!(feature = "sqlcipher") {
+        return Err(Error::EncryptionUnsupported);
+    }
+    let encrypted = path.with_extension("encrypted");
+    if encrypted.exists() {
+        return Err(Error::FileOpen);
+    }
+
+    let (db, _) = Database::open(Cow::from(path), None)?;
+    // the connection is not allowed to create the attached file
+    drop(Database::create(Cow::from(&encrypted), Some(key))?);
+    db.con.execute(
+        "attach database ? as encrypted key ?",
+        [&*encrypted.to_string_lossy(), key],
+    )?;
+    let result = db
+        .con
+        .query_row("select sqlcipher_export('encrypted')", [], |_| Ok(()))
+        .map_err(Error::from)
+        .and_then(|_| {
+            // the export does not include the schema version
+            Ok(db.con.pragma_update(
+                Some(DatabaseName::Attached("encrypted")),
+                "user_version",
+                version(&db)?,
+            )?)
+        });
+    db.con.execute_batch("detach database encrypted")?;
+    drop(db);
+
+    if let Err(e) = result {
+        fs::remove_file(&encrypted).ok();
+        return Err(e);
+    }
+    fs::rename(&encrypted, path)?;
+    Ok(())
+}
+
+/// Replaces the key of the encrypted database.
+///
+/// Plaintext databases have to be encrypted with [`encrypt`] instead.
+/// Other connections have to be reopened with the new key.
+pub fn rekey(db: &Database, key: &str) -> Result<()> {
+    if !cfg!(feature = "sqlcipher") {
+        return Err(Error::EncryptionUnsupported);
+    }
+    if db.key().is_none() {
+        return Err(Error::Arguments);
+    }
+    db.con.pragma_update(None, "rekey", key)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+
+    use crate::db::encryption;
+    use crate::db::project::{create, Database, Error};
+    use crate::db::role::{self, Role, RoleKind};
+    use crate::db::user::{self, User};
+
+    #[test]
+    fn encrypt_rekey() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("schiller-db.db");
+        let db = Database::create(Cow::from(&path), None).unwrap();
+        create(&db).unwrap();
+        role::add(&db, "test", &Role::new("Demo", RoleKind::Other)).unwrap();
+        let user = User {
+            account: "foo.bar".into(),
+            forename: "Foo".into(),
+            surname: "Bar".into(),
+            role: "Demo".into(),
+        };
+        user::add(&db, "test", &user).unwrap();
+        drop(db);
+
+        if !cfg!(feature = "sqlcipher") {
+            assert_eq!(
+                encryption::encrypt(&path, "secret"),
+                Err(Error::EncryptionUnsupported)
+            );
+            assert_eq!(
+                Database::open(Cow::from(&path), Some("secret")).unwrap_err(),
+                Error::EncryptionUnsupported
+            );
+            return;
+        }
+
+        encryption::encrypt(&path, "secret").unwrap();
+        assert!(Database::open(Cow::from(&path), None).is_err());
+        assert_eq!(
+            Database::open(Cow::from(&path), Some("wrong")).unwrap_err(),
+            Error::InvalidKey
+        );
+        let (db, migrated) = Database::open(Cow::from(&path), Some("secret")).unwrap();
+        assert!(!migrated);
+        assert_eq!(user::fetch(&db, &user.account).unwrap(), user);
+
+        encryption::rekey(&db, "other").unwrap();
+        drop(db);
+        let (db, _) = Database::open(Cow::from(&path), Some("other")).unwrap();
+        assert_eq!(user::fetch(&db, &user.account).unwrap(), user);
+    }
+}
@@ -0,0 +1,17 @@
+use crate::db::project::{Error, Result};
+use crate::db::repository::Repository;
+
+/// Writes the `items` as CSV table with a header row.
+///
+/// The columns are named like the fields, so the table can be imported again.
+/// The header is written even without items.
+pub fn csv<T: Repository>(items: &[T]) -> Result<Vec<u8>> {
+    let mut writer = csv::WriterBuilder::new()
+        .has_headers(false)
+        .from_writer(Vec::new());
+    writer.write_record(T::COLUMNS)?;
+    for item in items {
+        writer.serialize(item)?;
+    }
+    writer.into_inner().map_err(|e| Error::from(e.into_error()))
+}
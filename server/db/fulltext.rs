@@ -0,0 +1,180 @@
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::db::criminal::Criminal;
+use crate::db::project::{Database, FromRow, Result};
+use crate::db::user::User;
+
+/// Marks the matched terms in a [`Hit::snippet`].
+pub const HIGHLIGHT: (&str, &str) = ("<mark>", "</mark>");
+
+/// Result of a full-text search.
+#[derive(Serialize, Debug, Clone, ToSchema)]
+#[aliases(UserHit = Hit<User>, CriminalHit = Hit<Criminal>)]
+pub struct Hit<T> {
+    pub item: T,
+    /// Relevance of the hit, lower is better.
+    pub rank: f64,
+    /// Excerpt of the best matching column with highlighted terms.
+    pub snippet: String,
+}
+
+impl<T: FromRow> FromRow for Hit<T> {
+    fn from_row(row: &rusqlite::Row) -> rusqlite::Result<Self> {
+        Ok(Hit {
+            item: T::from_row(row)?,
+            rank: row.get("rank")?,
+            snippet: row.get("snippet")?,
+        })
+    }
+}
+
+/// Converts the search text into an FTS5 query.
+///
+/// Every word is quoted so that no FTS5 syntax can be injected,
+/// and matched as prefix. All words have to be found.
+pub fn query(text: &str) -> Option<String> {
+    let words = text
+        .split_whitespace()
+        .map(|word| format!("\"{}\"*", word.replace('"', "\"\"")))
+        .collect::<Vec<_>>();
+    (!words.is_empty()).then(|| words.join(" "))
+}
+
+/// Version 3: Full-text indices for users and criminal records.
+///
+/// The indices are kept in sync by triggers.
+pub fn migrate(db: &Database) -> Result<()> {
+    const MIGRATION: &str = "\
+    create virtual table user_fts using fts5( \
+        account, forename, surname, role, \
+        tokenize = 'unicode61 remove_diacritics 2'); \
+    insert into user_fts select account, forename, surname, role from user; \
+    \
+    create trigger user_fts_insert after insert on user begin \
+        insert into user_fts values (new.account, new.forename, new.surname, new.role); \
+    end; \
+    create trigger user_fts_update after update on user begin \
+        update user_fts set account=new.account, forename=new.forename, \
+            surname=new.surname, role=new.role \
+        where account=old.account; \
+    end; \
+    create trigger user_fts_delete after delete on user begin \
+        delete from user_fts where account=old.account; \
+    end; \
+    \
+    create virtual table criminal_fts using fts5( \
+        account, kind, accuser, police_consultant, lawyer_culprit, lawyer_accuser, \
+        facts, time_of_crime, location_of_crime, note, verdict, \
+        tokenize = 'unicode61 remove_diacritics 2'); \
+    insert into criminal_fts select \
+        account, kind, accuser, police_consultant, lawyer_culprit, lawyer_accuser, \
+        facts, time_of_crime, location_of_crime, note, verdict \
+        from criminal; \
+    \
+    create trigger criminal_fts_insert after insert on criminal begin \
+        insert into criminal_fts values (new.account, new.kind, new.accuser, \
+            new.police_consultant, new.lawyer_culprit, new.lawyer_accuser, new.facts, \
+            new.time_of_crime, new.location_of_crime, new.note, new.verdict); \
+    end; \
+    create trigger criminal_fts_update after update on criminal begin \
+        update criminal_fts set account=new.account, kind=new.kind, accuser=new.accuser, \
+            police_consultant=new.police_consultant, lawyer_culprit=new.lawyer_culprit, \
+            lawyer_accuser=new.lawyer_accuser, facts=new.facts, \
+            time_of_crime=new.time_of_crime, location_of_crime=new.location_of_crime, \
+            note=new.note, verdict=new.verdict \
+        where account=old.account and kind=old.kind; \
+    end; \
+    create trigger criminal_fts_delete after delete on criminal begin \
+        delete from criminal_fts where account=old.account and kind=old.kind; \
+    end; \
+    ";
+    db.con.execute_batch(MIGRATION)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::db::criminal::{self, Criminal};
+    use crate::db::fulltext;
+    use crate::db::page::Paging;
+    use crate::db::project::{create, Database};
+    use crate::db::role::{self, Role, RoleKind};
+    use crate::db::sort::Sort;
+    use crate::db::user::{self, User};
+
+    #[test]
+    fn query() {
+        assert_eq!(fulltext::query("  "), None);
+        assert_eq!(
+            fulltext::query("caf\"e OR"),
+            Some("\"caf\"\"e\"* \"OR\"*".into())
+        );
+    }
+
+    #[test]
+    fn search_text() {
+        let db = Database::memory().unwrap();
+        create(&db).unwrap();
+        role::add(&db, "test", &Role::new("Demo", RoleKind::Other)).unwrap();
+
+        let user = User {
+            account: "foo.bar".into(),
+            forename: "Jürgen".into(),
+            surname: "Bar".into(),
+            role: "Demo".into(),
+        };
+        user::add(&db, "test", &user).unwrap();
+
+        let result = user::search_text(&db, "jurg", &Sort::default(), Paging::default())
+            .unwrap()
+            .items;
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].item, user);
+        assert_eq!(result[0].snippet, "<mark>Jürgen</mark>");
+
+        let criminal = Criminal {
+            account: user.account.clone(),
+            kind: "Theft".into(),
+            facts: "Stole a cake in the cafeteria".into(),
+            ..Default::default()
+        };
+        let criminal = criminal::add(&db, "test", &criminal).unwrap();
+        let result = criminal::search_text(&db, "cafeteria", &Sort::default(), Paging::default())
+            .unwrap()
+            .items;
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].item, criminal);
+
+        // renaming the user updates both indices
+        user::update(
+            &db,
+            "test",
+            &user.account,
+            &User {
+                account: "bar.baz".into(),
+                ..user.clone()
+            },
+        )
+        .unwrap();
+        let result = criminal::search_text(&db, "cafeteria", &Sort::default(), Paging::default())
+            .unwrap()
+            .items;
+        assert_eq!(result[0].item.account, "bar.baz");
+        assert_eq!(
+            user::search_text(&db, "foo", &Sort::default(), Paging::default())
+                .unwrap()
+                .items
+                .len(),
+            0
+        );
+
+        criminal::delete(&db, "test", criminal.id).unwrap();
+        assert!(
+            criminal::search_text(&db, "cafeteria", &Sort::default(), Paging::default())
+                .unwrap()
+                .items
+                .is_empty()
+        );
+    }
+}
@@ -0,0 +1,594 @@
+use chrono::{NaiveDate, NaiveDateTime, Utc};
+use ics::properties::{Description, DtEnd, DtStart, Location, Summary};
+use ics::{escape_text, Event, ICalendar};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::db::audit::{self, Audited, Entity};
+use crate::db::criminal;
+use crate::db::project::{DBIter, Database, Error, FieldError, FromRow, Result};
+use crate::db::status::CaseStatus;
+use crate::db::validate::{self, Field, Validate, Value};
+
+/// Data object for a court hearing of a criminal case.
+///
+/// The times are local, like the ones of the project week.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default, ToSchema)]
+pub struct Hearing {
+    /// Assigned when the hearing is scheduled
+    #[serde(default)]
+    pub id: i64,
+    /// Id of the case
+    pub criminal: i64,
+    pub starts_at: NaiveDateTime,
+    pub ends_at: NaiveDateTime,
+    pub room: String,
+    pub judge: String,
+    /// Taken from the case if empty
+    #[serde(default)]
+    pub lawyer_culprit: String,
+    /// Taken from the case if empty
+    #[serde(default)]
+    pub lawyer_accuser: String,
+    #[serde(default)]
+    pub note: String,
+}
+
+impl Hearing {
+    /// Persons that attend the hearing, with the names of their fields.
+    fn persons(&self) -> impl Iterator<Item = (&'static str, &str)> {
+        [
+            ("judge", self.judge.as_str()),
+            ("lawyer_culprit", self.lawyer_culprit.as_str()),
+            ("lawyer_accuser", self.lawyer_accuser.as_str()),
+        ]
+        .into_iter()
+        .filter(|(_, name)| !name.trim().is_empty())
+    }
+
+    /// Whether the hearing takes place at the same time as the `other` one.
+    fn overlaps(&self, other: &Hearing) -> bool {
+        self.starts_at < other.ends_at && other.starts_at < self.ends_at
+    }
+}
+
+impl Validate for Hearing {
+    const FIELDS: &'static [Field] = &[
+        Field::new("starts_at", validate::DATE),
+        Field::new("ends_at", validate::DATE),
+        Field::new("room", validate::NAME),
+        Field::new("judge", validate::NAME),
+        Field::new("lawyer_culprit", validate::SHORT_TEXT),
+        Field::new("lawyer_accuser", validate::SHORT_TEXT),
+        Field::new("note", validate::LONG_TEXT),
+    ];
+    fn fields(&self) -> Vec<Value<'_>> {
+        vec![
+            Value::Date(self.starts_at.date()),
+            Value::Date(self.ends_at.date()),
+            Value::Text(&self.room),
+            Value::Text(&self.judge),
+            Value::Text(&self.lawyer_culprit),
+            Value::Text(&self.lawyer_accuser),
+            Value::Text(&self.note),
+        ]
+    }
+    fn invalid(details: Vec<FieldError>) -> Error {
+        Error::InvalidHearing(details)
+    }
+}
+
+impl Audited for Hearing {
+    const ENTITY: Entity = Entity::Hearing;
+    fn key(&self) -> String {
+        format!("{}/{}", self.criminal, self.id)
+    }
+}
+
+impl FromRow for Hearing {
+    fn from_row(row: &rusqlite::Row) -> rusqlite::Result<Hearing> {
+        Ok(Hearing {
+            id: row.get("id")?,
+            criminal: row.get("criminal")?,
+            starts_at: row.get("starts_at")?,
+            ends_at: row.get("ends_at")?,
+            room: row.get("room")?,
+            judge: row.get("judge")?,
+            lawyer_culprit: row.get("lawyer_culprit")?,
+            lawyer_accuser: row.get("lawyer_accuser")?,
+            note: row.get("note")?,
+        })
+    }
+}
+
+/// Hearing on the docket, with the case it belongs to.
+#[derive(Serialize, Debug, Clone, PartialEq, ToSchema)]
+pub struct DocketEntry {
+    pub hearing: Hearing,
+    pub case_number: String,
+    /// Account of the accused
+    pub account: String,
+    pub kind: String,
+    pub status: CaseStatus,
+}
+
+impl FromRow for DocketEntry {
+    fn from_row(row: &rusqlite::Row) -> rusqlite::Result<DocketEntry> {
+        Ok(DocketEntry {
+            hearing: Hearing::from_row(row)?,
+            case_number: row.get("case_number")?,
+            account: row.get("account")?,
+            kind: row.get("kind")?,
+            status: row.get("status")?,
+        })
+    }
+}
+
+const SELECT: &str = "select hearing.id, hearing.criminal, hearing.starts_at, \
+    hearing.ends_at, hearing.room, hearing.judge, hearing.lawyer_culprit, \
+    hearing.lawyer_accuser, hearing.note, \
+    criminal.case_number, criminal.account, criminal.kind, criminal.status \
+    from hearing \
+    join criminal on criminal.id = hearing.criminal";
+
+/// Returns the hearing with the given `id`.
+pub fn fetch(db: &Database, id: i64) -> Result<Hearing> {
+    Ok(db.con.query_row(
+        "select id, criminal, starts_at, ends_at, room, judge, lawyer_culprit, \
+        lawyer_accuser, note from hearing where id=?",
+        [id],
+        Hearing::from_row,
+    )?)
+}
+
+/// Returns the hearings of the case with the `id`, the earliest first.
+pub fn of_case(db: &Database, id: i64) -> Result<Vec<Hearing>> {
+    criminal::fetch(db, id)?;
+    let mut stmt = db.con.prepare(
+        "select id, criminal, starts_at, ends_at, room, judge, lawyer_culprit, \
+        lawyer_accuser, note from hearing \
+        where criminal=? \
+        order by starts_at asc, id asc",
+    )?;
+    let rows = stmt.query([id])?;
+    DBIter::new(rows).collect()
+}
+
+/// Returns the hearings from the day `from` to the day `to` (inclusive),
+/// optionally only in the `room`, ordered by room and time.
+///
+/// Hearings of cases in the trash bin are not on the docket.
+pub fn docket(
+    db: &Database,
+    from: NaiveDate,
+    to: NaiveDate,
+    room: Option<&str>,
+) -> Result<Vec<DocketEntry>> {
+    let (Some(start), Some(end)) = (from.and_hms_opt(0, 0, 0), to.succ_opt()) else {
+        return Err(Error::InvalidDate);
+    };
+    let mut stmt = db.con.prepare(&format!(
+        "{SELECT} \
+        where criminal.deleted_at is null \
+        and hearing.starts_at >= ?1 and hearing.starts_at < ?2 \
+        and (?3 is null or hearing.room = ?3 collate nocase) \
+        order by hearing.room collate nocase asc, hearing.starts_at asc, hearing.id asc"
+    ))?;
+    let rows = stmt.query(rusqlite::params![
+        start,
+        end.and_hms_opt(0, 0, 0),
+        room.map(str::trim)
+    ])?;
+    DBIter::new(rows).collect()
+}
+
+/// Copy of the hearing as it is stored, with trimmed texts
+/// and the lawyers of the case, if none are given.
+fn normalized(db: &Database, hearing: &Hearing) -> Result<Hearing> {
+    let case = criminal::fetch(db, hearing.criminal)?;
+    let or_case = |lawyer: &str, of_case: &str| {
+        if lawyer.trim().is_empty() {
+            of_case.trim().to_string()
+        } else {
+            lawyer.trim().to_string()
+        }
+    };
+    Ok(Hearing {
+        id: hearing.id,
+        criminal: case.id,
+        starts_at: hearing.starts_at,
+        ends_at: hearing.ends_at,
+        room: hearing.room.trim().into(),
+        judge: hearing.judge.trim().into(),
+        lawyer_culprit: or_case(&hearing.lawyer_culprit, &case.lawyer_culprit),
+        lawyer_accuser: or_case(&hearing.lawyer_accuser, &case.lawyer_accuser),
+        note: hearing.note.trim().into(),
+    })
+}
+
+/// Fails if the hearing does not end after it starts, or with
+/// [`Error::DoubleBooked`] if its room or one of its persons is booked
+/// by another hearing at the same time.
+///
+/// Rooms and persons are compared regardless of the case.
+fn check(db: &Database, hearing: &Hearing) -> Result<()> {
+    if hearing.ends_at <= hearing.starts_at {
+        return Err(Error::InvalidHearing(vec![FieldError::new(
+            "ends_at",
+            "must be after the start",
+        )]));
+    }
+    let mut stmt = db.con.prepare(&format!(
+        "{SELECT} \
+        where criminal.deleted_at is null and hearing.id != ?1 \
+        and hearing.starts_at < ?3 and ?2 < hearing.ends_at \
+        order by hearing.starts_at asc, hearing.id asc"
+    ))?;
+    let rows = stmt.query(rusqlite::params![
+        hearing.id,
+        hearing.starts_at,
+        hearing.ends_at
+    ])?;
+    let others = DBIter::new(rows).collect::<Result<Vec<DocketEntry>>>()?;
+
+    let mut details = Vec::new();
+    for other in others.iter().filter(|o| hearing.overlaps(&o.hearing)) {
+        let booked = format!(
+            "already booked by the hearing {} of {} from {} to {}",
+            other.hearing.id,
+            other.case_number,
+            other.hearing.starts_at.format("%Y-%m-%d %H:%M"),
+            other.hearing.ends_at.format("%Y-%m-%d %H:%M")
+        );
+        if hearing.room.eq_ignore_ascii_case(&other.hearing.room) {
+            details.push(FieldError::new("room", booked.clone()));
+        }
+        for (field, person) in hearing.persons() {
+            if other
+                .hearing
+                .persons()
+                .any(|(_, p)| p.trim().to_lowercase() == person.trim().to_lowercase())
+            {
+                details.push(FieldError::new(field, booked.clone()));
+            }
+        }
+    }
+    if details.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::DoubleBooked(details))
+    }
+}
+
+/// Inserts the hearing as it is, a new id is assigned if it has none.
+pub(crate) fn insert(db: &Database, hearing: &Hearing) -> Result<i64> {
+    db.con.execute(
+        "insert into hearing (id, criminal, starts_at, ends_at, room, judge, \
+        lawyer_culprit, lawyer_accuser, note) values (?, ?, ?, ?, ?, ?, ?, ?, ?)",
+        rusqlite::params![
+            (hearing.id != 0).then_some(hearing.id),
+            hearing.criminal,
+            hearing.starts_at,
+            hearing.ends_at,
+            hearing.room,
+            hearing.judge,
+            hearing.lawyer_culprit,
+            hearing.lawyer_accuser,
+            hearing.note,
+        ],
+    )?;
+    Ok(db.con.last_insert_rowid())
+}
+
+/// Schedules a new hearing and returns it with its id.
+pub fn add(db: &Database, actor: &str, hearing: &Hearing) -> Result<Hearing> {
+    hearing.validate()?;
+    db.transaction(|db| {
+        let hearing = Hearing {
+            id: 0,
+            ..normalized(db, hearing)?
+        };
+        check(db, &hearing)?;
+        let hearing = fetch(db, insert(db, &hearing)?)?;
+        audit::insert(db, actor, &hearing)?;
+        Ok(hearing)
+    })
+}
+
+/// Reschedules the hearing, it stays with its case.
+pub fn update(db: &Database, actor: &str, id: i64, hearing: &Hearing) -> Result<()> {
+    hearing.validate()?;
+    db.transaction(|db| {
+        let before = fetch(db, id)?;
+        let hearing = normalized(
+            db,
+            &Hearing {
+                id,
+                criminal: before.criminal,
+                ..hearing.clone()
+            },
+        )?;
+        check(db, &hearing)?;
+        db.con.execute(
+            "update hearing set starts_at=?, ends_at=?, room=?, judge=?, \
+            lawyer_culprit=?, lawyer_accuser=?, note=? where id=?",
+            rusqlite::params![
+                hearing.starts_at,
+                hearing.ends_at,
+                hearing.room,
+                hearing.judge,
+                hearing.lawyer_culprit,
+                hearing.lawyer_accuser,
+                hearing.note,
+                id
+            ],
+        )?;
+        audit::update(db, actor, &before, &fetch(db, id)?)
+    })
+}
+
+/// Cancels the hearing.
+pub fn delete(db: &Database, actor: &str, id: i64) -> Result<()> {
+    db.transaction(|db| {
+        let before = fetch(db, id)?;
+        db.con.execute("delete from hearing where id=?", [id])?;
+        audit::delete(db, actor, &before)
+    })
+}
+
+/// Deletes the hearings of the case `id`, before it is purged.
+pub(crate) fn purge(db: &Database, actor: &str, id: i64) -> Result<()> {
+    let mut stmt = db.con.prepare(
+        "select id, criminal, starts_at, ends_at, room, judge, lawyer_culprit, \
+        lawyer_accuser, note from hearing \
+        where criminal=? \
+        order by id asc",
+    )?;
+    let hearings: Vec<Hearing> = DBIter::new(stmt.query([id])?).collect::<Result<_>>()?;
+    for hearing in hearings {
+        delete(db, actor, hearing.id)?;
+    }
+    Ok(())
+}
+
+/// Writes the hearings as iCalendar events.
+pub fn ics(entries: &[DocketEntry]) -> Result<Vec<u8>> {
+    const LOCAL: &str = "%Y%m%dT%H%M%S";
+    let stamp = Utc::now().format("%Y%m%dT%H%M%SZ").to_string();
+    let mut calendar = ICalendar::new("2.0", "-//schiller-db//Hearings//DE");
+    for entry in entries {
+        let hearing = &entry.hearing;
+        let mut event = Event::new(format!("hearing-{}@schiller-db", hearing.id), &*stamp);
+        event.push(DtStart::new(hearing.starts_at.format(LOCAL).to_string()));
+        event.push(DtEnd::new(hearing.ends_at.format(LOCAL).to_string()));
+        event.push(Summary::new(escape_text(format!(
+            "{} {} ({})",
+            entry.case_number, entry.kind, entry.account
+        ))));
+        event.push(Location::new(escape_text(hearing.room.clone())));
+        let description = [
+            ("Aktenzeichen", &entry.case_number),
+            ("Richter", &hearing.judge),
+            ("Anwalt des Beschuldigten", &hearing.lawyer_culprit),
+            ("Anwalt des Anzeigers", &hearing.lawyer_accuser),
+            ("Kommentar", &hearing.note),
+        ]
+        .into_iter()
+        .filter(|(_, value)| !value.is_empty())
+        .map(|(label, value)| format!("{label}: {value}"))
+        .collect::<Vec<_>>()
+        .join("\n");
+        event.push(Description::new(escape_text(description)));
+        calendar.add_event(event);
+    }
+    let mut data = Vec::new();
+    calendar.write(&mut data)?;
+    Ok(data)
+}
+
+/// Version 10: Criminal cases have hearings, which are scheduled in rooms.
+///
+/// Hearings are removed with their case, when it is purged.
+/// [`purge`] deletes them beforehand to record them in the audit trail.
+pub fn migrate(db: &Database) -> Result<()> {
+    db.con.execute_batch(
+        "create table hearing ( \
+            id integer primary key, \
+            criminal integer not null references criminal(id) on delete cascade, \
+            starts_at text not null, \
+            ends_at text not null, \
+            room text not null, \
+            judge text not null, \
+            lawyer_culprit text not null, \
+            lawyer_accuser text not null, \
+            note text not null); \
+        create index hearing_criminal on hearing(criminal, starts_at); \
+        create index hearing_starts_at on hearing(starts_at);",
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use crate::db::audit::{self, AuditSearch, Entity, Operation};
+    use crate::db::criminal::{self, Criminal};
+    use crate::db::hearing::{self, Hearing};
+    use crate::db::project::{create, Database, Error, FieldError};
+    use crate::db::role::{self, Role, RoleKind};
+    use crate::db::user::{self, User};
+
+    #[test]
+    fn schedule_hearings() {
+        let db = Database::memory().unwrap();
+        create(&db).unwrap();
+        role::add(&db, "test", &Role::new("Demo", RoleKind::Other)).unwrap();
+        let user = User {
+            account: "foo.bar".into(),
+            forename: "Foo".into(),
+            surname: "Bar".into(),
+            role: "Demo".into(),
+        };
+        user::add(&db, "test", &user).unwrap();
+        let theft = Criminal {
+            account: user.account.clone(),
+            kind: "Theft".into(),
+            lawyer_culprit: "Saul".into(),
+            ..Default::default()
+        };
+        let theft = criminal::add(&db, "test", &theft).unwrap();
+        let fraud = Criminal {
+            id: 0,
+            case_number: String::new(),
+            kind: "Fraud".into(),
+            lawyer_culprit: String::new(),
+            ..theft.clone()
+        };
+        let fraud = criminal::add(&db, "test", &fraud).unwrap();
+
+        let day = NaiveDate::from_ymd_opt(2026, 7, 1).unwrap();
+        let at = |h, m| day.and_hms_opt(h, m, 0).unwrap();
+        let first = Hearing {
+            criminal: theft.id,
+            starts_at: at(9, 0),
+            ends_at: at(10, 0),
+            room: " Aula ".into(),
+            judge: "Judge Judy".into(),
+            ..Default::default()
+        };
+        let first = hearing::add(&db, "test", &first).unwrap();
+        assert_eq!(first.room, "Aula");
+        // the lawyers are taken from the case
+        assert_eq!(first.lawyer_culprit, "Saul");
+        assert_eq!(
+            hearing::add(
+                &db,
+                "test",
+                &Hearing {
+                    ends_at: at(9, 0),
+                    ..first.clone()
+                }
+            ),
+            Err(Error::InvalidHearing(vec![FieldError::new(
+                "ends_at",
+                "must be after the start"
+            )]))
+        );
+
+        // the room and the lawyer are booked, regardless of the case
+        let booked = "already booked by the hearing 1 of ".to_string()
+            + &theft.case_number
+            + " from 2026-07-01 09:00 to 2026-07-01 10:00";
+        let second = Hearing {
+            criminal: fraud.id,
+            starts_at: at(9, 30),
+            ends_at: at(10, 30),
+            room: "aula".into(),
+            judge: "Someone Else".into(),
+            lawyer_accuser: "saul".into(),
+            ..Default::default()
+        };
+        assert_eq!(
+            hearing::add(&db, "test", &second),
+            Err(Error::DoubleBooked(vec![
+                FieldError::new("room", booked.clone()),
+                FieldError::new("lawyer_accuser", booked),
+            ]))
+        );
+        // adjacent hearings are fine
+        let second = hearing::add(
+            &db,
+            "test",
+            &Hearing {
+                starts_at: at(10, 0),
+                ends_at: at(11, 0),
+                ..second
+            },
+        )
+        .unwrap();
+        let other_room = Hearing {
+            room: "Room 101".into(),
+            ..second.clone()
+        };
+        assert!(matches!(
+            hearing::add(&db, "test", &other_room),
+            Err(Error::DoubleBooked(details)) if details.len() == 2
+        ));
+        // a hearing does not clash with itself
+        hearing::update(
+            &db,
+            "test",
+            first.id,
+            &Hearing {
+                ends_at: at(9, 45),
+                ..first.clone()
+            },
+        )
+        .unwrap();
+        let third = hearing::add(
+            &db,
+            "test",
+            &Hearing {
+                criminal: fraud.id,
+                starts_at: at(13, 0),
+                ends_at: at(14, 0),
+                room: "Room 101".into(),
+                judge: "Judge Judy".into(),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        let docket = hearing::docket(&db, day, day, None).unwrap();
+        let order = docket
+            .iter()
+            .map(|e| (e.hearing.id, e.case_number.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            order,
+            [
+                (first.id, theft.case_number.as_str()),
+                (second.id, fraud.case_number.as_str()),
+                (third.id, fraud.case_number.as_str())
+            ]
+        );
+        let room = hearing::docket(&db, day, day, Some("ROOM 101")).unwrap();
+        assert_eq!(room.len(), 1);
+        assert_eq!(room[0].hearing, third);
+        let next = day.succ_opt().unwrap();
+        assert!(hearing::docket(&db, next, next, None).unwrap().is_empty());
+        assert_eq!(
+            hearing::of_case(&db, fraud.id).unwrap(),
+            [second, third.clone()]
+        );
+
+        let ics = String::from_utf8(hearing::ics(&docket).unwrap()).unwrap();
+        assert!(ics.starts_with("BEGIN:VCALENDAR\r\n"));
+        assert_eq!(ics.matches("BEGIN:VEVENT").count(), 3);
+        assert!(ics.contains("DTSTART:20260701T090000\r\n"));
+        assert!(ics.contains("LOCATION:Room 101\r\n"));
+        assert!(ics.contains(&format!("UID:hearing-{}@schiller-db", first.id)));
+
+        // hearings of cases in the trash bin are not on the docket
+        criminal::delete(&db, "test", fraud.id).unwrap();
+        assert_eq!(hearing::docket(&db, day, day, None).unwrap().len(), 1);
+        hearing::delete(&db, "test", first.id).unwrap();
+        assert_eq!(hearing::fetch(&db, first.id), Err(Error::NothingFound));
+        // and are removed with the case
+        criminal::purge(&db, "test", fraud.id).unwrap();
+        assert_eq!(hearing::fetch(&db, third.id), Err(Error::NothingFound));
+        let key = format!("{}/{}", fraud.id, third.id);
+        let search = AuditSearch {
+            entities: &[Entity::Hearing],
+            key: Some(&key),
+            ..Default::default()
+        };
+        let operations: Vec<_> = audit::search(&db, search, 10)
+            .unwrap()
+            .into_iter()
+            .map(|audit| audit.operation)
+            .collect();
+        assert_eq!(operations, [Operation::Delete, Operation::Insert]);
+    }
+}
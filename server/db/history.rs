@@ -0,0 +1,219 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::db::criminal::Criminal;
+use crate::db::project::{Database, FromRow, Result};
+use crate::db::user::User;
+use crate::db::workless::Workless;
+
+/// Revision of a record.
+#[derive(Serialize, Debug, Clone, ToSchema)]
+#[aliases(UserVersion = Version<User>, WorklessVersion = Version<Workless>, CriminalVersion = Version<Criminal>)]
+pub struct Version<T> {
+    pub item: T,
+    /// Start of the validity of this revision
+    pub valid_from: DateTime<Utc>,
+    /// End of the validity, if the record was changed afterwards
+    pub valid_to: Option<DateTime<Utc>>,
+    /// Whether the record was in the trash bin during this revision
+    pub deleted: bool,
+}
+
+impl<T: FromRow> FromRow for Version<T> {
+    fn from_row(row: &rusqlite::Row) -> rusqlite::Result<Self> {
+        Ok(Version {
+            item: T::from_row(row)?,
+            valid_from: row.get("valid_from")?,
+            valid_to: row.get("valid_to")?,
+            deleted: row.get::<_, Option<String>>("deleted_at")?.is_some(),
+        })
+    }
+}
+
+/// Version 6: Every revision of users, workless and criminal records
+/// is kept in a history table.
+///
+/// The tables are kept in sync by triggers. Revisions are valid from
+/// `valid_from` (inclusive) to `valid_to` (exclusive), the current revision
+/// has no `valid_to`. Their timestamps have the same text format as the
+/// ones of rusqlite, so they are compared as text.
+/// Existing records start their history with the migration.
+pub fn migrate(db: &Database) -> Result<()> {
+    const MIGRATION: &str = "\
+    create table user_history ( \
+        account text not null, \
+        forename text not null, \
+        surname text not null, \
+        role text not null, \
+        deleted_at text, \
+        valid_from text not null, \
+        valid_to text); \
+    create index user_history_key on user_history(account, valid_from); \
+    insert into user_history select \
+        account, forename, surname, role, deleted_at, \
+        strftime('%Y-%m-%d %H:%M:%f+00:00', 'now'), null \
+        from user; \
+    \
+    create trigger user_history_insert after insert on user begin \
+        insert into user_history values (new.account, new.forename, new.surname, \
+            new.role, new.deleted_at, strftime('%Y-%m-%d %H:%M:%f+00:00', 'now'), null); \
+    end; \
+    create trigger user_history_update after update on user begin \
+        update user_history set valid_to=strftime('%Y-%m-%d %H:%M:%f+00:00', 'now') \
+        where account=old.account and valid_to is null; \
+        insert into user_history values (new.account, new.forename, new.surname, \
+            new.role, new.deleted_at, strftime('%Y-%m-%d %H:%M:%f+00:00', 'now'), null); \
+    end; \
+    create trigger user_history_delete after delete on user begin \
+        update user_history set valid_to=strftime('%Y-%m-%d %H:%M:%f+00:00', 'now') \
+        where account=old.account and valid_to is null; \
+    end; \
+    \
+    create table workless_history ( \
+        account text not null, \
+        old_company text not null, \
+        date_of_dismiss text not null, \
+        currently integer not null, \
+        new_company text not null, \
+        total_time text not null, \
+        deleted_at text, \
+        valid_from text not null, \
+        valid_to text); \
+    create index workless_history_key \
+        on workless_history(account, old_company, date_of_dismiss, valid_from); \
+    insert into workless_history select \
+        account, old_company, date_of_dismiss, currently, new_company, total_time, \
+        deleted_at, strftime('%Y-%m-%d %H:%M:%f+00:00', 'now'), null \
+        from workless; \
+    \
+    create trigger workless_history_insert after insert on workless begin \
+        insert into workless_history values (new.account, new.old_company, \
+            new.date_of_dismiss, new.currently, new.new_company, new.total_time, \
+            new.deleted_at, strftime('%Y-%m-%d %H:%M:%f+00:00', 'now'), null); \
+    end; \
+    create trigger workless_history_update after update on workless begin \
+        update workless_history set valid_to=strftime('%Y-%m-%d %H:%M:%f+00:00', 'now') \
+        where account=old.account and old_company=old.old_company \
+            and date_of_dismiss=old.date_of_dismiss and valid_to is null; \
+        insert into workless_history values (new.account, new.old_company, \
+            new.date_of_dismiss, new.currently, new.new_company, new.total_time, \
+            new.deleted_at, strftime('%Y-%m-%d %H:%M:%f+00:00', 'now'), null); \
+    end; \
+    create trigger workless_history_delete after delete on workless begin \
+        update workless_history set valid_to=strftime('%Y-%m-%d %H:%M:%f+00:00', 'now') \
+        where account=old.account and old_company=old.old_company \
+            and date_of_dismiss=old.date_of_dismiss and valid_to is null; \
+    end; \
+    \
+    create table criminal_history ( \
+        account text not null, \
+        kind text not null, \
+        accuser text not null, \
+        police_consultant text not null, \
+        lawyer_culprit text not null, \
+        lawyer_accuser text not null, \
+        facts text not null, \
+        time_of_crime text not null, \
+        location_of_crime text not null, \
+        note text not null, \
+        verdict text not null, \
+        deleted_at text, \
+        valid_from text not null, \
+        valid_to text); \
+    create index criminal_history_key on criminal_history(account, kind, valid_from); \
+    insert into criminal_history select \
+        account, kind, accuser, police_consultant, lawyer_culprit, lawyer_accuser, \
+        facts, time_of_crime, location_of_crime, note, verdict, deleted_at, \
+        strftime('%Y-%m-%d %H:%M:%f+00:00', 'now'), null \
+        from criminal; \
+    \
+    create trigger criminal_history_insert after insert on criminal begin \
+        insert into criminal_history values (new.account, new.kind, new.accuser, \
+            new.police_consultant, new.lawyer_culprit, new.lawyer_accuser, new.facts, \
+            new.time_of_crime, new.location_of_crime, new.note, new.verdict, \
+            new.deleted_at, strftime('%Y-%m-%d %H:%M:%f+00:00', 'now'), null); \
+    end; \
+    create trigger criminal_history_update after update on criminal begin \
+        update criminal_history set valid_to=strftime('%Y-%m-%d %H:%M:%f+00:00', 'now') \
+        where account=old.account and kind=old.kind and valid_to is null; \
+        insert into criminal_history values (new.account, new.kind, new.accuser, \
+            new.police_consultant, new.lawyer_culprit, new.lawyer_accuser, new.facts, \
+            new.time_of_crime, new.location_of_crime, new.note, new.verdict, \
+            new.deleted_at, strftime('%Y-%m-%d %H:%M:%f+00:00', 'now'), null); \
+    end; \
+    create trigger criminal_history_delete after delete on criminal begin \
+        update criminal_history set valid_to=strftime('%Y-%m-%d %H:%M:%f+00:00', 'now') \
+        where account=old.account and kind=old.kind and valid_to is null; \
+    end; \
+    ";
+    db.con.execute_batch(MIGRATION)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread::sleep;
+    use std::time::Duration;
+
+    use chrono::Utc;
+
+    use crate::db::criminal::{self, Criminal};
+    use crate::db::project::{create, Database};
+    use crate::db::role::{self, Role, RoleKind};
+    use crate::db::user::{self, OnDelete, User};
+
+    #[test]
+    fn history_as_of() {
+        let db = Database::memory().unwrap();
+        create(&db).unwrap();
+        role::add(&db, "test", &Role::new("Demo", RoleKind::Other)).unwrap();
+
+        let user = User {
+            account: "foo.bar".into(),
+            forename: "Foo".into(),
+            surname: "Bar".into(),
+            role: "Demo".into(),
+        };
+        user::add(&db, "test", &user).unwrap();
+        let criminal = Criminal {
+            account: user.account.clone(),
+            kind: "Theft".into(),
+            verdict: "Guilty".into(),
+            ..Default::default()
+        };
+        let criminal = criminal::add(&db, "test", &criminal).unwrap();
+        sleep(Duration::from_millis(5));
+        let before = Utc::now();
+        sleep(Duration::from_millis(5));
+
+        let innocent = Criminal {
+            verdict: "Innocent".into(),
+            ..criminal.clone()
+        };
+        criminal::update(&db, "test", criminal.id, &innocent).unwrap();
+        user::delete(&db, "test", &user.account, OnDelete::Cascade).unwrap();
+
+        let history = criminal::history(&db, criminal.id).unwrap();
+        let verdicts = history
+            .iter()
+            .map(|v| (v.item.verdict.as_str(), v.deleted))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            verdicts,
+            [("Guilty", false), ("Innocent", false), ("Innocent", true)]
+        );
+        assert_eq!(history[0].valid_to, Some(history[1].valid_from));
+        assert!(history[2].valid_to.is_none());
+
+        assert_eq!(user::as_of(&db, before, 10).unwrap()[0], user);
+        assert_eq!(criminal::as_of(&db, before, 10).unwrap(), [criminal]);
+        assert!(user::as_of(&db, Utc::now(), 10).unwrap().is_empty());
+
+        // purged records keep their history
+        user::purge(&db, "test", &user.account).unwrap();
+        let history = user::history(&db, &user.account).unwrap();
+        assert_eq!(history.len(), 2);
+        assert!(history[1].valid_to.is_some());
+    }
+}
@@ -0,0 +1,500 @@
+use std::fmt;
+use std::io::{BufRead, Read};
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::db::criminal::{self, Criminal};
+use crate::db::project::{Database, Error, FieldError, Result};
+use crate::db::role;
+use crate::db::user::{self, User};
+use crate::db::validate::Validate;
+use crate::db::workless::{self, Workless};
+
+/// Default of the [`Config::role_precedence`](crate::config::Config).
+///
+/// Teachers win over classes, roles that only mention teachers
+/// or biology never replace another role.
+pub fn default_role_precedence() -> Vec<String> {
+    ["Lehrer", "!*Lehrer*", "!*Bio*", "Klasse*"]
+        .into_iter()
+        .map(Into::into)
+        .collect()
+}
+
+/// Outcome of an import.
+#[derive(Serialize, Debug, Clone, Default, PartialEq, ToSchema)]
+pub struct ImportReport {
+    pub added: usize,
+    pub updated: usize,
+    pub unchanged: usize,
+    pub rejected: usize,
+    /// Why the rejected lines were refused
+    pub errors: Vec<LineError>,
+}
+
+impl fmt::Display for ImportReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} added, {} updated, {} unchanged, {} rejected",
+            self.added, self.updated, self.unchanged, self.rejected
+        )?;
+        for LineError { line, error } in &self.errors {
+            write!(f, "\n  line {line}: {error}")?;
+            for detail in error.details() {
+                write!(f, ", {}: {}", detail.field, detail.message)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Rejected line of an import.
+#[derive(Serialize, Debug, Clone, PartialEq, ToSchema)]
+pub struct LineError {
+    /// Line number, starting at 1
+    pub line: usize,
+    pub error: Error,
+}
+
+/// Imports users from lines of `account|forename|surname|role`,
+/// where `div` is the separator.
+///
+/// Invalid lines are rejected and reported with their line numbers,
+/// the remaining lines are still imported. Empty lines are skipped.
+///
+/// If an account already exists, its names are updated if the role is
+/// the same. A different role only replaces the existing one according to
+/// the `role_precedence`: an ordered list of patterns, where `*` matches
+/// any text. The first matching pattern ranks a role, the earlier the
+/// better. A role replaces the existing one if it ranks at least as high.
+/// Roles that match no pattern or a pattern starting with `!` are not
+/// ranked and never replace another role, but are always replaced.
+///
+/// With `dry_run` all changes are rolled back, only the report is returned.
+pub fn users(
+    db: &Database,
+    actor: &str,
+    reader: impl BufRead,
+    div: &str,
+    role_precedence: &[String],
+    dry_run: bool,
+) -> Result<ImportReport> {
+    let import = |db: &Database| {
+        let mut report = ImportReport::default();
+        for (i, line) in reader.lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            match import_user(db, actor, &line, div, role_precedence) {
+                Ok(Outcome::Added) => report.added += 1,
+                Ok(Outcome::Updated) => report.updated += 1,
+                Ok(Outcome::Unchanged) => report.unchanged += 1,
+                Err(error) => {
+                    report.rejected += 1;
+                    report.errors.push(LineError { line: i + 1, error });
+                }
+            }
+        }
+        Ok(report)
+    };
+    if dry_run {
+        db.dry_run(import)
+    } else {
+        db.transaction(import)
+    }
+}
+
+enum Outcome {
+    Added,
+    Updated,
+    Unchanged,
+}
+
+fn import_user(
+    db: &Database,
+    actor: &str,
+    line: &str,
+    div: &str,
+    role_precedence: &[String],
+) -> Result<Outcome> {
+    let fields = line.split(div).map(str::trim).collect::<Vec<_>>();
+    let [account, forename, surname, role] = fields[..] else {
+        return Err(Error::InvalidFormat);
+    };
+    let mut user = User {
+        account: account.into(),
+        forename: forename.into(),
+        surname: surname.into(),
+        role: role.into(),
+    };
+    user.validate()?;
+    user.role = match role::resolve(db, role) {
+        Err(Error::UnknownRole) => role::canonical(role),
+        result => result?,
+    };
+
+    let existing = match user::fetch(db, account) {
+        Ok(existing) => existing,
+        Err(Error::NothingFound) => {
+            // unknown roles are added, because the lists come from other systems
+            role::ensure(db, actor, &user.role)?;
+            user::add(db, actor, &user)?;
+            return Ok(Outcome::Added);
+        }
+        Err(e) => return Err(e),
+    };
+    let replace = if existing.role == user.role {
+        existing.forename != user.forename || existing.surname != user.surname
+    } else {
+        replaces(role_precedence, &user.role, &existing.role)
+    };
+    if replace {
+        role::ensure(db, actor, &user.role)?;
+        user::update(db, actor, account, &user)?;
+        Ok(Outcome::Updated)
+    } else {
+        Ok(Outcome::Unchanged)
+    }
+}
+
+/// Imports users from CSV, see [`csv`].
+pub fn users_csv(
+    db: &Database,
+    actor: &str,
+    reader: impl Read,
+    dry_run: bool,
+) -> Result<ImportReport> {
+    csv(db, reader, dry_run, Error::InvalidUser, |db, user: User| {
+        user.validate()?;
+        match user::fetch(db, &user.account) {
+            Ok(existing) if existing == user => Ok(Outcome::Unchanged),
+            Ok(_) => {
+                user::update(db, actor, &user.account, &user)?;
+                Ok(Outcome::Updated)
+            }
+            Err(Error::NothingFound) => {
+                user::add(db, actor, &user)?;
+                Ok(Outcome::Added)
+            }
+            Err(e) => Err(e),
+        }
+    })
+}
+
+/// Imports workless from CSV, see [`csv`].
+pub fn workless_csv(
+    db: &Database,
+    actor: &str,
+    reader: impl Read,
+    dry_run: bool,
+) -> Result<ImportReport> {
+    csv(
+        db,
+        reader,
+        dry_run,
+        Error::InvalidWorkless,
+        |db, workless: Workless| {
+            workless.validate()?;
+            let (account, old_company, date) = (
+                &workless.account,
+                &workless.old_company,
+                workless.date_of_dismiss,
+            );
+            match workless::fetch(db, account, old_company, date) {
+                Ok(existing) if existing == workless => Ok(Outcome::Unchanged),
+                Ok(_) => {
+                    workless::update(db, actor, account, old_company, date, &workless)?;
+                    Ok(Outcome::Updated)
+                }
+                Err(Error::NothingFound) => {
+                    workless::add(db, actor, &workless)?;
+                    Ok(Outcome::Added)
+                }
+                Err(e) => Err(e),
+            }
+        },
+    )
+}
+
+/// Imports criminals from CSV, see [`csv`].
+pub fn criminals_csv(
+    db: &Database,
+    actor: &str,
+    reader: impl Read,
+    dry_run: bool,
+) -> Result<ImportReport> {
+    csv(
+        db,
+        reader,
+        dry_run,
+        Error::InvalidCriminal,
+        |db, criminal: Criminal| {
+            criminal.validate()?;
+            // cases without a known id are added as new ones,
+            // their status only changes by transitions
+            match criminal::fetch(db, criminal.id) {
+                Ok(existing)
+                    if existing
+                        == (Criminal {
+                            status: existing.status,
+                            ..criminal.clone()
+                        }) =>
+                {
+                    Ok(Outcome::Unchanged)
+                }
+                Ok(_) => {
+                    criminal::update(db, actor, criminal.id, &criminal)?;
+                    Ok(Outcome::Updated)
+                }
+                Err(Error::NothingFound) => {
+                    criminal::add(db, actor, &criminal)?;
+                    Ok(Outcome::Added)
+                }
+                Err(e) => Err(e),
+            }
+        },
+    )
+}
+
+/// Imports the rows of a CSV table with `import`.
+///
+/// The columns are mapped by the names in the header row, so their order
+/// does not matter and unknown columns are ignored. Unparsable values are
+/// reported with the `invalid` error of the table, naming the column if known.
+/// The import is all or nothing: if any row is rejected, no changes are
+/// made and the report lists the rejected rows with their line numbers.
+fn csv<T: DeserializeOwned + Clone>(
+    db: &Database,
+    reader: impl Read,
+    dry_run: bool,
+    invalid: fn(Vec<FieldError>) -> Error,
+    import: impl Fn(&Database, T) -> Result<Outcome>,
+) -> Result<ImportReport> {
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(reader);
+    let headers = reader.headers()?.clone();
+    let mut rows = Vec::new();
+    for record in reader.records() {
+        let record = record?;
+        let line = record.position().map_or(0, |p| p.line() as usize);
+        let item = record
+            .deserialize::<T>(Some(&headers))
+            .map_err(|e| match e.kind() {
+                csv::ErrorKind::Deserialize { err, .. } => {
+                    let field = err
+                        .field()
+                        .and_then(|i| headers.get(i as usize))
+                        .unwrap_or_default();
+                    invalid(vec![FieldError::new(field, err.kind().to_string())])
+                }
+                _ => Error::from(e),
+            });
+        rows.push((line, item));
+    }
+
+    let apply = |db: &Database| {
+        let mut report = ImportReport::default();
+        for (line, item) in &rows {
+            match item.clone().and_then(|item| import(db, item)) {
+                Ok(Outcome::Added) => report.added += 1,
+                Ok(Outcome::Updated) => report.updated += 1,
+                Ok(Outcome::Unchanged) => report.unchanged += 1,
+                Err(error) => {
+                    report.rejected += 1;
+                    report.errors.push(LineError { line: *line, error });
+                }
+            }
+        }
+        Ok(report)
+    };
+    let report = db.dry_run(apply)?;
+    if dry_run || report.rejected > 0 {
+        return Ok(report);
+    }
+    db.transaction(apply)
+}
+
+/// Whether the `role` replaces the `existing` one.
+fn replaces(role_precedence: &[String], role: &str, existing: &str) -> bool {
+    match (rank(role_precedence, role), rank(role_precedence, existing)) {
+        (Some(rank), Some(existing)) => rank <= existing,
+        (Some(_), None) => true,
+        (None, _) => false,
+    }
+}
+
+/// Position of the first pattern matching the `role`, if it is ranked.
+fn rank(role_precedence: &[String], role: &str) -> Option<usize> {
+    role_precedence
+        .iter()
+        .enumerate()
+        .find(|(_, pattern)| matches(pattern.trim_start_matches('!'), role))
+        .filter(|(_, pattern)| !pattern.starts_with('!'))
+        .map(|(i, _)| i)
+}
+
+/// Matches `text` against the `pattern`, where `*` matches any text.
+fn matches(pattern: &str, text: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = text.strip_prefix(first) else {
+        return false;
+    };
+    let mut parts = parts.collect::<Vec<_>>();
+    let Some(last) = parts.pop() else {
+        // no wildcard
+        return rest.is_empty();
+    };
+    for part in parts {
+        match rest.find(part) {
+            Some(i) => rest = &rest[i + part.len()..],
+            None => return false,
+        }
+    }
+    rest.len() >= last.len() && rest.ends_with(last)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::db::audit::{self, AuditSearch, Entity};
+    use crate::db::export;
+    use crate::db::import::{self, default_role_precedence, ImportReport, LineError};
+    use crate::db::page::Paging;
+    use crate::db::project::{create, Database, Error, FieldError};
+    use crate::db::role::{self, Role, RoleKind};
+    use crate::db::sort::Sort;
+    use crate::db::user::{self, UserSearch};
+    use crate::db::workless::{self, Workless};
+
+    #[test]
+    fn import_users() {
+        let db = Database::memory().unwrap();
+        create(&db).unwrap();
+        let rules = default_role_precedence();
+
+        let data = "\
+            foo.bar|Foo|Bar|Klasse 5a\n\
+            baz.qux|Baz|Qux|Lehrer\n\
+            \n\
+            too|short\n\
+            baz.qux|Baz|Qux|Klasse 6b\n\
+            foo.bar|Foo|Bar|Lehrer\n\
+            foo.bar|Foo|Bar|Bio-AG\n\
+            1nvalid|Foo|Bar|Lehrer\n";
+
+        let expected = ImportReport {
+            added: 2,
+            updated: 1,
+            unchanged: 2,
+            rejected: 2,
+            errors: vec![
+                LineError {
+                    line: 4,
+                    error: Error::InvalidFormat,
+                },
+                LineError {
+                    line: 8,
+                    error: Error::InvalidUser(vec![FieldError::new(
+                        "account",
+                        "must start with a letter",
+                    )]),
+                },
+            ],
+        };
+        let report = import::users(&db, "test", data.as_bytes(), "|", &rules, true).unwrap();
+        assert_eq!(report, expected);
+        assert!(user::fetch(&db, "foo.bar").is_err());
+
+        let report = import::users(&db, "importer", data.as_bytes(), "|", &rules, false).unwrap();
+        assert_eq!(report, expected);
+        assert_eq!(user::fetch(&db, "foo.bar").unwrap().role, "Lehrer");
+        // the changes are audited as the importing actor
+        let audit = audit::search(
+            &db,
+            AuditSearch {
+                entities: &[Entity::User],
+                key: Some("foo.bar"),
+                ..Default::default()
+            },
+            10,
+        )
+        .unwrap();
+        assert!(!audit.is_empty());
+        assert!(audit.iter().all(|a| a.actor == "importer"));
+        assert_eq!(user::fetch(&db, "baz.qux").unwrap().role, "Lehrer");
+        // only the roles that were assigned are added
+        assert_eq!(role::fetch(&db, "klasse 5A").unwrap().kind, RoleKind::Class);
+        assert_eq!(role::fetch(&db, "Klasse 6b"), Err(Error::NothingFound));
+
+        // a class replaces unranked roles, but not teachers
+        let data = "foo.bar|Foo|Bar|Klasse 7c\nbar.baz|Bar|Baz|Bio-AG\nbar.baz|Bar|Baz|Klasse 7c";
+        let report = import::users(&db, "test", data.as_bytes(), "|", &rules, false).unwrap();
+        assert_eq!((report.added, report.updated, report.unchanged), (1, 1, 1));
+        assert_eq!(user::fetch(&db, "bar.baz").unwrap().role, "Klasse 7c");
+    }
+
+    #[test]
+    fn csv_round_trip() {
+        let db = Database::memory().unwrap();
+        create(&db).unwrap();
+
+        // columns in any order, unknown ones are ignored
+        let data = "\
+            role,surname,forename,account,note\n\
+            Demo,Bar,Foo,foo.bar,x\n\
+            Lehrer,Qux,Baz,baz.qux,\n";
+        // the roles of CSV imports must exist
+        let report = import::users_csv(&db, "test", data.as_bytes(), false).unwrap();
+        assert_eq!(report.errors[0].error, Error::UnknownRole);
+        for name in ["Demo", "Lehrer"] {
+            role::add(&db, "test", &Role::new(name, RoleKind::guess(name))).unwrap();
+        }
+        let report = import::users_csv(&db, "test", data.as_bytes(), false).unwrap();
+        assert_eq!((report.added, report.rejected), (2, 0));
+
+        let users = user::search(
+            &db,
+            UserSearch::new("", "%"),
+            &Sort::default(),
+            Paging::default(),
+        )
+        .unwrap()
+        .items;
+        let csv = export::csv(&users).unwrap();
+        assert!(csv.starts_with(b"account,forename,surname,role\n"));
+        let report = import::users_csv(&db, "test", &csv[..], false).unwrap();
+        assert_eq!((report.unchanged, report.rejected), (2, 0));
+
+        // one invalid row rejects the whole import
+        let data = "\
+            account,old_company,date_of_dismiss,currently,new_company,total_time\n\
+            foo.bar,Bars,2023-06-01,true,Baz,1 year\n\
+            foo.bar,Bars,2023-07-01,maybe,Baz,1 year\n";
+        let report = import::workless_csv(&db, "test", data.as_bytes(), false).unwrap();
+        assert_eq!((report.added, report.rejected), (1, 1));
+        assert_eq!(report.errors[0].line, 3);
+        assert_eq!(report.errors[0].error.details()[0].field, "currently");
+        assert!(workless::of_account(&db, "foo.bar").unwrap().is_empty());
+
+        // empty tables still have their header
+        assert_eq!(
+            export::csv::<Workless>(&[]).unwrap(),
+            b"account,old_company,date_of_dismiss,currently,new_company,total_time\n"
+        );
+    }
+
+    #[test]
+    fn matches() {
+        assert!(import::matches("Lehrer", "Lehrer"));
+        assert!(!import::matches("Lehrer", "Lehrerin"));
+        assert!(import::matches("Klasse*", "Klasse 5a"));
+        assert!(import::matches("*Bio*", "Klasse Bio 5"));
+        assert!(import::matches("a*b*a", "aba"));
+        assert!(!import::matches("a*a", "a"));
+    }
+}
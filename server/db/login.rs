@@ -1,8 +1,11 @@
-use crate::db::project::{Database, Error, FromRow, Result};
+use crate::db::audit::{self, Audited, Entity};
+use crate::db::project::{Database, Error, FieldError, FromRow, Result};
+use crate::db::validate::{Field, Rule, Validate, Value};
 use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
 use log::warn;
 use rand::RngCore;
 use rusqlite::types::{FromSql, FromSqlResult, ToSql, ToSqlOutput, ValueRef};
+use rusqlite::OptionalExtension;
 use serde::{Deserialize, Serialize};
 use sha2::Digest;
 use utoipa::ToSchema;
@@ -61,7 +64,34 @@ impl FromRow for Permissions {
     }
 }
 
-#[derive(Deserialize, PartialEq, Debug, ToSchema)]
+/// Login as written to the audit trail, without its credentials.
+#[derive(Serialize, Debug)]
+pub struct AuditedLogin {
+    pub user: String,
+    pub access_user: Permission,
+    pub access_workless: Permission,
+    pub access_criminal: Permission,
+}
+
+impl From<&Login> for AuditedLogin {
+    fn from(login: &Login) -> Self {
+        Self {
+            user: login.user.clone(),
+            access_user: login.access_user,
+            access_workless: login.access_workless,
+            access_criminal: login.access_criminal,
+        }
+    }
+}
+
+impl Audited for AuditedLogin {
+    const ENTITY: Entity = Entity::Login;
+    fn key(&self) -> String {
+        self.user.clone()
+    }
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug, ToSchema)]
 pub struct Login {
     pub user: String,
     pub hash: String,
@@ -72,13 +102,6 @@ pub struct Login {
 }
 
 impl Login {
-    pub fn is_valid(&self) -> bool {
-        !self.user.trim().is_empty()
-            && self.user.starts_with(char::is_alphabetic)
-            && !self.user.contains(':')
-            && !self.hash.trim().is_empty()
-            && !self.salt.trim().is_empty()
-    }
     pub fn compute_hash(salt: &str, password: &str) -> Result<String> {
         let Ok(salt) = BASE64.decode(salt) else {
             warn!("salt could not be decoded");
@@ -121,8 +144,31 @@ pub struct NewLogin {
     pub access_criminal: Permission,
 }
 
+impl Validate for NewLogin {
+    const FIELDS: &'static [Field] = &[
+        // the name is separated from the password by ':' in basic auth
+        Field::new(
+            "user",
+            &[
+                Rule::Required,
+                Rule::StartsWithLetter,
+                Rule::MaxLength(64),
+                Rule::Forbidden(":"),
+            ],
+        ),
+        Field::new("password", &[Rule::Required]),
+    ];
+    fn fields(&self) -> Vec<Value<'_>> {
+        vec![Value::Text(&self.user), Value::Text(&self.password)]
+    }
+    fn invalid(details: Vec<FieldError>) -> Error {
+        Error::InvalidLogin(details)
+    }
+}
+
 impl NewLogin {
     pub fn salted(self) -> Result<Login> {
+        self.validate()?;
         let NewLogin {
             user,
             password,
@@ -131,9 +177,6 @@ impl NewLogin {
             access_criminal,
         } = self;
         let password = password.trim().to_string();
-        if user.trim().is_empty() || password.is_empty() {
-            return Err(Error::InvalidLogin);
-        }
 
         let mut salt = [0; 32];
         rand::thread_rng().fill_bytes(&mut salt);
@@ -151,9 +194,13 @@ impl NewLogin {
     }
 }
 
+/// Logins of users in the trash bin are kept, but can not be used
+/// until their user is restored.
+const USABLE: &str = "user not in (select account from user where deleted_at is not null)";
+
 /// Returns the login with the given `user` and `password`.
 pub fn fetch(db: &Database, user: &str) -> Result<Login> {
-    let mut stmt = db.con.prepare(
+    let mut stmt = db.con.prepare(&format!(
         "select \
         user, \
         hash, \
@@ -162,24 +209,24 @@ pub fn fetch(db: &Database, user: &str) -> Result<Login> {
         access_workless, \
         access_criminal \
         from login \
-        where user=?
-        limit 1",
-    )?;
+        where user=? and {USABLE} \
+        limit 1"
+    ))?;
     let mut result = stmt.query([user])?;
     Ok(Login::from_row(result.next()?.ok_or(Error::NothingFound)?)?)
 }
 
 /// Returns the permissions of the user with the given `user`.
 pub fn fetch_permission(db: &Database, user: &str) -> Result<Permissions> {
-    let mut stmt = db.con.prepare(
+    let mut stmt = db.con.prepare(&format!(
         "select \
         access_user, \
         access_workless, \
         access_criminal \
         from login \
-        where user=?
-        limit 1",
-    )?;
+        where user=? and {USABLE} \
+        limit 1"
+    ))?;
     let mut result = stmt.query([user])?;
     Ok(Permissions::from_row(
         result.next()?.ok_or(Error::NothingFound)?,
@@ -187,12 +234,13 @@ pub fn fetch_permission(db: &Database, user: &str) -> Result<Permissions> {
 }
 
 pub fn all_logins(db: &Database) -> Result<Vec<String>> {
-    let mut stmt = db.con.prepare(
+    let mut stmt = db.con.prepare(&format!(
         "select \
         user \
         from login \
-        order by user",
-    )?;
+        where {USABLE} \
+        order by user"
+    ))?;
 
     let mut rows = stmt.query([])?;
     let mut users = Vec::new();
@@ -204,26 +252,29 @@ pub fn all_logins(db: &Database) -> Result<Vec<String>> {
 }
 
 /// Adds a new login.
-pub fn add(db: &Database, login: NewLogin) -> Result<()> {
+pub fn add(db: &Database, actor: &str, login: NewLogin) -> Result<()> {
     let login = login.salted()?;
 
-    db.con.execute(
-        "INSERT INTO login VALUES (?, ?, ?, ?, ?, ?)",
-        rusqlite::params![
-            login.user.trim(),
-            login.hash,
-            login.salt,
-            login.access_user,
-            login.access_workless,
-            login.access_criminal
-        ],
-    )?;
-    Ok(())
+    db.transaction(|db| {
+        db.con.execute(
+            "INSERT INTO login VALUES (?, ?, ?, ?, ?, ?)",
+            rusqlite::params![
+                login.user.trim(),
+                login.hash,
+                login.salt,
+                login.access_user,
+                login.access_workless,
+                login.access_criminal
+            ],
+        )?;
+        let after = fetch(db, login.user.trim())?;
+        audit::insert(db, actor, &AuditedLogin::from(&after))
+    })
 }
 
 /// Updates a login.
 /// This includes only it's user and password.
-pub fn update(db: &Database, user: &str, password: &str) -> Result<()> {
+pub fn update(db: &Database, actor: &str, user: &str, password: &str) -> Result<()> {
     let login = NewLogin {
         user: user.to_string(),
         password: password.to_string(),
@@ -231,22 +282,50 @@ pub fn update(db: &Database, user: &str, password: &str) -> Result<()> {
     }
     .salted()?;
 
-    db.con.execute(
-        "update login set hash=?, salt=? where user=?",
-        rusqlite::params![login.hash, login.salt, login.user.trim(),],
-    )?;
-    Ok(())
+    db.transaction(|db| {
+        let before = AuditedLogin::from(&fetch(db, login.user.trim())?);
+        db.con.execute(
+            "update login set hash=?, salt=? where user=?",
+            rusqlite::params![login.hash, login.salt, login.user.trim(),],
+        )?;
+        // only the password changed, which is not part of the audit trail
+        audit::update(db, actor, &before, &before)
+    })
 }
 
 /// Deletes the login by user.
-pub fn delete(db: &Database, user: &str) -> Result<()> {
+pub fn delete(db: &Database, actor: &str, user: &str) -> Result<()> {
     let user = user.trim();
     if user.is_empty() {
-        return Err(Error::InvalidUser);
+        return Err(Error::InvalidUser(vec![FieldError::new(
+            "user",
+            "must not be empty",
+        )]));
+    }
+    db.transaction(|db| {
+        let before = AuditedLogin::from(&fetch(db, user)?);
+        // remove non-admin users
+        db.con
+            .execute("delete from login where user=?", rusqlite::params![user])?;
+        audit::delete(db, actor, &before)
+    })
+}
+
+/// Deletes the login of a user that is purged from the trash bin.
+pub(crate) fn purge(db: &Database, actor: &str, user: &str) -> Result<()> {
+    let login = db
+        .con
+        .query_row(
+            "select user, hash, salt, access_user, access_workless, access_criminal \
+            from login where user=?",
+            [user],
+            Login::from_row,
+        )
+        .optional()?;
+    if let Some(login) = login {
+        db.con.execute("delete from login where user=?", [user])?;
+        audit::delete(db, actor, &AuditedLogin::from(&login))?;
     }
-    // remove non-admin users
-    db.con
-        .execute("delete from login where user=?", rusqlite::params![user])?;
     Ok(())
 }
 
@@ -269,7 +348,7 @@ mod tests {
             access_workless: Permission::Write,
             access_criminal: Permission::None,
         };
-        login::add(&db, login.clone()).unwrap();
+        login::add(&db, "test", login.clone()).unwrap();
 
         let result = login::fetch(&db, &login.user);
         assert!(result.is_ok());
@@ -281,7 +360,7 @@ mod tests {
         let result = login::all_logins(&db).unwrap();
         assert_eq!(result.len(), 1);
 
-        login::delete(&db, &login.user).unwrap();
+        login::delete(&db, "test", &login.user).unwrap();
 
         let result = login::fetch(&db, &login.user);
         println!("{result:?}");
@@ -1,6 +1,25 @@
+pub mod audit;
+pub mod backup;
+pub mod certificate;
 pub mod criminal;
+pub mod dump;
+pub mod encryption;
+pub mod export;
+pub mod fulltext;
+pub mod hearing;
+pub mod history;
+pub mod import;
 pub mod login;
+pub mod page;
 pub mod project;
+pub mod repository;
+pub mod role;
+pub mod sort;
 pub mod stats;
+pub mod status;
+pub mod trash;
 pub mod user;
+pub mod validate;
 pub mod workless;
+#[cfg(feature = "xlsx")]
+pub mod xlsx;
@@ -0,0 +1,151 @@
+use rusqlite::Params;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::db::criminal::Criminal;
+use crate::db::fulltext::{CriminalHit, UserHit};
+use crate::db::project::{DBIter, Database, FromRow, Result};
+use crate::db::user::User;
+use crate::db::workless::Workless;
+
+/// Number of items in a page, if the client does not ask for another size.
+pub const DEFAULT_PAGE_SIZE: usize = 200;
+/// Larger pages are cut to this size.
+pub const MAX_PAGE_SIZE: usize = 1000;
+
+/// Page of the search results.
+#[derive(Serialize, Debug, Clone, PartialEq, ToSchema)]
+#[aliases(UserPage = Page<User>, WorklessPage = Page<Workless>, CriminalPage = Page<Criminal>, UserHitPage = Page<UserHit>, CriminalHitPage = Page<CriminalHit>)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    /// Number of all results
+    pub total: usize,
+    /// Offset of the next page, if there are more results
+    pub next: Option<usize>,
+}
+
+impl<T> Page<T> {
+    pub fn empty() -> Self {
+        Page {
+            items: Vec::new(),
+            total: 0,
+            next: None,
+        }
+    }
+}
+
+/// Position and size of a page.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Paging {
+    pub offset: usize,
+    pub limit: usize,
+}
+
+impl Default for Paging {
+    fn default() -> Self {
+        Paging {
+            offset: 0,
+            limit: DEFAULT_PAGE_SIZE,
+        }
+    }
+}
+
+impl Paging {
+    /// Page requested by a client, which can be at most [`MAX_PAGE_SIZE`] large.
+    pub fn new(offset: Option<usize>, limit: Option<usize>) -> Paging {
+        Paging {
+            offset: offset.unwrap_or_default(),
+            limit: limit.unwrap_or(DEFAULT_PAGE_SIZE).min(MAX_PAGE_SIZE),
+        }
+    }
+
+    /// All results, only for exports.
+    pub fn all() -> Paging {
+        Paging {
+            offset: 0,
+            limit: u32::MAX as usize,
+        }
+    }
+}
+
+/// Returns the requested page of the `query` results and counts all of them.
+///
+/// The query must not have a limit, the `params` are bound to both queries.
+pub fn paginate<T: FromRow>(
+    db: &Database,
+    query: &str,
+    params: impl Params + Clone,
+    paging: Paging,
+) -> Result<Page<T>> {
+    let total: usize = db.con.query_row(
+        &format!("select count(*) from ({query})"),
+        params.clone(),
+        |row| row.get(0),
+    )?;
+    let mut stmt = db.con.prepare(&format!(
+        "{query} limit {} offset {}",
+        paging.limit, paging.offset
+    ))?;
+    let items = DBIter::new(stmt.query(params)?).collect::<Result<Vec<T>>>()?;
+    let end = paging.offset.saturating_add(items.len());
+    Ok(Page {
+        next: (!items.is_empty() && end < total).then_some(end),
+        items,
+        total,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::db::page::{Paging, MAX_PAGE_SIZE};
+    use crate::db::project::{create, Database};
+    use crate::db::role::{self, Role, RoleKind};
+    use crate::db::sort::Sort;
+    use crate::db::user::{self, User, UserSearch};
+
+    #[test]
+    fn pages() {
+        let db = Database::memory().unwrap();
+        create(&db).unwrap();
+        role::add(&db, "test", &Role::new("Demo", RoleKind::Other)).unwrap();
+        for i in 0..5 {
+            let user = User {
+                account: format!("foo.bar{i}"),
+                forename: "Foo".into(),
+                surname: "Bar".into(),
+                role: "Demo".into(),
+            };
+            user::add(&db, "test", &user).unwrap();
+        }
+
+        let search = UserSearch::new("foo", "%");
+        let page = user::search(
+            &db,
+            search.clone(),
+            &Sort::default(),
+            Paging::new(None, Some(2)),
+        )
+        .unwrap();
+        assert_eq!(page.total, 5);
+        assert_eq!(page.items.len(), 2);
+        assert_eq!(page.next, Some(2));
+
+        let last = user::search(
+            &db,
+            search.clone(),
+            &Sort::default(),
+            Paging::new(Some(4), Some(2)),
+        )
+        .unwrap();
+        assert_eq!(last.items[0].account, "foo.bar4");
+        assert_eq!(last.next, None);
+
+        let beyond =
+            user::search(&db, search, &Sort::default(), Paging::new(Some(10), None)).unwrap();
+        assert!(beyond.items.is_empty());
+        assert_eq!(beyond.total, 5);
+        assert_eq!(beyond.next, None);
+
+        assert_eq!(Paging::new(None, Some(usize::MAX)).limit, MAX_PAGE_SIZE);
+    }
+}
@@ -4,26 +4,43 @@ use std::{
     fs::File,
     io::BufRead,
     path::{Path, PathBuf},
-    ptr::addr_of,
+    time::Duration,
 };
 
 use std::io::BufReader;
 
+use log::{error, warn};
 use rusqlite::{types::FromSql, Connection};
 use serde::{Deserialize, Serialize};
+use utoipa::openapi::{RefOr, Schema};
 use utoipa::ToSchema;
 
 use super::login::NewLogin;
-use crate::db::user::User;
+use super::status::CaseStatus;
 
-macro_rules! error {
-    ($($args:tt)*) => {
-        println!($($args)*)
-    };
+/// Validation error of a single field.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, ToSchema)]
+pub struct FieldError {
+    /// Name of the invalid field.
+    pub field: String,
+    /// Why the value was refused.
+    pub message: String,
+}
+
+impl FieldError {
+    pub fn new(field: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            field: field.into(),
+            message: message.into(),
+        }
+    }
 }
 
 /// Operation Error
-#[derive(Serialize, Deserialize, Debug, ToSchema)]
+///
+/// Serialized as `{"code", "message", "details"}`, where `code` is the name
+/// of the variant and `details` lists the invalid fields, if known.
+#[derive(Debug, Clone, PartialEq)]
 pub enum Error {
     /// Default errors
     Arguments,
@@ -31,31 +48,222 @@ pub enum Error {
     NoProject,
     FileNotFound,
     FileOpen,
-    SQL,
+    SQL(String),
     Network,
     InvalidFormat,
     NothingFound,
+    AlreadyExists,
+    Trashed,
     /// Specific errors
-    InvalidUser,
-    InvalidLogin,
-    InvalidWorkless,
-    InvalidCriminal,
+    InvalidUser(Vec<FieldError>),
+    InvalidLogin(Vec<FieldError>),
+    InvalidWorkless(Vec<FieldError>),
+    InvalidCriminal(Vec<FieldError>),
+    InvalidRole(Vec<FieldError>),
+    InvalidHearing(Vec<FieldError>),
     InvalidDate,
     InvalidKind,
+    InvalidSort(String),
+    InvalidTransition(CaseStatus, CaseStatus),
+    UnknownAccount,
+    UserReferenced,
+    UnknownRole,
+    RoleReferenced,
+    DoubleBooked(Vec<FieldError>),
     /// Server specific errors
     Unauthorized,
     ExceededLimit,
     PageNotFound,
     UnprocessableEntity,
     InternalError,
+    DatabaseUnavailable,
     /// Migration
     UnsupportedProjectVersion,
+    /// Encryption
+    InvalidKey,
+    EncryptionUnsupported,
+}
+
+impl Error {
+    /// Machine-readable name of the error.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::Arguments => "Arguments",
+            Self::Logic => "Logic",
+            Self::NoProject => "NoProject",
+            Self::FileNotFound => "FileNotFound",
+            Self::FileOpen => "FileOpen",
+            Self::SQL(_) => "SQL",
+            Self::Network => "Network",
+            Self::InvalidFormat => "InvalidFormat",
+            Self::NothingFound => "NothingFound",
+            Self::AlreadyExists => "AlreadyExists",
+            Self::Trashed => "Trashed",
+            Self::InvalidUser(_) => "InvalidUser",
+            Self::InvalidLogin(_) => "InvalidLogin",
+            Self::InvalidWorkless(_) => "InvalidWorkless",
+            Self::InvalidCriminal(_) => "InvalidCriminal",
+            Self::InvalidRole(_) => "InvalidRole",
+            Self::InvalidHearing(_) => "InvalidHearing",
+            Self::InvalidDate => "InvalidDate",
+            Self::InvalidKind => "InvalidKind",
+            Self::InvalidSort(_) => "InvalidSort",
+            Self::InvalidTransition(..) => "InvalidTransition",
+            Self::UnknownAccount => "UnknownAccount",
+            Self::UserReferenced => "UserReferenced",
+            Self::UnknownRole => "UnknownRole",
+            Self::RoleReferenced => "RoleReferenced",
+            Self::DoubleBooked(_) => "DoubleBooked",
+            Self::Unauthorized => "Unauthorized",
+            Self::ExceededLimit => "ExceededLimit",
+            Self::PageNotFound => "PageNotFound",
+            Self::UnprocessableEntity => "UnprocessableEntity",
+            Self::InternalError => "InternalError",
+            Self::DatabaseUnavailable => "DatabaseUnavailable",
+            Self::UnsupportedProjectVersion => "UnsupportedProjectVersion",
+            Self::InvalidKey => "InvalidKey",
+            Self::EncryptionUnsupported => "EncryptionUnsupported",
+        }
+    }
+
+    /// Invalid fields of a validation error.
+    pub fn details(&self) -> &[FieldError] {
+        match self {
+            Self::InvalidUser(details)
+            | Self::InvalidLogin(details)
+            | Self::InvalidWorkless(details)
+            | Self::InvalidCriminal(details)
+            | Self::InvalidRole(details)
+            | Self::InvalidHearing(details)
+            | Self::DoubleBooked(details) => details,
+            _ => &[],
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Arguments => write!(f, "Invalid arguments"),
+            Self::Logic => write!(f, "Logic error"),
+            Self::NoProject => write!(f, "The database contains no project"),
+            Self::FileNotFound => write!(f, "File not found"),
+            Self::FileOpen => write!(f, "File could not be opened"),
+            Self::SQL(msg) => write!(f, "Database error: {msg}"),
+            Self::Network => write!(f, "Network error"),
+            Self::InvalidFormat => write!(f, "Invalid format"),
+            Self::NothingFound => write!(f, "Nothing found"),
+            Self::AlreadyExists => write!(f, "The entry already exists"),
+            Self::Trashed => write!(
+                f,
+                "The entry is in the trash bin, restore or purge it first"
+            ),
+            Self::InvalidUser(_) => write!(f, "Invalid user"),
+            Self::InvalidLogin(_) => write!(f, "Invalid login"),
+            Self::InvalidWorkless(_) => write!(f, "Invalid workless entry"),
+            Self::InvalidCriminal(_) => write!(f, "Invalid criminal entry"),
+            Self::InvalidRole(_) => write!(f, "Invalid role"),
+            Self::InvalidHearing(_) => write!(f, "Invalid hearing"),
+            Self::InvalidDate => write!(f, "Invalid date"),
+            Self::InvalidKind => write!(f, "Invalid kind"),
+            Self::InvalidSort(column) => write!(f, "Unknown sort column: {column}"),
+            Self::InvalidTransition(from, to) => {
+                write!(f, "The case can not change from {from} to {to}")
+            }
+            Self::UnknownAccount => write!(f, "The account does not exist"),
+            Self::UserReferenced => write!(f, "The user still has entries"),
+            Self::UnknownRole => write!(f, "The role does not exist"),
+            Self::RoleReferenced => write!(f, "The role is still assigned"),
+            Self::DoubleBooked(_) => {
+                write!(f, "The room or a person is already booked at this time")
+            }
+            Self::Unauthorized => write!(f, "Unauthorized"),
+            Self::ExceededLimit => write!(f, "Request too large"),
+            Self::PageNotFound => write!(f, "Page not found"),
+            Self::UnprocessableEntity => write!(f, "Unprocessable entity"),
+            Self::InternalError => write!(f, "Internal server error"),
+            Self::DatabaseUnavailable => write!(f, "The database is unavailable"),
+            Self::UnsupportedProjectVersion => {
+                write!(f, "The project version is not supported")
+            }
+            Self::InvalidKey => write!(f, "The database key is wrong"),
+            Self::EncryptionUnsupported => {
+                write!(f, "Encryption requires the sqlcipher feature")
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Error response with a machine-readable code, a message and the invalid fields.
+#[derive(Serialize, ToSchema)]
+struct ErrorBody {
+    /// Name of the error, e.g. `NothingFound`.
+    #[schema(example = "InvalidUser")]
+    code: &'static str,
+    /// Human readable description.
+    #[schema(example = "Invalid user")]
+    message: String,
+    /// Invalid fields of validation errors.
+    details: Vec<FieldError>,
+}
+
+impl Serialize for Error {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        ErrorBody {
+            code: self.code(),
+            message: self.to_string(),
+            details: self.details().to_vec(),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'s> ToSchema<'s> for Error {
+    fn schema() -> (&'s str, RefOr<Schema>) {
+        ("Error", ErrorBody::schema().1)
+    }
 }
 
 impl From<rusqlite::Error> for Error {
     fn from(e: rusqlite::Error) -> Self {
-        error!("SQL: {e}");
-        Self::SQL
+        use rusqlite::ErrorCode;
+        match &e {
+            rusqlite::Error::QueryReturnedNoRows => Self::NothingFound,
+            rusqlite::Error::SqliteFailure(err, _)
+                if err.extended_code == rusqlite::ffi::SQLITE_CONSTRAINT_PRIMARYKEY
+                    || err.extended_code == rusqlite::ffi::SQLITE_CONSTRAINT_UNIQUE =>
+            {
+                Self::AlreadyExists
+            }
+            // raised by the triggers of the role table
+            rusqlite::Error::SqliteFailure(err, Some(msg))
+                if err.extended_code == rusqlite::ffi::SQLITE_CONSTRAINT_TRIGGER =>
+            {
+                match msg.as_str() {
+                    "UnknownRole" => Self::UnknownRole,
+                    "RoleReferenced" => Self::RoleReferenced,
+                    _ => Self::SQL(e.to_string()),
+                }
+            }
+            rusqlite::Error::SqliteFailure(err, _)
+                if matches!(
+                    err.code,
+                    ErrorCode::DatabaseBusy | ErrorCode::DatabaseLocked
+                ) =>
+            {
+                error!("SQL: {e}");
+                Self::DatabaseUnavailable
+            }
+            _ => {
+                error!("SQL: {e}");
+                Self::SQL(e.to_string())
+            }
+        }
     }
 }
 
@@ -73,6 +281,31 @@ impl From<std::io::Error> for Error {
     }
 }
 
+impl From<csv::Error> for Error {
+    fn from(e: csv::Error) -> Self {
+        error!("CSV Error: {e}");
+        Self::InvalidFormat
+    }
+}
+
+#[cfg(feature = "xlsx")]
+impl From<rust_xlsxwriter::XlsxError> for Error {
+    fn from(e: rust_xlsxwriter::XlsxError) -> Self {
+        error!("XLSX Error: {e}");
+        match e {
+            rust_xlsxwriter::XlsxError::RowColumnLimitError => Self::ExceededLimit,
+            _ => Self::InternalError,
+        }
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Self {
+        error!("JSON Error: {e}");
+        Self::InvalidFormat
+    }
+}
+
 pub type Result<T> = std::result::Result<T, Error>;
 
 pub trait FromRow: Sized {
@@ -109,8 +342,12 @@ impl<'a, T> DBIter<'a, T> {
     }
 }
 
+/// Time a connection waits for a locked database before giving up.
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
 pub struct Database {
     path: PathBuf,
+    key: Option<String>,
     pub con: rusqlite::Connection,
 }
 impl fmt::Debug for Database {
@@ -123,7 +360,9 @@ impl fmt::Debug for Database {
 
 impl Database {
     /// Creates a new database at the given path.
-    pub fn create(path: Cow<'_, Path>) -> Result<Database> {
+    ///
+    /// If a `key` is given, the database is encrypted with it.
+    pub fn create(path: Cow<'_, Path>, key: Option<&str>) -> Result<Database> {
         if !path.exists() {
             let database = Database {
                 con: rusqlite::Connection::open_with_flags(
@@ -133,7 +372,10 @@ impl Database {
                 )
                 .map_err(|_| Error::FileOpen)?,
                 path: path.into_owned(),
+                key: key.map(Into::into),
             };
+            database.unlock()?;
+            database.configure()?;
             Ok(database)
         } else {
             Err(Error::FileOpen)
@@ -141,7 +383,11 @@ impl Database {
     }
 
     /// Opens a database connection to the given project database.
-    pub fn open(path: Cow<'_, Path>) -> Result<(Database, bool)> {
+    ///
+    /// Encrypted databases are unlocked with the `key`.
+    /// Outdated schemas are migrated to the current [`VERSION`].
+    /// The returned flag tells whether any migration was applied.
+    pub fn open(path: Cow<'_, Path>, key: Option<&str>) -> Result<(Database, bool)> {
         if path.exists() {
             let database = Database {
                 con: rusqlite::Connection::open_with_flags(
@@ -150,8 +396,14 @@ impl Database {
                 )
                 .map_err(|_| Error::FileOpen)?,
                 path: path.into_owned(),
+                key: key.map(Into::into),
             };
-            Ok((database, true))
+            database.unlock()?;
+            // the migration waits for concurrent writers too,
+            // it turns off the foreign keys while it runs
+            database.configure()?;
+            let migrated = migrate(&database)?;
+            Ok((database, migrated))
         } else {
             Err(Error::FileNotFound)
         }
@@ -162,46 +414,369 @@ impl Database {
         &self.path
     }
 
+    /// Returns the key of an encrypted database.
+    pub fn key(&self) -> Option<&str> {
+        self.key.as_deref()
+    }
+
     /// In memory database for testing purposes.
     pub fn memory() -> Result<Database> {
-        Ok(Database {
+        let database = Database {
             path: PathBuf::new(),
+            key: None,
             con: rusqlite::Connection::open_in_memory()?,
-        })
+        };
+        database.configure()?;
+        Ok(database)
+    }
+
+    /// Applies the key, which has to precede any other statement.
+    fn unlock(&self) -> Result<()> {
+        let Some(key) = &self.key else {
+            return Ok(());
+        };
+        if !cfg!(feature = "sqlcipher") {
+            // plain SQLite would ignore the key and store everything unencrypted
+            return Err(Error::EncryptionUnsupported);
+        }
+        self.con.pragma_update(None, "key", key)?;
+        // the key is only checked when the database is read
+        self.con
+            .query_row("select count(*) from sqlite_master", [], |_| Ok(()))
+            .map_err(|e| match e.sqlite_error_code() {
+                Some(rusqlite::ErrorCode::NotADatabase) => Error::InvalidKey,
+                _ => e.into(),
+            })
+    }
+
+    /// Applies the connection specific settings.
+    fn configure(&self) -> Result<()> {
+        // wait for concurrent writers instead of failing immediately
+        self.con.busy_timeout(BUSY_TIMEOUT)?;
+        self.con.pragma_update(None, "foreign_keys", true)?;
+        Ok(())
+    }
+
+    /// Executes `f` atomically.
+    ///
+    /// All changes made by `f` are committed if it succeeds and rolled back
+    /// if it fails or panics.
+    /// Transactions can be nested, an inner transaction becomes a savepoint
+    /// of the outer one and is only persisted if the outer one succeeds too.
+    pub fn transaction<T>(&self, f: impl FnOnce(&Database) -> Result<T>) -> Result<T> {
+        let transaction = Transaction::begin(&self.con)?;
+        let result = f(self)?;
+        transaction.commit()?;
+        Ok(result)
     }
 
-    /// Creates a rollback point.
-    /// If any statement on a transaction fails, all changes are rolled back
-    /// to the point before this function is called.
+    /// Executes `f` in a transaction that is always rolled back.
     ///
-    /// ## Safety
-    /// This operation is only safe if called once.
-    /// Stacking transactions on top of each other is not allowed!
-    pub fn transaction(&self) -> rusqlite::Result<rusqlite::Transaction> {
-        let con = unsafe { &mut *(addr_of!(self.con) as *mut Connection) };
-        con.transaction()
+    /// This previews the result of `f` without changing the database.
+    pub fn dry_run<T>(&self, f: impl FnOnce(&Database) -> Result<T>) -> Result<T> {
+        let _transaction = Transaction::begin(&self.con)?;
+        f(self)
+    }
+}
+
+/// An open transaction or savepoint, rolled back if dropped before it is committed.
+struct Transaction<'a> {
+    con: &'a Connection,
+    nested: bool,
+    open: bool,
+}
+
+impl<'a> Transaction<'a> {
+    fn begin(con: &'a Connection) -> Result<Self> {
+        // savepoints with the same name are released in reverse order
+        let nested = !con.is_autocommit();
+        con.execute_batch(if nested {
+            "savepoint nested"
+        } else {
+            "begin immediate"
+        })?;
+        Ok(Self {
+            con,
+            nested,
+            open: true,
+        })
+    }
+
+    fn commit(mut self) -> Result<()> {
+        self.open = false;
+        self.con.execute_batch(if self.nested {
+            "release nested"
+        } else {
+            "commit"
+        })?;
+        Ok(())
+    }
+}
+
+impl Drop for Transaction<'_> {
+    fn drop(&mut self) {
+        if self.open {
+            let result = self.con.execute_batch(if self.nested {
+                "rollback to nested; release nested"
+            } else {
+                "rollback"
+            });
+            if let Err(e) = result {
+                error!("Rollback: {e}");
+            }
+        }
+    }
+}
+
+/// Connection pool to a project database.
+pub type Pool = r2d2::Pool<Manager>;
+
+/// Creates a connection pool with `size` connections to the given project database.
+///
+/// Getting a connection fails with [`Error::DatabaseUnavailable`]
+/// if none becomes free within `timeout`.
+pub fn pool(path: Cow<'_, Path>, key: Option<&str>, size: u32, timeout: Duration) -> Result<Pool> {
+    r2d2::Pool::builder()
+        .max_size(size)
+        .connection_timeout(timeout)
+        .build(Manager {
+            path: path.into_owned(),
+            key: key.map(Into::into),
+        })
+        .map_err(|e| {
+            error!("Pool: {e}");
+            Error::DatabaseUnavailable
+        })
+}
+
+/// Opens the pooled connections of a [`Pool`].
+pub struct Manager {
+    path: PathBuf,
+    key: Option<String>,
+}
+impl fmt::Debug for Manager {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Manager").field("path", &self.path).finish()
+    }
+}
+
+impl r2d2::ManageConnection for Manager {
+    type Connection = Database;
+    type Error = Error;
+
+    fn connect(&self) -> Result<Database> {
+        Ok(Database::open(Cow::from(&self.path), self.key.as_deref())?.0)
+    }
+
+    fn is_valid(&self, db: &mut Database) -> Result<()> {
+        Ok(db.con.execute_batch("")?)
+    }
+
+    fn has_broken(&self, _db: &mut Database) -> bool {
+        false
+    }
+}
+
+/// Current schema version of the project database.
+///
+/// The version is stored in the `user_version` pragma of the database file.
+/// Version 1 is the initial schema of [`create`], every later version is
+/// reached by applying the next entry of [`MIGRATIONS`].
+pub const VERSION: u32 = 1 + MIGRATIONS.len() as u32;
+
+/// Upgrades the schema by exactly one version.
+type Migration = fn(&Database) -> Result<()>;
+
+/// Ordered list of migrations, `MIGRATIONS[i]` upgrades version `i + 1` to `i + 2`.
+///
+/// Migrations must never be changed or removed once released,
+/// new ones are only appended.
+const MIGRATIONS: &[Migration] = &[
+    foreign_keys,
+    super::fulltext::migrate,
+    super::audit::migrate,
+    super::trash::migrate,
+    super::history::migrate,
+    super::role::migrate,
+    super::criminal::migrate,
+    super::status::migrate,
+    super::hearing::migrate,
+];
+
+/// Returns the schema version of the database.
+pub fn version(db: &Database) -> Result<u32> {
+    Ok(db
+        .con
+        .pragma_query_value(None, "user_version", |row| row.get(0))?)
+}
+
+/// Migrates the database to the current [`VERSION`].
+///
+/// Returns whether any migration was applied.
+/// Databases created by a newer release are refused with
+/// [`Error::UnsupportedProjectVersion`].
+pub fn migrate(db: &Database) -> Result<bool> {
+    let mut version = version(db)?;
+    if version == 0 {
+        // Databases created before the schema was versioned
+        let tables: usize = db.con.query_row(
+            "select count(*) from sqlite_master where type='table' and name='user'",
+            [],
+            |row| row.get(0),
+        )?;
+        if tables == 0 {
+            error!("Database has no project tables");
+            return Err(Error::NoProject);
+        }
+        db.con.pragma_update(None, "user_version", 1)?;
+        version = 1;
+    }
+    if version > VERSION {
+        error!("Database version {version} is newer than the supported version {VERSION}");
+        return Err(Error::UnsupportedProjectVersion);
     }
+    if version == VERSION {
+        return Ok(false);
+    }
+
+    // Tables are rebuilt by the migrations, which is only possible
+    // without enforced foreign keys. They can't be toggled within a transaction.
+    db.con.pragma_update(None, "foreign_keys", false)?;
+    let result = db.transaction(|db| {
+        for (i, migration) in MIGRATIONS.iter().enumerate().skip(version as usize - 1) {
+            migration(db)?;
+            db.con.pragma_update(None, "user_version", i as u32 + 2)?;
+        }
+        Ok(true)
+    });
+    db.con.pragma_update(None, "foreign_keys", true)?;
+
+    for (table, account) in orphans(db)? {
+        warn!("Entry of {table} references the missing user '{account}'");
+    }
+    result
 }
 
+/// Migrates an unversioned database only up to the `target` version,
+/// to prepare the data that a later migration finds.
+#[cfg(test)]
+pub(crate) fn migrate_to(db: &Database, target: u32) -> Result<()> {
+    db.con.pragma_update(None, "foreign_keys", false)?;
+    for (i, migration) in MIGRATIONS.iter().enumerate().take(target as usize - 1) {
+        migration(db)?;
+        db.con.pragma_update(None, "user_version", i as u32 + 2)?;
+    }
+    Ok(())
+}
+
+/// Returns all entries referencing missing users as `(table, account)`.
+///
+/// Entries can only become orphans if they were created before
+/// foreign keys were enforced.
+pub fn orphans(db: &Database) -> Result<Vec<(String, String)>> {
+    let mut stmt = db.con.prepare(
+        "select 'workless', account from workless \
+        where rowid in (select rowid from pragma_foreign_key_check('workless')) \
+        union all \
+        select 'criminal', account from criminal \
+        where rowid in (select rowid from pragma_foreign_key_check('criminal'))",
+    )?;
+    let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+    Ok(rows.collect::<rusqlite::Result<_>>()?)
+}
+
+/// Maps violated foreign key constraints to [`Error::UnknownAccount`].
+pub fn unknown_account(e: rusqlite::Error) -> Error {
+    if is_foreign_key_violation(&e) {
+        Error::UnknownAccount
+    } else {
+        e.into()
+    }
+}
+
+/// Returns whether the error is a violated foreign key constraint.
+pub fn is_foreign_key_violation(e: &rusqlite::Error) -> bool {
+    matches!(
+        e,
+        rusqlite::Error::SqliteFailure(e, _)
+            if e.extended_code == rusqlite::ffi::SQLITE_CONSTRAINT_FOREIGNKEY
+    )
+}
+
+/// Initial schema of version 1.
+pub(crate) const CREATE_TABLES: &str = "\
+create table user ( \
+    account text not null primary key, \
+    forename text not null, \
+    surname text not null, \
+    role text not null); \
+\
+create table workless ( \
+    account text not null, \
+    old_company text not null, \
+    date_of_dismiss text not null, \
+    currently integer not null default 1, \
+    new_company text not null, \
+    total_time text text not null, \
+    primary key (account, old_company, date_of_dismiss)); \
+\
+create table criminal ( \
+    account text not null, \
+    kind text not null, \
+    accuser text not null, \
+    police_consultant text not null, \
+    lawyer_culprit text not null, \
+    lawyer_accuser text not null, \
+    facts text not null, \
+    time_of_crime text not null, \
+    location_of_crime text not null, \
+    note text not null, \
+    verdict text not null, \
+    primary key (account, kind));
+\
+create table login ( \
+    user text not null primary key, \
+    hash text not null, \
+    salt text not null, \
+    access_user int default 0, \
+    access_workless int default 0, \
+    access_criminal int default 0); \
+";
+
+/// Creates the project tables in the current [`VERSION`].
 pub fn create(db: &Database) -> Result<()> {
-    const CREATE_TABLES: &str = "\
-    create table user ( \
-        account text not null primary key, \
-        forename text not null, \
-        surname text not null, \
-        role text not null); \
-    \
-    create table workless ( \
-        account text not null, \
+    db.transaction(|db| {
+        db.con.execute_batch(CREATE_TABLES)?;
+        for migration in MIGRATIONS {
+            migration(db)?;
+        }
+        db.con.pragma_update(None, "user_version", VERSION)?;
+        Ok(())
+    })
+}
+
+/// Version 2: Enforces that workless and criminal entries belong to existing users.
+///
+/// Logins are kept in sync by triggers instead of foreign keys,
+/// because the admin does not need a user.
+fn foreign_keys(db: &Database) -> Result<()> {
+    const MIGRATION: &str = "\
+    create table workless_new ( \
+        account text not null references user(account) \
+            on update cascade on delete cascade, \
         old_company text not null, \
         date_of_dismiss text not null, \
         currently integer not null default 1, \
         new_company text not null, \
-        total_time text text not null, \
+        total_time text not null, \
         primary key (account, old_company, date_of_dismiss)); \
+    insert into workless_new select * from workless; \
+    drop table workless; \
+    alter table workless_new rename to workless; \
     \
-    create table criminal ( \
-        account text not null, \
+    create table criminal_new ( \
+        account text not null references user(account) \
+            on update cascade on delete cascade, \
         kind text not null, \
         accuser text not null, \
         police_consultant text not null, \
@@ -212,53 +787,22 @@ pub fn create(db: &Database) -> Result<()> {
         location_of_crime text not null, \
         note text not null, \
         verdict text not null, \
-        primary key (account, kind));
+        primary key (account, kind)); \
+    insert into criminal_new select * from criminal; \
+    drop table criminal; \
+    alter table criminal_new rename to criminal; \
     \
-    create table login ( \
-        user text not null primary key, \
-        hash text not null, \
-        salt text not null, \
-        access_user int default 0, \
-        access_workless int default 0, \
-        access_criminal int default 0); \
+    create trigger user_update_login after update of account on user begin \
+        update login set user = new.account where user = old.account; \
+    end; \
+    create trigger user_delete_login after delete on user begin \
+        delete from login where user = old.account; \
+    end; \
     ";
-
-    let transaction = db.transaction()?;
-    transaction.execute_batch(CREATE_TABLES)?;
-    transaction.commit()?;
+    db.con.execute_batch(MIGRATION)?;
     Ok(())
 }
 
-//Fetches User Data from a file. Performance might not be the best.
-//Ignore the Error messages!
-pub fn fetch_user_data(db: &Database, path: Cow<'_, Path>, div: &str) -> Result<()> {
-    if path.exists() {
-        let reader = BufReader::new(File::open(path)?);
-        for i in reader.lines() {
-            let line = i?;
-            let mut lines = line.split(div);
-            let user = User {
-                account: lines.next().unwrap().into(),
-                forename: lines.next().unwrap().into(),
-                surname: lines.next().unwrap().into(),
-                role: lines.next().unwrap().into(),
-            };
-            if super::user::add(db, &user).is_err()
-                && (user.role == "Lehrer"
-                    || (user.role.starts_with("Klasse")
-                        && super::user::fetch(db, &user.account)?.role != "Lehrer")
-                        && !user.role.contains("Lehrer")
-                        && !user.role.contains("Bio"))
-            {
-                super::user::update(db, &user.account, &user)?;
-            }
-        }
-        Ok(())
-    } else {
-        Err(Error::FileNotFound)
-    }
-}
-
 //Fetches Logins from a file. Performance might not be the best.
 //Ignore the Error messages!
 pub fn fetch_logins(db: &Database, path: Cow<'_, Path>, div: &str) -> Result<()> {
@@ -274,10 +818,122 @@ pub fn fetch_logins(db: &Database, path: Cow<'_, Path>, div: &str) -> Result<()>
                 access_workless: lines.next().unwrap().into(),
                 access_criminal: lines.next().unwrap().into(),
             };
-            super::login::add(db, login)?;
+            super::login::add(db, super::audit::SYSTEM, login)?;
         }
         Ok(())
     } else {
         Err(Error::FileNotFound)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::db::project::{
+        create, migrate, orphans, version, Database, Error, CREATE_TABLES, VERSION,
+    };
+    use crate::db::role::{self, Role, RoleKind};
+    use crate::db::user::{self, User};
+
+    #[test]
+    fn versioning() {
+        let db = Database::memory().unwrap();
+        assert!(matches!(migrate(&db), Err(Error::NoProject)));
+
+        create(&db).unwrap();
+        assert_eq!(version(&db).unwrap(), VERSION);
+        assert!(!migrate(&db).unwrap());
+
+        db.con
+            .pragma_update(None, "user_version", VERSION + 1)
+            .unwrap();
+        assert!(matches!(
+            migrate(&db),
+            Err(Error::UnsupportedProjectVersion)
+        ));
+    }
+
+    #[test]
+    fn migrate_foreign_keys() {
+        let db = Database::memory().unwrap();
+        // unversioned schema with an orphaned entry
+        db.con.execute_batch(CREATE_TABLES).unwrap();
+        db.con
+            .execute(
+                "insert into workless values ('nobody', 'bars', '2023-06-01', 0, '', '')",
+                [],
+            )
+            .unwrap();
+
+        assert!(migrate(&db).unwrap());
+        assert_eq!(
+            orphans(&db).unwrap(),
+            [("workless".to_string(), "nobody".to_string())]
+        );
+    }
+
+    #[test]
+    fn nested_transactions() {
+        let db = Database::memory().unwrap();
+        create(&db).unwrap();
+        role::add(&db, "test", &Role::new("Demo", RoleKind::Other)).unwrap();
+
+        let user = |account: &str| User {
+            account: account.into(),
+            forename: "Foo".into(),
+            surname: "Bar".into(),
+            role: "Demo".into(),
+        };
+
+        db.transaction(|db| {
+            user::add(db, "test", &user("foo"))?;
+            // the failing inner transaction is rolled back on its own
+            let inner = db.transaction(|db| {
+                user::add(db, "test", &user("bar"))?;
+                Err::<(), _>(Error::Logic)
+            });
+            assert!(inner.is_err());
+            Ok(())
+        })
+        .unwrap();
+        assert!(user::fetch(&db, "foo").is_ok());
+        assert!(user::fetch(&db, "bar").is_err());
+
+        // failing outer transactions roll back committed inner ones
+        let outer = db.transaction(|db| {
+            db.transaction(|db| user::add(db, "test", &user("baz")))?;
+            Err::<(), _>(Error::Logic)
+        });
+        assert!(outer.is_err());
+        assert!(user::fetch(&db, "baz").is_err());
+        assert!(db.con.is_autocommit());
+    }
+
+    #[test]
+    fn errors() {
+        let db = Database::memory().unwrap();
+        create(&db).unwrap();
+        role::add(&db, "test", &Role::new("Demo", RoleKind::Other)).unwrap();
+
+        let user = User {
+            account: "foo.bar".into(),
+            forename: "Foo".into(),
+            surname: "Bar".into(),
+            role: "Demo".into(),
+        };
+        assert_eq!(user::fetch(&db, &user.account), Err(Error::NothingFound));
+        user::add(&db, "test", &user).unwrap();
+        assert_eq!(user::add(&db, "test", &user), Err(Error::AlreadyExists));
+        user::delete(&db, "test", &user.account, user::OnDelete::Cascade).unwrap();
+        assert_eq!(user::add(&db, "test", &user), Err(Error::Trashed));
+
+        let error = user::delete(&db, "test", " ", user::OnDelete::Cascade).unwrap_err();
+        assert_eq!(
+            serde_json::to_value(error).unwrap(),
+            serde_json::json!({
+                "code": "InvalidUser",
+                "message": "Invalid user",
+                "details": [{"field": "account", "message": "must not be empty"}],
+            })
+        );
+    }
+}
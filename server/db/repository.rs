@@ -0,0 +1,353 @@
+use chrono::{DateTime, Utc};
+use rusqlite::{params_from_iter, Params, ToSql};
+
+use crate::db::audit::{self, Audited};
+use crate::db::history::Version;
+use crate::db::project::{DBIter, Database, Error, FromRow, Result};
+use crate::db::trash::Trashed;
+use crate::db::validate::Validate;
+
+/// Table of audited records with a trash bin and a history.
+///
+/// The generic functions of this module implement fetching, adding, updating,
+/// deleting, restoring and purging for every table, the entity modules only
+/// add their searches and checks.
+pub trait Repository: FromRow + Audited + Validate {
+    /// Name of the table, the history is kept in `{TABLE}_history`
+    const TABLE: &'static str;
+    /// Columns of the table, in the order of [`Repository::values`]
+    const COLUMNS: &'static [&'static str];
+    /// Columns of the primary key
+    const KEY: &'static [&'static str];
+    /// Order of listings, like the trash bin
+    const ORDER: &'static str;
+
+    /// Primary key, with the values of the [`Repository::KEY`] columns
+    type Key;
+
+    /// Primary key of this record.
+    fn id(&self) -> Self::Key;
+    /// Values of the key columns.
+    fn bind(key: &Self::Key) -> Vec<&dyn ToSql>;
+    /// Values of all columns.
+    fn values(&self) -> Vec<&dyn ToSql>;
+    /// Copy of the record with trimmed text fields, as it is stored.
+    fn trimmed(&self) -> Self;
+
+    /// Copy of a new record with its generated keys, like the surrogate id of a case.
+    fn assign(self, _db: &Database) -> Result<Self> {
+        Ok(self)
+    }
+    /// Checks the references of the record, before it is written or restored.
+    fn check(&self, _db: &Database) -> Result<()> {
+        Ok(())
+    }
+    /// Maps the errors of inserts and updates, like violated foreign keys.
+    fn map_err(e: rusqlite::Error) -> Error {
+        e.into()
+    }
+}
+
+fn columns<T: Repository>() -> String {
+    T::COLUMNS.join(", ")
+}
+
+fn key_filter<T: Repository>() -> String {
+    T::KEY
+        .iter()
+        .map(|c| format!("{c}=?"))
+        .collect::<Vec<_>>()
+        .join(" and ")
+}
+
+/// Returns the record with the given `key`, if it is not in the trash bin.
+pub fn fetch<T: Repository>(db: &Database, key: &T::Key) -> Result<T> {
+    Ok(db.con.query_row(
+        &format!(
+            "select {} from {} where {} and deleted_at is null",
+            columns::<T>(),
+            T::TABLE,
+            key_filter::<T>()
+        ),
+        &*T::bind(key),
+        T::from_row,
+    )?)
+}
+
+/// Adds a new record and returns it as it was stored.
+pub fn add<T: Repository>(db: &Database, actor: &str, item: &T) -> Result<T> {
+    item.validate()?;
+    db.transaction(|db| {
+        let item = item.trimmed().assign(db)?;
+        let key = item.id();
+        item.check(db)?;
+        let placeholders = vec!["?"; T::COLUMNS.len()].join(", ");
+        db.con
+            .execute(
+                &format!(
+                    "insert into {} ({}) values ({placeholders})",
+                    T::TABLE,
+                    columns::<T>()
+                ),
+                &*item.values(),
+            )
+            .map_err(|e| conflict::<T>(db, &key, T::map_err(e)))?;
+        let item = fetch::<T>(db, &key)?;
+        audit::insert(db, actor, &item)?;
+        Ok(item)
+    })
+}
+
+/// Tells a conflict with a record in the trash bin apart from one with a stored record.
+fn conflict<T: Repository>(db: &Database, key: &T::Key, error: Error) -> Error {
+    match error {
+        Error::AlreadyExists if fetch_trashed::<T>(db, key).is_ok() => Error::Trashed,
+        error => error,
+    }
+}
+
+/// Replaces the record with the `previous` key.
+pub fn update<T: Repository>(
+    db: &Database,
+    actor: &str,
+    previous: &T::Key,
+    item: &T,
+) -> Result<()> {
+    item.validate()?;
+    let item = item.trimmed();
+    let key = item.id();
+    db.transaction(|db| {
+        let before = fetch::<T>(db, previous)?;
+        item.check(db)?;
+        let assignments = T::COLUMNS
+            .iter()
+            .map(|c| format!("{c}=?"))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let params = item.values().into_iter().chain(T::bind(previous));
+        db.con
+            .execute(
+                &format!(
+                    "update {} set {assignments} where {}",
+                    T::TABLE,
+                    key_filter::<T>()
+                ),
+                params_from_iter(params),
+            )
+            .map_err(|e| conflict::<T>(db, &key, T::map_err(e)))?;
+        audit::update(db, actor, &before, &fetch::<T>(db, &key)?)
+    })
+}
+
+/// Moves the record into the trash bin.
+pub fn delete<T: Repository>(db: &Database, actor: &str, key: &T::Key) -> Result<()> {
+    db.transaction(|db| {
+        let before = fetch::<T>(db, key)?;
+        let deleted_at = Utc::now();
+        let params = std::iter::once(&deleted_at as &dyn ToSql).chain(T::bind(key));
+        db.con.execute(
+            &format!(
+                "update {} set deleted_at=? where {}",
+                T::TABLE,
+                key_filter::<T>()
+            ),
+            params_from_iter(params),
+        )?;
+        audit::delete(db, actor, &before)
+    })
+}
+
+/// Returns the records in the trash bin, the most recently deleted first.
+pub fn trash<T: Repository>(db: &Database, limit: usize) -> Result<Vec<Trashed<T>>> {
+    query(
+        db,
+        &format!(
+            "select {}, deleted_at from {} \
+            where deleted_at is not null \
+            order by deleted_at desc, {} \
+            limit ?",
+            columns::<T>(),
+            T::TABLE,
+            T::ORDER
+        ),
+        [limit],
+    )
+}
+
+/// Returns the record with the given `key` from the trash bin.
+pub fn fetch_trashed<T: Repository>(db: &Database, key: &T::Key) -> Result<Trashed<T>> {
+    Ok(db.con.query_row(
+        &format!(
+            "select {}, deleted_at from {} where {} and deleted_at is not null",
+            columns::<T>(),
+            T::TABLE,
+            key_filter::<T>()
+        ),
+        &*T::bind(key),
+        Trashed::from_row,
+    )?)
+}
+
+/// Restores the record from the trash bin and returns it.
+pub fn restore<T: Repository>(db: &Database, actor: &str, key: &T::Key) -> Result<Trashed<T>> {
+    db.transaction(|db| {
+        let trashed = fetch_trashed::<T>(db, key)?;
+        trashed.item.check(db)?;
+        db.con.execute(
+            &format!(
+                "update {} set deleted_at=null where {}",
+                T::TABLE,
+                key_filter::<T>()
+            ),
+            &*T::bind(key),
+        )?;
+        audit::restore(db, actor, &trashed.item)?;
+        Ok(trashed)
+    })
+}
+
+/// Permanently deletes the record from the trash bin.
+pub fn purge<T: Repository>(db: &Database, actor: &str, key: &T::Key) -> Result<()> {
+    db.transaction(|db| {
+        let trashed = fetch_trashed::<T>(db, key)?;
+        db.con.execute(
+            &format!("delete from {} where {}", T::TABLE, key_filter::<T>()),
+            &*T::bind(key),
+        )?;
+        audit::purge(db, actor, &trashed.item)
+    })
+}
+
+/// Returns all revisions of the record, the oldest first.
+pub fn history<T: Repository>(db: &Database, key: &T::Key) -> Result<Vec<Version<T>>> {
+    query(
+        db,
+        &format!(
+            "select {}, deleted_at, valid_from, valid_to from {}_history \
+            where {} \
+            order by valid_from asc, rowid asc",
+            columns::<T>(),
+            T::TABLE,
+            key_filter::<T>()
+        ),
+        &*T::bind(key),
+    )
+}
+
+/// Returns the records as they were at the given `time`.
+pub fn as_of<T: Repository>(db: &Database, time: DateTime<Utc>, limit: usize) -> Result<Vec<T>> {
+    query(
+        db,
+        &format!(
+            "select {} from {}_history \
+            where valid_from <= ?1 and (valid_to is null or valid_to > ?1) \
+            and deleted_at is null \
+            order by {} \
+            limit ?2",
+            columns::<T>(),
+            T::TABLE,
+            T::ORDER
+        ),
+        rusqlite::params![time, limit],
+    )
+}
+
+/// Returns all rows of the `sql` query.
+pub fn query<T: FromRow>(db: &Database, sql: &str, params: impl Params) -> Result<Vec<T>> {
+    let mut stmt = db.con.prepare(sql)?;
+    let rows = stmt.query(params)?;
+    DBIter::new(rows).collect()
+}
+
+/// Returns the first column of the `sql` query without duplicates,
+/// in the order of the query.
+pub fn distinct(db: &Database, sql: &str, params: impl Params) -> Result<Vec<String>> {
+    let mut stmt = db.con.prepare(sql)?;
+    let values = stmt
+        .query_map(params, |row| row.get::<_, String>(0))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    let mut seen = std::collections::HashSet::new();
+    Ok(values
+        .into_iter()
+        .filter(|v| seen.insert(v.clone()))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::db::criminal::Criminal;
+    use crate::db::project::{create, Database, Error, FieldError};
+    use crate::db::repository::{self, Repository};
+    use crate::db::role::{self, Role, RoleKind};
+    use crate::db::user::User;
+
+    #[test]
+    fn repository() {
+        let db = Database::memory().unwrap();
+        create(&db).unwrap();
+        role::add(&db, "test", &Role::new("Demo", RoleKind::Other)).unwrap();
+
+        let user = User {
+            account: " foo.bar ".into(),
+            forename: "Foo".into(),
+            surname: "Bar".into(),
+            role: "Demo".into(),
+        };
+        let criminal = Criminal {
+            account: "foo.bar".into(),
+            kind: "Theft".into(),
+            ..Default::default()
+        };
+        assert_eq!(
+            repository::add(&db, "test", &criminal),
+            Err(Error::UnknownAccount)
+        );
+        assert_eq!(
+            repository::add(&db, "test", &User::default()),
+            Err(Error::InvalidUser(vec![
+                FieldError::new("account", "must not be empty"),
+                FieldError::new("forename", "must not be empty"),
+                FieldError::new("surname", "must not be empty"),
+                FieldError::new("role", "must not be empty"),
+            ]))
+        );
+        repository::add(&db, "test", &user.trimmed()).unwrap();
+        // the key of the case is assigned when it is added
+        let criminal = repository::add(&db, "test", &criminal).unwrap();
+        let key = criminal.id();
+        assert_ne!(key, 0);
+        assert_eq!(repository::fetch::<Criminal>(&db, &key).unwrap(), criminal);
+
+        let changed = Criminal {
+            verdict: " Guilty ".into(),
+            ..criminal.clone()
+        };
+        repository::update(&db, "test", &key, &changed).unwrap();
+        assert_eq!(
+            repository::fetch::<Criminal>(&db, &key).unwrap().verdict,
+            "Guilty"
+        );
+
+        repository::delete::<Criminal>(&db, "test", &key).unwrap();
+        assert_eq!(
+            repository::fetch::<Criminal>(&db, &key),
+            Err(Error::NothingFound)
+        );
+        assert_eq!(repository::trash::<Criminal>(&db, 10).unwrap().len(), 1);
+        repository::restore::<Criminal>(&db, "test", &key).unwrap();
+        let history = repository::history::<Criminal>(&db, &key).unwrap();
+        assert_eq!(history.len(), 4);
+        assert!(history[2].deleted);
+        assert_eq!(history[3].item.verdict, "Guilty");
+
+        assert_eq!(
+            repository::distinct(
+                &db,
+                "select kind from criminal union all select kind from criminal",
+                [],
+            )
+            .unwrap(),
+            ["Theft"]
+        );
+    }
+}
@@ -0,0 +1,498 @@
+use rusqlite::types::{FromSql, FromSqlError, FromSqlResult, ToSql, ToSqlOutput, ValueRef};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::db::audit::{self, Audited, Entity};
+use crate::db::project::{DBIter, Database, Error, FieldError, FromRow, Result};
+use crate::db::user;
+use crate::db::validate::{self, Field, Validate, Value};
+
+/// Kind of a role.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum RoleKind {
+    /// School class or grade
+    Class,
+    Teacher,
+    Staff,
+    #[default]
+    Other,
+}
+
+impl RoleKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            RoleKind::Class => "class",
+            RoleKind::Teacher => "teacher",
+            RoleKind::Staff => "staff",
+            RoleKind::Other => "other",
+        }
+    }
+
+    /// Kind of a role that was only known by its name,
+    /// like the roles of imported users.
+    pub fn guess(name: &str) -> RoleKind {
+        let name = name.to_lowercase();
+        if name.starts_with("klasse") || name.starts_with("jahrgang") {
+            RoleKind::Class
+        } else if name.starts_with("lehrer") {
+            RoleKind::Teacher
+        } else {
+            RoleKind::Other
+        }
+    }
+}
+
+impl FromSql for RoleKind {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        match value.as_str()? {
+            "class" => Ok(RoleKind::Class),
+            "teacher" => Ok(RoleKind::Teacher),
+            "staff" => Ok(RoleKind::Staff),
+            "other" => Ok(RoleKind::Other),
+            _ => Err(FromSqlError::InvalidType),
+        }
+    }
+}
+
+impl ToSql for RoleKind {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        Ok(self.as_str().into())
+    }
+}
+
+/// Data object for a role, like a class, that is assigned to users.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, ToSchema)]
+pub struct Role {
+    /// Canonical name, unique regardless of the case
+    pub name: String,
+    /// Name shown to the users
+    pub display_name: String,
+    pub kind: RoleKind,
+    /// Enclosing role, like the grade of a class
+    pub parent: Option<String>,
+    /// Account of the homeroom teacher
+    pub teacher: Option<String>,
+}
+
+impl Role {
+    /// Role without parent or teacher, displayed by its name.
+    pub fn new(name: &str, kind: RoleKind) -> Role {
+        Role {
+            name: name.into(),
+            display_name: name.into(),
+            kind,
+            parent: None,
+            teacher: None,
+        }
+    }
+}
+
+impl Validate for Role {
+    const FIELDS: &'static [Field] = &[
+        Field::new("name", validate::NAME),
+        Field::new("display_name", validate::NAME),
+        Field::new("parent", validate::SHORT_TEXT),
+        Field::new("teacher", validate::SHORT_TEXT),
+    ];
+    fn fields(&self) -> Vec<Value<'_>> {
+        vec![
+            Value::Text(&self.name),
+            Value::Text(&self.display_name),
+            Value::Text(self.parent.as_deref().unwrap_or_default()),
+            Value::Text(self.teacher.as_deref().unwrap_or_default()),
+        ]
+    }
+    fn invalid(details: Vec<FieldError>) -> Error {
+        Error::InvalidRole(details)
+    }
+}
+
+impl Audited for Role {
+    const ENTITY: Entity = Entity::Role;
+    fn key(&self) -> String {
+        self.name.clone()
+    }
+}
+
+impl FromRow for Role {
+    fn from_row(row: &rusqlite::Row) -> rusqlite::Result<Role> {
+        Ok(Role {
+            name: row.get("name")?,
+            display_name: row.get("display_name")?,
+            kind: row.get("kind")?,
+            parent: row.get("parent")?,
+            teacher: row.get("teacher")?,
+        })
+    }
+}
+
+/// Name with trimmed and collapsed whitespace.
+pub fn canonical(name: &str) -> String {
+    name.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Returns the role with the given `name`, regardless of the case.
+pub fn fetch(db: &Database, name: &str) -> Result<Role> {
+    Ok(db.con.query_row(
+        "select name, display_name, kind, parent, teacher from role where name=?",
+        [canonical(name)],
+        Role::from_row,
+    )?)
+}
+
+/// Returns all roles, ordered by their names.
+pub fn all(db: &Database) -> Result<Vec<Role>> {
+    let mut stmt = db.con.prepare(
+        "select name, display_name, kind, parent, teacher from role \
+        order by name asc",
+    )?;
+    let rows = stmt.query([])?;
+    DBIter::new(rows).collect()
+}
+
+/// Returns the canonical name of the existing role with the given `name`.
+///
+/// Fails with [`Error::UnknownRole`] if there is no such role.
+pub fn resolve(db: &Database, name: &str) -> Result<String> {
+    match fetch(db, name) {
+        Ok(role) => Ok(role.name),
+        Err(Error::NothingFound) => Err(Error::UnknownRole),
+        Err(e) => Err(e),
+    }
+}
+
+/// Returns the canonical name of the role with the given `name`,
+/// adding the role first if it is unknown.
+///
+/// This is only meant for bulk imports, where the roles are not known in advance.
+pub fn ensure(db: &Database, actor: &str, name: &str) -> Result<String> {
+    match resolve(db, name) {
+        Err(Error::UnknownRole) => {
+            let role = Role::new(&canonical(name), RoleKind::guess(name));
+            add(db, actor, &role)?;
+            Ok(role.name)
+        }
+        result => result,
+    }
+}
+
+/// Fails if the parent or the teacher of the `role` are unknown,
+/// or if the role would become its own parent.
+fn check(db: &Database, role: &Role, previous: Option<&str>) -> Result<()> {
+    if let Some(teacher) = &role.teacher {
+        user::ensure_exists(db, teacher)?;
+    }
+    let mut parent = role.parent.clone();
+    // the depth is limited, in case the hierarchy already has a cycle
+    for _ in 0..100 {
+        let Some(name) = parent else {
+            return Ok(());
+        };
+        let name = canonical(&name);
+        if name.eq_ignore_ascii_case(&role.name)
+            || previous.is_some_and(|p| name.eq_ignore_ascii_case(p))
+        {
+            break;
+        }
+        parent = match fetch(db, &name) {
+            Ok(role) => role.parent,
+            Err(Error::NothingFound) => {
+                return Err(Error::InvalidRole(vec![FieldError::new(
+                    "parent",
+                    "unknown role",
+                )]))
+            }
+            Err(e) => return Err(e),
+        };
+    }
+    Err(Error::InvalidRole(vec![FieldError::new(
+        "parent",
+        "must not contain the role itself",
+    )]))
+}
+
+/// Copy of the role as it is stored, with canonical names.
+fn normalized(db: &Database, role: &Role) -> Result<Role> {
+    let parent = match role.parent.as_deref().map(str::trim) {
+        Some("") | None => None,
+        Some(parent) => match resolve(db, parent) {
+            Err(Error::UnknownRole) => {
+                return Err(Error::InvalidRole(vec![FieldError::new(
+                    "parent",
+                    "unknown role",
+                )]))
+            }
+            result => Some(result?),
+        },
+    };
+    Ok(Role {
+        name: canonical(&role.name),
+        display_name: role.display_name.trim().into(),
+        kind: role.kind,
+        parent,
+        teacher: role
+            .teacher
+            .as_deref()
+            .map(str::trim)
+            .filter(|t| !t.is_empty())
+            .map(Into::into),
+    })
+}
+
+/// Adds a new role.
+pub fn add(db: &Database, actor: &str, role: &Role) -> Result<()> {
+    role.validate()?;
+    db.transaction(|db| {
+        let role = normalized(db, role)?;
+        check(db, &role, None)?;
+        db.con.execute(
+            "insert into role (name, display_name, kind, parent, teacher) values (?, ?, ?, ?, ?)",
+            rusqlite::params![
+                role.name,
+                role.display_name,
+                role.kind,
+                role.parent,
+                role.teacher
+            ],
+        )?;
+        audit::insert(db, actor, &fetch(db, &role.name)?)
+    })
+}
+
+/// Updates the role, renaming it also renames it for its users and child roles.
+pub fn update(db: &Database, actor: &str, previous: &str, role: &Role) -> Result<()> {
+    role.validate()?;
+    db.transaction(|db| {
+        let before = fetch(db, previous)?;
+        let role = normalized(db, role)?;
+        check(db, &role, Some(&before.name))?;
+        // users are renamed by a trigger, child roles by the foreign key
+        db.con.execute(
+            "update role set name=?, display_name=?, kind=?, parent=?, teacher=? where name=?",
+            rusqlite::params![
+                role.name,
+                role.display_name,
+                role.kind,
+                role.parent,
+                role.teacher,
+                before.name
+            ],
+        )?;
+        audit::update(db, actor, &before, &fetch(db, &role.name)?)
+    })
+}
+
+/// Deletes the role.
+///
+/// Fails with [`Error::RoleReferenced`] if it is still assigned to users,
+/// including the ones in the trash bin, or if it has child roles.
+pub fn delete(db: &Database, actor: &str, name: &str) -> Result<()> {
+    db.transaction(|db| {
+        let before = fetch(db, name)?;
+        let referenced: bool = db.con.query_row(
+            "select exists (select 1 from user where role=?1) \
+            or exists (select 1 from role where parent=?1)",
+            [&before.name],
+            |row| row.get(0),
+        )?;
+        if referenced {
+            return Err(Error::RoleReferenced);
+        }
+        db.con
+            .execute("delete from role where name=?", [&before.name])?;
+        audit::delete(db, actor, &before)
+    })
+}
+
+/// Version 7: Roles are kept in their own table, which the users reference.
+///
+/// The role strings of the existing users are normalised: spellings that
+/// only differ in case or whitespace become one role, which is named like
+/// its most common spelling. The users reference the roles by triggers,
+/// because the user table can not be altered to have a foreign key.
+pub fn migrate(db: &Database) -> Result<()> {
+    db.con.execute_batch(
+        "create table role ( \
+            name text not null primary key collate nocase, \
+            display_name text not null, \
+            kind text not null default 'other', \
+            parent text references role(name) on update cascade on delete restrict, \
+            teacher text references user(account) on update cascade on delete set null);",
+    )?;
+
+    let spellings = {
+        let mut stmt = db.con.prepare(
+            "select role, count(*) from user \
+            group by role \
+            order by count(*) desc, role asc",
+        )?;
+        let rows = stmt.query_map([], |row| row.get::<_, String>(0))?;
+        rows.collect::<rusqlite::Result<Vec<_>>>()?
+    };
+    let mut roles: Vec<String> = Vec::new();
+    for spelling in spellings {
+        let name = canonical(&spelling);
+        let role = match roles.iter().find(|r| r.eq_ignore_ascii_case(&name)) {
+            Some(role) => role.clone(),
+            None => {
+                db.con.execute(
+                    "insert into role (name, display_name, kind) values (?1, ?1, ?2)",
+                    rusqlite::params![name, RoleKind::guess(&name)],
+                )?;
+                roles.push(name.clone());
+                name
+            }
+        };
+        if role != spelling {
+            db.con.execute(
+                "update user set role=? where role=?",
+                rusqlite::params![role, spelling],
+            )?;
+        }
+    }
+
+    db.con.execute_batch(
+        "create trigger user_role_insert before insert on user \
+            when not exists (select 1 from role where name = new.role) begin \
+            select raise(abort, 'UnknownRole'); \
+        end; \
+        create trigger user_role_update before update of role on user \
+            when not exists (select 1 from role where name = new.role) begin \
+            select raise(abort, 'UnknownRole'); \
+        end; \
+        create trigger role_update_user after update of name on role begin \
+            update user set role = new.name where role = old.name; \
+        end; \
+        create trigger role_delete_user before delete on role \
+            when exists (select 1 from user where role = old.name) begin \
+            select raise(abort, 'RoleReferenced'); \
+        end;",
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::db::project::{create, Database, Error, FieldError};
+    use crate::db::role::{self, Role, RoleKind};
+    use crate::db::user::{self, User};
+
+    #[test]
+    fn roles() {
+        let db = Database::memory().unwrap();
+        create(&db).unwrap();
+
+        let grade = Role::new("Jahrgang 5", RoleKind::Class);
+        role::add(&db, "test", &grade).unwrap();
+        let class = Role {
+            parent: Some("jahrgang 5".into()),
+            ..Role::new("Klasse  5a ", RoleKind::Class)
+        };
+        role::add(&db, "test", &class).unwrap();
+        assert_eq!(
+            role::add(&db, "test", &Role::new("KLASSE 5A", RoleKind::Class)),
+            Err(Error::AlreadyExists)
+        );
+        let fetched = role::fetch(&db, "klasse 5A").unwrap();
+        assert_eq!(fetched.name, "Klasse 5a");
+        assert_eq!(fetched.parent.as_deref(), Some("Jahrgang 5"));
+
+        let user = User {
+            account: "foo.bar".into(),
+            forename: "Foo".into(),
+            surname: "Bar".into(),
+            role: "klasse 5A".into(),
+        };
+        user::add(&db, "test", &user).unwrap();
+        assert_eq!(user::fetch(&db, "foo.bar").unwrap().role, "Klasse 5a");
+        let typo = User {
+            role: "Klase 5a".into(),
+            ..user.clone()
+        };
+        assert_eq!(
+            user::update(&db, "test", "foo.bar", &typo),
+            Err(Error::UnknownRole)
+        );
+
+        // renaming cascades to the users and child roles
+        let grade = Role::new("Jahrgang 05", RoleKind::Class);
+        role::update(&db, "test", "jahrgang 5", &grade).unwrap();
+        assert_eq!(
+            role::fetch(&db, "Klasse 5a").unwrap().parent.as_deref(),
+            Some("Jahrgang 05")
+        );
+        let renamed = Role {
+            parent: Some("Jahrgang 05".into()),
+            teacher: Some("foo.bar".into()),
+            ..Role::new("Klasse 5b", RoleKind::Class)
+        };
+        role::update(&db, "test", "Klasse 5a", &renamed).unwrap();
+        assert_eq!(user::fetch(&db, "foo.bar").unwrap().role, "Klasse 5b");
+
+        let cycle = Role {
+            parent: Some("Klasse 5b".into()),
+            ..grade.clone()
+        };
+        assert_eq!(
+            role::update(&db, "test", "Jahrgang 05", &cycle),
+            Err(Error::InvalidRole(vec![FieldError::new(
+                "parent",
+                "must not contain the role itself"
+            )]))
+        );
+        assert_eq!(
+            role::delete(&db, "test", "Jahrgang 05"),
+            Err(Error::RoleReferenced)
+        );
+        assert_eq!(
+            role::delete(&db, "test", "Klasse 5b"),
+            Err(Error::RoleReferenced)
+        );
+        user::delete(&db, "test", "foo.bar", user::OnDelete::Cascade).unwrap();
+        user::purge(&db, "test", "foo.bar").unwrap();
+        role::delete(&db, "test", "Klasse 5b").unwrap();
+        role::delete(&db, "test", "Jahrgang 05").unwrap();
+        assert!(role::all(&db).unwrap().is_empty());
+    }
+
+    #[test]
+    fn migration() {
+        let db = Database::memory().unwrap();
+        create(&db).unwrap();
+        // back to the previous version
+        db.con
+            .execute_batch(
+                "drop trigger user_role_insert; \
+                drop trigger user_role_update; \
+                drop trigger role_update_user; \
+                drop trigger role_delete_user; \
+                drop table role;",
+            )
+            .unwrap();
+        for (account, role) in [
+            ("a", "Klasse 5a"),
+            ("b", "Klasse 5A"),
+            ("c", " klasse  5a"),
+            ("d", "Klasse 5a"),
+            ("e", "Lehrer"),
+        ] {
+            db.con
+                .execute(
+                    "insert into user values (?, 'Foo', 'Bar', ?, null)",
+                    [account, role],
+                )
+                .unwrap();
+        }
+
+        role::migrate(&db).unwrap();
+        let roles = role::all(&db).unwrap();
+        let names = roles.iter().map(|r| r.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, ["Klasse 5a", "Lehrer"]);
+        assert_eq!(roles[0].kind, RoleKind::Class);
+        assert_eq!(roles[1].kind, RoleKind::Teacher);
+        for account in ["a", "b", "c", "d"] {
+            assert_eq!(user::fetch(&db, account).unwrap().role, "Klasse 5a");
+        }
+    }
+}
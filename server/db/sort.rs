@@ -0,0 +1,104 @@
+use crate::db::project::{Error, Result};
+
+/// Sortable columns of a search, pairs of the client-facing name and the SQL column.
+pub type SortColumns = &'static [(&'static str, &'static str)];
+
+/// Order of the search results chosen by the client.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Sort {
+    /// SQL columns with whether they are sorted descending
+    keys: Vec<(&'static str, bool)>,
+}
+
+impl Sort {
+    /// Parses a comma separated list like `-date_of_dismiss,surname`.
+    ///
+    /// A leading `-` sorts descending. Only the given `columns` are allowed.
+    pub fn parse(text: &str, columns: SortColumns) -> Result<Sort> {
+        let mut keys = Vec::new();
+        for name in text.split(',').map(str::trim).filter(|n| !n.is_empty()) {
+            let (name, descending) = match name.strip_prefix('-') {
+                Some(name) => (name, true),
+                None => (name, false),
+            };
+            let Some(&(_, column)) = columns.iter().find(|(n, _)| *n == name) else {
+                return Err(Error::InvalidSort(name.into()));
+            };
+            keys.push((column, descending));
+        }
+        Ok(Sort { keys })
+    }
+
+    /// Order by clause, the `default` order breaks ties.
+    pub fn order_by(&self, default: &str) -> String {
+        let mut terms = self
+            .keys
+            .iter()
+            .map(|(column, descending)| {
+                format!("{column} {}", if *descending { "desc" } else { "asc" })
+            })
+            .collect::<Vec<_>>();
+        terms.push(default.into());
+        format!("order by {}", terms.join(", "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::db::page::Paging;
+    use crate::db::project::{create, Database, Error};
+    use crate::db::role::{self, Role, RoleKind};
+    use crate::db::sort::Sort;
+    use crate::db::user::{self, User};
+    use crate::db::workless::{self, Workless, WorklessSearch};
+    use chrono::NaiveDate;
+
+    #[test]
+    fn sort() {
+        assert_eq!(
+            Sort::parse("surname,-password", user::SORT_COLUMNS),
+            Err(Error::InvalidSort("password".into()))
+        );
+        assert_eq!(
+            Sort::parse("-surname, account", user::SORT_COLUMNS)
+                .unwrap()
+                .order_by("rank"),
+            "order by user.surname desc, user.account asc, rank"
+        );
+
+        let db = Database::memory().unwrap();
+        create(&db).unwrap();
+        role::add(&db, "test", &Role::new("Demo", RoleKind::Other)).unwrap();
+        for (account, surname, day) in [("a.z", "Zett", 1), ("b.a", "Ah", 2), ("c.m", "Em", 2)] {
+            let user = User {
+                account: account.into(),
+                forename: "Foo".into(),
+                surname: surname.into(),
+                role: "Demo".into(),
+            };
+            user::add(&db, "test", &user).unwrap();
+            let workless = Workless {
+                account: account.into(),
+                old_company: "bars".into(),
+                date_of_dismiss: NaiveDate::from_ymd_opt(2023, 6, day).unwrap(),
+                ..Default::default()
+            };
+            workless::add(&db, "test", &workless).unwrap();
+        }
+
+        let sort = Sort::parse("-date_of_dismiss,surname", workless::SORT_COLUMNS).unwrap();
+        let result = workless::search(
+            &db,
+            WorklessSearch::new("", "%", "%"),
+            &sort,
+            Paging::default(),
+        )
+        .unwrap()
+        .items;
+        let accounts = result
+            .iter()
+            .map(|w| w.account.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(accounts, ["b.a", "c.m", "a.z"]);
+    }
+}
@@ -40,6 +40,6 @@ impl FromRow for Stats {
 pub fn fetch(db: &Database) -> Result<Stats> {
     const STATS: &str = "\
         select \
-        (select count(*) from user) as users";
+        (select count(*) from user where deleted_at is null) as users";
     Ok(db.con.query_row(STATS, [], Stats::from_row)?)
 }
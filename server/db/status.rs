@@ -0,0 +1,346 @@
+use std::fmt;
+use std::str::FromStr;
+
+use chrono::{DateTime, Utc};
+use rusqlite::types::{FromSql, FromSqlError, FromSqlResult, ToSql, ToSqlOutput, ValueRef};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::db::project::{DBIter, Database, Error, FromRow, Result};
+
+/// Status of a criminal case.
+///
+/// A case starts as reported and only moves along the [`CaseStatus::next`] ones.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum CaseStatus {
+    #[default]
+    Reported,
+    Investigating,
+    Charged,
+    TrialScheduled,
+    Verdict,
+    Appeal,
+    Closed,
+}
+
+impl CaseStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            CaseStatus::Reported => "reported",
+            CaseStatus::Investigating => "investigating",
+            CaseStatus::Charged => "charged",
+            CaseStatus::TrialScheduled => "trial_scheduled",
+            CaseStatus::Verdict => "verdict",
+            CaseStatus::Appeal => "appeal",
+            CaseStatus::Closed => "closed",
+        }
+    }
+
+    /// Statuses the case can change to.
+    ///
+    /// Cases can be closed at any time before the trial, e.g. if the charges
+    /// are dropped. An appeal leads to another trial or ends the case.
+    pub fn next(self) -> &'static [CaseStatus] {
+        match self {
+            CaseStatus::Reported => &[CaseStatus::Investigating, CaseStatus::Closed],
+            CaseStatus::Investigating => &[CaseStatus::Charged, CaseStatus::Closed],
+            CaseStatus::Charged => &[CaseStatus::TrialScheduled, CaseStatus::Closed],
+            CaseStatus::TrialScheduled => &[CaseStatus::Verdict],
+            CaseStatus::Verdict => &[CaseStatus::Appeal, CaseStatus::Closed],
+            CaseStatus::Appeal => &[CaseStatus::TrialScheduled, CaseStatus::Closed],
+            CaseStatus::Closed => &[],
+        }
+    }
+
+    /// Whether the case can change from this status to `next`.
+    pub fn allows(self, next: CaseStatus) -> bool {
+        self.next().contains(&next)
+    }
+}
+
+impl fmt::Display for CaseStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for CaseStatus {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "reported" => Ok(CaseStatus::Reported),
+            "investigating" => Ok(CaseStatus::Investigating),
+            "charged" => Ok(CaseStatus::Charged),
+            "trial_scheduled" => Ok(CaseStatus::TrialScheduled),
+            "verdict" => Ok(CaseStatus::Verdict),
+            "appeal" => Ok(CaseStatus::Appeal),
+            "closed" => Ok(CaseStatus::Closed),
+            _ => Err(Error::Arguments),
+        }
+    }
+}
+
+impl FromSql for CaseStatus {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        value
+            .as_str()?
+            .parse()
+            .map_err(|_| FromSqlError::InvalidType)
+    }
+}
+
+impl ToSql for CaseStatus {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        Ok(self.as_str().into())
+    }
+}
+
+/// Change of the status of a case.
+#[derive(Serialize, Debug, Clone, PartialEq, ToSchema)]
+pub struct Transition {
+    /// Status before the change, none when the case was added
+    pub previous: Option<CaseStatus>,
+    pub status: CaseStatus,
+    /// Login that changed the status
+    pub actor: String,
+    pub changed_at: DateTime<Utc>,
+}
+
+impl FromRow for Transition {
+    fn from_row(row: &rusqlite::Row) -> rusqlite::Result<Transition> {
+        Ok(Transition {
+            previous: row.get("previous")?,
+            status: row.get("status")?,
+            actor: row.get("actor")?,
+            changed_at: row.get("changed_at")?,
+        })
+    }
+}
+
+/// Records that the `actor` changed the status of the case with the `id`.
+pub fn record(
+    db: &Database,
+    actor: &str,
+    id: i64,
+    previous: Option<CaseStatus>,
+    status: CaseStatus,
+) -> Result<()> {
+    db.con.execute(
+        "insert into criminal_status (criminal, previous, status, actor, changed_at) \
+        values (?, ?, ?, ?, ?)",
+        rusqlite::params![id, previous, status, actor, Utc::now()],
+    )?;
+    Ok(())
+}
+
+/// Returns the status changes of the case with the `id`, the oldest first.
+pub fn of_case(db: &Database, id: i64) -> Result<Vec<Transition>> {
+    let mut stmt = db.con.prepare(
+        "select previous, status, actor, changed_at from criminal_status \
+        where criminal=? \
+        order by changed_at asc, rowid asc",
+    )?;
+    let rows = stmt.query([id])?;
+    DBIter::new(rows).collect()
+}
+
+/// Version 9: Criminal cases have a status, whose changes are recorded.
+///
+/// Existing cases with a verdict start with the verdict status,
+/// all others as reported.
+pub fn migrate(db: &Database) -> Result<()> {
+    const MIGRATION: &str = "\
+    alter table criminal add column status text not null default 'reported'; \
+    update criminal set status = 'verdict' where trim(verdict) != ''; \
+    alter table criminal_history add column status text not null default 'reported'; \
+    update criminal_history set status = 'verdict' where trim(verdict) != ''; \
+    \
+    drop trigger criminal_history_insert; \
+    drop trigger criminal_history_update; \
+    create trigger criminal_history_insert after insert on criminal begin \
+        insert into criminal_history (id, case_number, account, kind, accuser, \
+            police_consultant, lawyer_culprit, lawyer_accuser, facts, time_of_crime, \
+            location_of_crime, note, verdict, status, deleted_at, valid_from, valid_to) \
+        values (new.id, new.case_number, new.account, new.kind, new.accuser, \
+            new.police_consultant, new.lawyer_culprit, new.lawyer_accuser, new.facts, \
+            new.time_of_crime, new.location_of_crime, new.note, new.verdict, new.status, \
+            new.deleted_at, strftime('%Y-%m-%d %H:%M:%f+00:00', 'now'), null); \
+    end; \
+    create trigger criminal_history_update after update on criminal begin \
+        update criminal_history set valid_to=strftime('%Y-%m-%d %H:%M:%f+00:00', 'now') \
+        where id=old.id and valid_to is null; \
+        insert into criminal_history (id, case_number, account, kind, accuser, \
+            police_consultant, lawyer_culprit, lawyer_accuser, facts, time_of_crime, \
+            location_of_crime, note, verdict, status, deleted_at, valid_from, valid_to) \
+        values (new.id, new.case_number, new.account, new.kind, new.accuser, \
+            new.police_consultant, new.lawyer_culprit, new.lawyer_accuser, new.facts, \
+            new.time_of_crime, new.location_of_crime, new.note, new.verdict, new.status, \
+            new.deleted_at, strftime('%Y-%m-%d %H:%M:%f+00:00', 'now'), null); \
+    end; \
+    \
+    create table criminal_status ( \
+        criminal integer not null references criminal(id) on delete cascade, \
+        previous text, \
+        status text not null, \
+        actor text not null, \
+        changed_at text not null); \
+    create index criminal_status_criminal on criminal_status(criminal, changed_at); \
+    insert into criminal_status select \
+        id, null, status, 'system', strftime('%Y-%m-%d %H:%M:%f+00:00', 'now') \
+        from criminal; \
+    ";
+    db.con.execute_batch(MIGRATION)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::db::criminal::{self, Criminal, CriminalSearch};
+    use crate::db::page::Paging;
+    use crate::db::project::{create, migrate, Database, Error, CREATE_TABLES};
+    use crate::db::role::{self, Role, RoleKind};
+    use crate::db::sort::Sort;
+    use crate::db::status::CaseStatus;
+    use crate::db::user::{self, User};
+
+    #[test]
+    fn transitions() {
+        let db = Database::memory().unwrap();
+        create(&db).unwrap();
+        role::add(&db, "test", &Role::new("Demo", RoleKind::Other)).unwrap();
+        let user = User {
+            account: "foo.bar".into(),
+            forename: "Foo".into(),
+            surname: "Bar".into(),
+            role: "Demo".into(),
+        };
+        user::add(&db, "test", &user).unwrap();
+        let criminal = Criminal {
+            account: user.account.clone(),
+            kind: "Theft".into(),
+            ..Default::default()
+        };
+        let criminal = criminal::add(&db, "clerk", &criminal).unwrap();
+        let id = criminal.id;
+
+        assert_eq!(
+            criminal::transition(&db, "judge", id, CaseStatus::Verdict),
+            Err(Error::InvalidTransition(
+                CaseStatus::Reported,
+                CaseStatus::Verdict
+            ))
+        );
+        for status in [
+            CaseStatus::Investigating,
+            CaseStatus::Charged,
+            CaseStatus::TrialScheduled,
+        ] {
+            criminal::transition(&db, "police", id, status).unwrap();
+        }
+        let changed = criminal::transition(&db, "judge", id, CaseStatus::Verdict).unwrap();
+        assert_eq!(changed.status, CaseStatus::Verdict);
+        // updates keep the status
+        criminal::update(&db, "clerk", id, &criminal).unwrap();
+        assert_eq!(
+            criminal::fetch(&db, id).unwrap().status,
+            CaseStatus::Verdict
+        );
+        criminal::transition(&db, "judge", id, CaseStatus::Closed).unwrap();
+        assert_eq!(
+            criminal::transition(&db, "judge", id, CaseStatus::Appeal),
+            Err(Error::InvalidTransition(
+                CaseStatus::Closed,
+                CaseStatus::Appeal
+            ))
+        );
+
+        let transitions = criminal::transitions(&db, id).unwrap();
+        let steps = transitions
+            .iter()
+            .map(|t| (t.previous, t.status, t.actor.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            steps,
+            [
+                (None, CaseStatus::Reported, "clerk"),
+                (
+                    Some(CaseStatus::Reported),
+                    CaseStatus::Investigating,
+                    "police"
+                ),
+                (
+                    Some(CaseStatus::Investigating),
+                    CaseStatus::Charged,
+                    "police"
+                ),
+                (
+                    Some(CaseStatus::Charged),
+                    CaseStatus::TrialScheduled,
+                    "police"
+                ),
+                (
+                    Some(CaseStatus::TrialScheduled),
+                    CaseStatus::Verdict,
+                    "judge"
+                ),
+                (Some(CaseStatus::Verdict), CaseStatus::Closed, "judge"),
+            ]
+        );
+        assert_eq!(criminal::transitions(&db, id + 1), Err(Error::NothingFound));
+
+        let search = |status| {
+            criminal::search(
+                &db,
+                CriminalSearch {
+                    status: Some(status),
+                    ..CriminalSearch::new("", "%", "%")
+                },
+                &Sort::default(),
+                Paging::default(),
+            )
+            .unwrap()
+            .items
+            .len()
+        };
+        assert_eq!(search(CaseStatus::Closed), 1);
+        assert_eq!(search(CaseStatus::Reported), 0);
+        assert_eq!("closed".parse::<CaseStatus>(), Ok(CaseStatus::Closed));
+        assert_eq!("done".parse::<CaseStatus>(), Err(Error::Arguments));
+    }
+
+    #[test]
+    fn migration() {
+        let db = Database::memory().unwrap();
+        db.con.execute_batch(CREATE_TABLES).unwrap();
+        db.con
+            .execute_batch(
+                "insert into user values ('foo', 'Foo', 'Bar', 'Demo'); \
+                insert into criminal (account, kind, accuser, police_consultant, \
+                    lawyer_culprit, lawyer_accuser, facts, time_of_crime, \
+                    location_of_crime, note, verdict) values \
+                    ('foo', 'Theft', '', '', '', '', '', '', '', '', 'Guilty'), \
+                    ('foo', 'Fraud', '', '', '', '', '', '', '', '', ' ');",
+            )
+            .unwrap();
+        migrate(&db).unwrap();
+
+        let statuses = criminal::of_account(&db, "foo")
+            .unwrap()
+            .iter()
+            .map(|c| (c.kind.clone(), c.status))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            statuses,
+            [
+                ("Fraud".into(), CaseStatus::Reported),
+                ("Theft".into(), CaseStatus::Verdict)
+            ]
+        );
+        let transitions = criminal::transitions(&db, 1).unwrap();
+        assert_eq!(transitions.len(), 1);
+        assert_eq!(transitions[0].previous, None);
+        assert_eq!(transitions[0].status, CaseStatus::Verdict);
+        assert_eq!(transitions[0].actor, "system");
+    }
+}
@@ -0,0 +1,141 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use crate::db::criminal::Criminal;
+use crate::db::project::{Database, FromRow, Result};
+use crate::db::user::User;
+use crate::db::workless::Workless;
+
+/// Deleted record in the trash bin.
+#[derive(Serialize, Debug, Clone, ToSchema)]
+#[aliases(TrashedUser = Trashed<User>, TrashedWorkless = Trashed<Workless>, TrashedCriminal = Trashed<Criminal>)]
+pub struct Trashed<T> {
+    pub item: T,
+    /// Records deleted together, like a user and its entries, share this timestamp.
+    pub deleted_at: DateTime<Utc>,
+}
+
+impl<T: FromRow> FromRow for Trashed<T> {
+    fn from_row(row: &rusqlite::Row) -> rusqlite::Result<Self> {
+        Ok(Trashed {
+            item: T::from_row(row)?,
+            deleted_at: row.get("deleted_at")?,
+        })
+    }
+}
+
+/// Version 5: Deleted records are kept in the trash bin.
+///
+/// They are marked by their `deleted_at` timestamp until they are
+/// restored or purged.
+pub fn migrate(db: &Database) -> Result<()> {
+    db.con.execute_batch(
+        "alter table user add column deleted_at text; \
+        alter table workless add column deleted_at text; \
+        alter table criminal add column deleted_at text;",
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use crate::db::audit::{self, AuditSearch, Entity, Operation};
+    use crate::db::criminal::{self, Criminal};
+    use crate::db::login::{self, NewLogin};
+    use crate::db::project::{create, Database, Error};
+    use crate::db::role::{self, Role, RoleKind};
+    use crate::db::user::{self, OnDelete, User};
+    use crate::db::workless::{self, Workless};
+
+    #[test]
+    fn delete_restore_purge() {
+        let db = Database::memory().unwrap();
+        create(&db).unwrap();
+        role::add(&db, "test", &Role::new("Demo", RoleKind::Other)).unwrap();
+
+        let user = User {
+            account: "foo.bar".into(),
+            forename: "Foo".into(),
+            surname: "Bar".into(),
+            role: "Demo".into(),
+        };
+        user::add(&db, "test", &user).unwrap();
+        let date = NaiveDate::from_ymd_opt(2023, 6, 1).unwrap();
+        let workless = Workless {
+            account: user.account.clone(),
+            old_company: "bars".into(),
+            date_of_dismiss: date,
+            ..Default::default()
+        };
+        workless::add(&db, "test", &workless).unwrap();
+        let criminal = Criminal {
+            account: user.account.clone(),
+            kind: "Theft".into(),
+            ..Default::default()
+        };
+        let criminal = criminal::add(&db, "test", &criminal).unwrap();
+        let new_login = NewLogin {
+            user: user.account.clone(),
+            password: "secret".into(),
+            ..Default::default()
+        };
+        login::add(&db, "test", new_login).unwrap();
+
+        // deleted on its own before the user
+        workless::delete(&db, "test", &user.account, "bars", date).unwrap();
+        user::delete(&db, "test", &user.account, OnDelete::Cascade).unwrap();
+        assert_eq!(user::fetch(&db, &user.account), Err(Error::NothingFound));
+        assert_eq!(
+            criminal::add(&db, "test", &criminal),
+            Err(Error::UnknownAccount)
+        );
+        assert_eq!(user::trash(&db, 10).unwrap()[0].item, user);
+        assert_eq!(workless::trash(&db, 10).unwrap().len(), 1);
+        assert_eq!(criminal::trash(&db, 10).unwrap()[0].item, criminal);
+        // the login is kept but can not be used
+        assert_eq!(login::fetch(&db, &user.account), Err(Error::NothingFound));
+        assert!(login::all_logins(&db).unwrap().is_empty());
+
+        // only the entries deleted with the user are restored
+        user::restore(&db, "test", &user.account).unwrap();
+        assert_eq!(user::fetch(&db, &user.account).unwrap(), user);
+        assert_eq!(criminal::fetch(&db, criminal.id).unwrap(), criminal);
+        assert!(workless::fetch(&db, &user.account, "bars", date).is_err());
+        assert!(user::trash(&db, 10).unwrap().is_empty());
+        assert!(login::fetch(&db, &user.account)
+            .unwrap()
+            .check_password("secret"));
+        assert_eq!(
+            user::restore(&db, "test", &user.account),
+            Err(Error::NothingFound)
+        );
+
+        user::delete(&db, "test", &user.account, OnDelete::Cascade).unwrap();
+        assert_eq!(
+            workless::restore(&db, "test", &user.account, "bars", date),
+            Err(Error::UnknownAccount)
+        );
+        user::purge(&db, "test", &user.account).unwrap();
+        assert!(user::trash(&db, 10).unwrap().is_empty());
+        assert!(workless::trash(&db, 10).unwrap().is_empty());
+        assert!(criminal::trash(&db, 10).unwrap().is_empty());
+        let count: usize = db
+            .con
+            .query_row("select count(*) from login", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(count, 0);
+        let search = AuditSearch {
+            entities: &[Entity::Login],
+            ..Default::default()
+        };
+        let operations: Vec<_> = audit::search(&db, search, 10)
+            .unwrap()
+            .into_iter()
+            .map(|audit| audit.operation)
+            .collect();
+        assert_eq!(operations, [Operation::Delete, Operation::Insert]);
+    }
+}
@@ -1,11 +1,24 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use rusqlite::ToSql;
 use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
 
-use crate::db::project::{DBIter, Database, Error, FromRow, Result};
+use crate::db::audit::{self, Audited, Entity};
+use crate::db::criminal;
+use crate::db::fulltext::{self, Hit, HIGHLIGHT};
+use crate::db::history::Version;
+use crate::db::login;
+use crate::db::page::{paginate, Page, Paging};
+use crate::db::project::{Database, Error, FieldError, FromRow, Result};
+use crate::db::repository::{self, Repository};
+use crate::db::role;
+use crate::db::sort::{Sort, SortColumns};
+use crate::db::trash::Trashed;
+use crate::db::validate::{self, Field, Validate, Value};
+use crate::db::workless;
 
 /// Data object for a user.
-#[derive(Serialize, Deserialize, Debug, Clone, ToSchema, Default)]
-#[cfg_attr(test, derive(PartialEq))]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, ToSchema, Default)]
 pub struct User {
     pub account: String,
     pub forename: String,
@@ -13,13 +26,30 @@ pub struct User {
     pub role: String,
 }
 
-impl User {
-    pub fn is_valid(&self) -> bool {
-        !self.account.trim().is_empty()
-            && self.account.starts_with(char::is_alphabetic)
-            && !self.forename.trim().is_empty()
-            && !self.surname.trim().is_empty()
-            && !self.role.trim().is_empty()
+impl Validate for User {
+    const FIELDS: &'static [Field] = &[
+        Field::new("account", validate::ACCOUNT),
+        Field::new("forename", validate::NAME),
+        Field::new("surname", validate::NAME),
+        Field::new("role", validate::NAME),
+    ];
+    fn fields(&self) -> Vec<Value<'_>> {
+        vec![
+            Value::Text(&self.account),
+            Value::Text(&self.forename),
+            Value::Text(&self.surname),
+            Value::Text(&self.role),
+        ]
+    }
+    fn invalid(details: Vec<FieldError>) -> Error {
+        Error::InvalidUser(details)
+    }
+}
+
+impl Audited for User {
+    const ENTITY: Entity = Entity::User;
+    fn key(&self) -> String {
+        self.account.clone()
     }
 }
 
@@ -34,52 +64,52 @@ impl FromRow for User {
     }
 }
 
+impl Repository for User {
+    const TABLE: &'static str = "user";
+    const COLUMNS: &'static [&'static str] = &["account", "forename", "surname", "role"];
+    const KEY: &'static [&'static str] = &["account"];
+    const ORDER: &'static str = "account asc";
+
+    type Key = String;
+
+    fn id(&self) -> String {
+        self.account.clone()
+    }
+    fn bind(key: &String) -> Vec<&dyn ToSql> {
+        vec![key]
+    }
+    fn values(&self) -> Vec<&dyn ToSql> {
+        vec![&self.account, &self.forename, &self.surname, &self.role]
+    }
+    fn trimmed(&self) -> User {
+        User {
+            account: self.account.trim().into(),
+            forename: self.forename.trim().into(),
+            surname: self.surname.trim().into(),
+            role: self.role.trim().into(),
+        }
+    }
+}
+
 /// Returns the user with the given `id`.
 pub fn fetch(db: &Database, id: &str) -> Result<User> {
-    Ok(db.con.query_row(
-        "select \
-        account, \
-        forename, \
-        surname, \
-        role \
-        from user \
-        where account=?",
-        [id],
-        User::from_row,
-    )?)
+    repository::fetch(db, &id.into())
 }
 
-use std::collections::HashSet;
-
 /// Returns all roles from the user table without duplicates
 pub fn all_roles(db: &Database, name: &str) -> Result<Vec<String>> {
-    let mut stmt = db.con.prepare(
+    repository::distinct(
+        db,
         "select \
         role \
         from user \
-        where (account like '%'||?1||'%' \
+        where deleted_at is null \
+        and (account like '%'||?1||'%' \
             or forename like '%'||?1||'%' \
             or surname like '%'||?1||'%') \
         order by role asc",
-    )?;
-
-    let mut rows = stmt.query(rusqlite::params![name.trim()])?;
-    let mut roles = Vec::new();
-    let mut seen_roles = HashSet::new();
-
-    while let Some(row) = rows.next()? {
-        let role: String = row.get(0).unwrap();
-
-        // Check if the role has already been seen
-        if seen_roles.contains(&role) {
-            continue; // Skip the duplicate role
-        }
-
-        roles.push(role.clone());
-        seen_roles.insert(role);
-    }
-
-    Ok(roles)
+        [name.trim()],
+    )
 }
 
 /// Parameters for the advanced search
@@ -97,120 +127,278 @@ impl<'a> UserSearch<'a> {
     }
 }
 
+/// Columns the user search can be sorted by.
+pub const SORT_COLUMNS: SortColumns = &[
+    ("account", "user.account"),
+    ("forename", "user.forename"),
+    ("surname", "user.surname"),
+    ("role", "user.role"),
+];
+
 /// Performes a simple user search with the given `text`.
-pub fn search(db: &Database, params: UserSearch, limit: usize) -> Result<Vec<User>> {
-    let mut stmt = db.con.prepare(
-        "select \
-        account, \
-        forename, \
-        surname, \
-        role \
-        \
-        from user \
-        where (account like '%'||?1||'%' \
-            or forename like '%'||?1||'%' \
-            or surname like '%'||?1||'%') \
-        and role like ?2 \
-        order by case \
-            when account like ?1 || '%' then 0 \
-            else 1 \
-        end asc, account asc \
-        limit ?3",
-    )?;
-    let rows = stmt.query(rusqlite::params![
-        params.name.trim(),
-        params.role.trim(),
-        limit
-    ])?;
-    DBIter::new(rows).collect()
-}
-
-/// Adds a new user.
-pub fn add(db: &Database, user: &User) -> Result<()> {
-    if !user.is_valid() {
-        return Err(Error::InvalidUser);
-    }
-    db.con.execute(
-        "insert into user values (?, ?, ?, ?)",
-        rusqlite::params![
-            user.account.trim(),
-            user.forename.trim(),
-            user.surname.trim(),
-            user.role.trim(),
-        ],
-    )?;
-    Ok(())
+pub fn search(
+    db: &Database,
+    params: UserSearch,
+    sort: &Sort,
+    paging: Paging,
+) -> Result<Page<User>> {
+    let order =
+        sort.order_by("case when account like ?1 || '%' then 0 else 1 end asc, account asc");
+    paginate(
+        db,
+        &format!(
+            "select \
+            account, \
+            forename, \
+            surname, \
+            role \
+            \
+            from user \
+            where deleted_at is null \
+            and (account like '%'||?1||'%' \
+                or forename like '%'||?1||'%' \
+                or surname like '%'||?1||'%') \
+            and role like ?2 \
+            {order}"
+        ),
+        rusqlite::params![params.name.trim(), params.role.trim()],
+        paging,
+    )
+}
+
+/// Searches the names and roles of all users for the words of `text`.
+///
+/// The hits are ordered by relevance.
+pub fn search_text(
+    db: &Database,
+    text: &str,
+    sort: &Sort,
+    paging: Paging,
+) -> Result<Page<Hit<User>>> {
+    let Some(query) = fulltext::query(text) else {
+        return Ok(Page::empty());
+    };
+    let order = sort.order_by("rank");
+    paginate(
+        db,
+        &format!(
+            "select \
+            user.account, \
+            user.forename, \
+            user.surname, \
+            user.role, \
+            user_fts.rank as rank, \
+            snippet(user_fts, -1, ?2, ?3, '…', 8) as snippet \
+            \
+            from user_fts \
+            join user on user.account = user_fts.account \
+            where user_fts match ?1 and user.deleted_at is null \
+            {order}"
+        ),
+        rusqlite::params![query, HIGHLIGHT.0, HIGHLIGHT.1],
+        paging,
+    )
+}
+
+/// Copy of the user with the canonical name of its role.
+///
+/// Fails with [`Error::UnknownRole`] if the role does not exist.
+fn with_role(db: &Database, user: &User) -> Result<User> {
+    user.validate()?;
+    Ok(User {
+        role: role::resolve(db, &user.role)?,
+        ..user.clone()
+    })
+}
+
+/// Adds a new user, its role must already exist.
+pub fn add(db: &Database, actor: &str, user: &User) -> Result<()> {
+    db.transaction(|db| {
+        repository::add(db, actor, &with_role(db, user)?)?;
+        Ok(())
+    })
 }
 
 /// Updates the user and all references if its account changes.
-pub fn update(db: &Database, previous_account: &str, user: &User) -> Result<()> {
+///
+/// The workless, criminal and login entries are updated by the database.
+pub fn update(db: &Database, actor: &str, previous_account: &str, user: &User) -> Result<()> {
     let previous_account = previous_account.trim();
-    if previous_account.is_empty() || !user.is_valid() {
-        return Err(Error::InvalidUser);
+    if previous_account.is_empty() {
+        return Err(Error::InvalidUser(vec![FieldError::new(
+            "account",
+            "must not be empty",
+        )]));
     }
-    let transaction = db.transaction()?;
-    // update user
-    transaction.execute(
-        "update user set account=?, forename=?, surname=?, role=? where account=?",
-        rusqlite::params![
-            user.account.trim(),
-            user.forename.trim(),
-            user.surname.trim(),
-            user.role.trim(),
-            previous_account,
-        ],
-    )?;
+    db.transaction(|db| {
+        repository::update(db, actor, &previous_account.into(), &with_role(db, user)?)
+    })
+}
 
-    // update workless
-    transaction.execute(
-        "update workless set account=? where account=?",
-        [user.account.trim(), previous_account],
-    )?;
+/// How deleting a user treats its workless and criminal entries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OnDelete {
+    /// Deletes them together with the user.
+    #[default]
+    Cascade,
+    /// Refuses to delete a user that still has entries.
+    Restrict,
+}
 
-    // update criminal
-    transaction.execute(
-        "update criminal set account=? where account=?",
-        [user.account.trim(), previous_account],
-    )?;
+/// Moves the user into the trash bin.
+/// Depending on `on_delete`, this trashes all its workless and criminal entries.
+/// Its login is kept, but can not be used until the user is restored.
+pub fn delete(db: &Database, actor: &str, account: &str, on_delete: OnDelete) -> Result<()> {
+    let account = account.trim();
+    if account.is_empty() {
+        return Err(Error::InvalidUser(vec![FieldError::new(
+            "account",
+            "must not be empty",
+        )]));
+    }
+    db.transaction(|db| {
+        if on_delete == OnDelete::Restrict {
+            let referenced: bool = db.con.query_row(
+                "select exists (select 1 from workless where account=?1 and deleted_at is null) \
+                or exists (select 1 from criminal where account=?1 and deleted_at is null)",
+                [account],
+                |row| row.get(0),
+            )?;
+            if referenced {
+                return Err(Error::UserReferenced);
+            }
+        }
+        let before = fetch(db, account)?;
+        let deleted_at = Utc::now();
+        for workless in workless::of_account(db, account)? {
+            audit::delete(db, actor, &workless)?;
+        }
+        for criminal in criminal::of_account(db, account)? {
+            audit::delete(db, actor, &criminal)?;
+        }
+        // the entries share the timestamp to be restored together with the user
+        db.con.execute(
+            "update workless set deleted_at=?2 where account=?1 and deleted_at is null",
+            rusqlite::params![account, deleted_at],
+        )?;
+        db.con.execute(
+            "update criminal set deleted_at=?2 where account=?1 and deleted_at is null",
+            rusqlite::params![account, deleted_at],
+        )?;
+        db.con.execute(
+            "update user set deleted_at=? where account=?",
+            rusqlite::params![deleted_at, account],
+        )?;
+        audit::delete(db, actor, &before)?;
+        Ok(())
+    })
+}
 
-    // update login
-    transaction.execute(
-        "update login set user=? where user=?",
-        [user.account.trim(), previous_account],
-    )?;
+/// Fails with [`Error::UnknownAccount`] if there is no user with the `account`
+/// or it is in the trash bin.
+pub fn ensure_exists(db: &Database, account: &str) -> Result<()> {
+    match fetch(db, account.trim()) {
+        Ok(_) => Ok(()),
+        Err(Error::NothingFound) => Err(Error::UnknownAccount),
+        Err(e) => Err(e),
+    }
+}
 
-    transaction.commit()?;
-    Ok(())
+/// Returns the users in the trash bin, the most recently deleted first.
+pub fn trash(db: &Database, limit: usize) -> Result<Vec<Trashed<User>>> {
+    repository::trash(db, limit)
 }
 
-/// Deletes the user.
-/// This includes all its workless.
-pub fn delete(db: &Database, account: &str) -> Result<()> {
+/// Keys of the workless entries and ids of the criminal cases of `account`,
+/// optionally only those deleted at `deleted_at`.
+#[allow(clippy::type_complexity)]
+fn entries(
+    db: &Database,
+    account: &str,
+    deleted_at: Option<DateTime<Utc>>,
+) -> Result<(Vec<(String, NaiveDate)>, Vec<i64>)> {
+    let mut stmt = db.con.prepare(
+        "select old_company, date_of_dismiss from workless \
+            where account=?1 and (?2 is null or deleted_at=?2)",
+    )?;
+    let workless = stmt
+        .query_map(rusqlite::params![account, deleted_at], |row| {
+            Ok((row.get(0)?, row.get(1)?))
+        })?
+        .collect::<rusqlite::Result<_>>()?;
+    let mut stmt = db.con.prepare(
+        "select id from criminal \
+            where account=?1 and (?2 is null or deleted_at=?2)",
+    )?;
+    let criminal = stmt
+        .query_map(rusqlite::params![account, deleted_at], |row| row.get(0))?
+        .collect::<rusqlite::Result<_>>()?;
+    Ok((workless, criminal))
+}
+
+/// Restores the user from the trash bin,
+/// together with the workless and criminal entries that were deleted with it.
+///
+/// Its login can be used again.
+pub fn restore(db: &Database, actor: &str, account: &str) -> Result<()> {
     let account = account.trim();
-    if account.is_empty() {
-        return Err(Error::InvalidUser);
-    }
-    let transaction = db.transaction()?;
-    // remove user
-    transaction.execute("delete from user where account=?", [account])?;
+    db.transaction(|db| {
+        let trashed = repository::restore::<User>(db, actor, &account.into())?;
+        let (workless, criminal) = entries(db, account, Some(trashed.deleted_at))?;
+        for (old_company, date) in workless {
+            workless::restore(db, actor, account, &old_company, date)?;
+        }
+        for id in criminal {
+            criminal::restore(db, actor, id)?;
+        }
+        Ok(())
+    })
+}
 
-    //remove from workless
-    transaction.execute("delete from workless where account=?", [account])?;
-    //remove from criminal
-    transaction.execute("delete from criminal where account=?", [account])?;
-    transaction.commit()?;
+/// Permanently deletes the user from the trash bin,
+/// including its login and all its workless and criminal entries.
+pub fn purge(db: &Database, actor: &str, account: &str) -> Result<()> {
+    let key = account.trim().to_string();
+    db.transaction(|db| {
+        repository::fetch_trashed::<User>(db, &key)?;
+        // all entries are in the trash bin together with the user
+        let (workless, criminal) = entries(db, &key, None)?;
+        for (old_company, date) in workless {
+            workless::purge(db, actor, &key, &old_company, date)?;
+        }
+        for id in criminal {
+            criminal::purge(db, actor, id)?;
+        }
+        login::purge(db, actor, &key)?;
+        repository::purge::<User>(db, actor, &key)
+    })
+}
 
-    Ok(())
+/// Returns all revisions of the user, the oldest first.
+pub fn history(db: &Database, account: &str) -> Result<Vec<Version<User>>> {
+    repository::history(db, &account.trim().into())
+}
+
+/// Returns the users as they were at the given `time`.
+pub fn as_of(db: &Database, time: DateTime<Utc>, limit: usize) -> Result<Vec<User>> {
+    repository::as_of(db, time, limit)
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::db::project::{create, Database};
-    use crate::db::user::{self, User, UserSearch};
+    use crate::db::page::Paging;
+    use crate::db::project::{create, Database, Error};
+    use crate::db::role::{self, Role, RoleKind};
+    use crate::db::sort::Sort;
+    use crate::db::user::{self, OnDelete, User, UserSearch};
+    use crate::db::workless::{self, Workless, WorklessSearch};
+
     #[test]
     fn add_update_remove_users_all_roles() {
         let db = Database::memory().unwrap();
         create(&db).unwrap();
+        role::add(&db, "test", &Role::new("Demo", RoleKind::Other)).unwrap();
+        role::add(&db, "test", &Role::new("Teacher", RoleKind::Teacher)).unwrap();
 
         let user = User {
             account: "foo.bar".into(),
@@ -218,9 +406,16 @@ mod tests {
             surname: "Bar".into(),
             role: "Demo".into(),
         };
-        user::add(&db, &user).unwrap();
+        user::add(&db, "test", &user).unwrap();
 
-        let result = user::search(&db, UserSearch::new("%", "%"), 200).unwrap();
+        let result = user::search(
+            &db,
+            UserSearch::new("%", "%"),
+            &Sort::default(),
+            Paging::default(),
+        )
+        .unwrap()
+        .items;
         assert_eq!(result.len(), 1);
         assert_eq!(result[0], user);
 
@@ -230,6 +425,7 @@ mod tests {
 
         user::update(
             &db,
+            "test",
             &user.account,
             &User {
                 role: "Teacher".into(),
@@ -237,12 +433,44 @@ mod tests {
             },
         )
         .unwrap();
-        let result = user::search(&db, UserSearch::new("%foo%", "%"), 200).unwrap();
+        let result = user::search(
+            &db,
+            UserSearch::new("%foo%", "%"),
+            &Sort::default(),
+            Paging::default(),
+        )
+        .unwrap()
+        .items;
         assert_eq!(result.len(), 1);
         assert_eq!(result[0].role, "Teacher");
 
-        user::delete(&db, &user.account).unwrap();
-        let result = user::search(&db, UserSearch::new("no one", "%"), 200).unwrap();
+        let workless = Workless {
+            account: user.account.clone(),
+            old_company: "bars".into(),
+            ..Default::default()
+        };
+        workless::add(&db, "test", &workless).unwrap();
+        let result = user::delete(&db, "test", &user.account, OnDelete::Restrict);
+        assert!(matches!(result, Err(Error::UserReferenced)));
+
+        user::delete(&db, "test", &user.account, OnDelete::Cascade).unwrap();
+        let result = workless::search(
+            &db,
+            WorklessSearch::new("", "%", "%"),
+            &Sort::default(),
+            Paging::default(),
+        )
+        .unwrap()
+        .items;
+        assert_eq!(result.len(), 0);
+        let result = user::search(
+            &db,
+            UserSearch::new("no one", "%"),
+            &Sort::default(),
+            Paging::default(),
+        )
+        .unwrap()
+        .items;
         assert_eq!(result.len(), 0);
     }
 }
@@ -0,0 +1,232 @@
+use chrono::NaiveDate;
+use utoipa::openapi::{schema::Schema, Components, RefOr};
+use utoipa::ToSchema;
+
+use crate::db::project::{Error, FieldError, Result};
+
+/// Rule for the value of a field.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rule {
+    /// Must not be empty or only whitespace
+    Required,
+    /// Must start with a letter, if not empty
+    StartsWithLetter,
+    /// Must start with a letter or a digit, if not empty
+    StartsWithAlphanumeric,
+    /// Must have at most this many characters
+    MaxLength(usize),
+    /// Must not contain any of these characters
+    Forbidden(&'static str),
+    /// Dates must be within [`MIN_DATE`] and [`MAX_DATE`]
+    DateRange,
+}
+
+/// Dates outside of this range can not be exported, e.g. to Excel.
+pub const MIN_DATE: NaiveDate = match NaiveDate::from_ymd_opt(1900, 1, 1) {
+    Some(date) => date,
+    None => panic!(),
+};
+pub const MAX_DATE: NaiveDate = match NaiveDate::from_ymd_opt(9999, 12, 31) {
+    Some(date) => date,
+    None => panic!(),
+};
+
+/// Account of a user, which is also the name of its login.
+pub const ACCOUNT: &[Rule] = &[Rule::Required, Rule::StartsWithLetter, Rule::MaxLength(64)];
+/// Names, roles, companies and other short texts that are required.
+pub const NAME: &[Rule] = &[Rule::Required, Rule::MaxLength(128)];
+/// Optional short texts.
+pub const SHORT_TEXT: &[Rule] = &[Rule::MaxLength(128)];
+/// Optional long texts, like the facts of a crime.
+pub const LONG_TEXT: &[Rule] = &[Rule::MaxLength(10_000)];
+pub const DATE: &[Rule] = &[Rule::DateRange];
+
+/// Value of a field that is validated.
+#[derive(Debug, Clone, Copy)]
+pub enum Value<'a> {
+    Text(&'a str),
+    Date(NaiveDate),
+}
+
+impl Rule {
+    /// Returns why the `value` breaks this rule.
+    fn check(self, value: Value) -> Option<String> {
+        match (self, value) {
+            (Rule::Required, Value::Text(text)) if text.trim().is_empty() => {
+                Some("must not be empty".into())
+            }
+            (Rule::StartsWithLetter, Value::Text(text))
+                if !text.is_empty() && !text.starts_with(char::is_alphabetic) =>
+            {
+                Some("must start with a letter".into())
+            }
+            (Rule::StartsWithAlphanumeric, Value::Text(text))
+                if !text.is_empty() && !text.starts_with(char::is_alphanumeric) =>
+            {
+                Some("must start with a letter or digit".into())
+            }
+            (Rule::MaxLength(max), Value::Text(text)) if text.trim().chars().count() > max => {
+                Some(format!("must be at most {max} characters long"))
+            }
+            (Rule::Forbidden(chars), Value::Text(text)) => text
+                .chars()
+                .find(|c| chars.contains(*c))
+                .map(|c| format!("must not contain '{c}'")),
+            (Rule::DateRange, Value::Date(date)) if !(MIN_DATE..=MAX_DATE).contains(&date) => {
+                Some(format!("must be between {MIN_DATE} and {MAX_DATE}"))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Field with its rules.
+#[derive(Debug, Clone, Copy)]
+pub struct Field {
+    pub name: &'static str,
+    pub rules: &'static [Rule],
+}
+
+impl Field {
+    pub const fn new(name: &'static str, rules: &'static [Rule]) -> Self {
+        Self { name, rules }
+    }
+}
+
+/// Record with rules for its fields.
+///
+/// The rules are declared once and used by the database functions,
+/// the imports and the OpenAPI schema.
+pub trait Validate {
+    /// Rules of the fields
+    const FIELDS: &'static [Field];
+
+    /// Values of the [`Validate::FIELDS`], in the same order.
+    fn fields(&self) -> Vec<Value<'_>>;
+    /// Validation error of the entity.
+    fn invalid(details: Vec<FieldError>) -> Error;
+
+    /// Fails with every rule that is broken, if any.
+    fn validate(&self) -> Result<()> {
+        let details = Self::FIELDS
+            .iter()
+            .zip(self.fields())
+            .flat_map(|(field, value)| {
+                field
+                    .rules
+                    .iter()
+                    .filter_map(move |rule| rule.check(value))
+                    .map(|message| FieldError::new(field.name, message))
+            })
+            .collect::<Vec<_>>();
+        if details.is_empty() {
+            Ok(())
+        } else {
+            Err(Self::invalid(details))
+        }
+    }
+}
+
+/// Adds the rules of `T` to its schema, as far as OpenAPI can express them.
+pub fn document<'s, T: Validate + ToSchema<'s>>(components: &mut Components) {
+    let (name, _) = T::schema();
+    let Some(RefOr::T(Schema::Object(schema))) = components.schemas.get_mut(name) else {
+        return;
+    };
+    for field in T::FIELDS {
+        let Some(RefOr::T(Schema::Object(property))) = schema.properties.get_mut(field.name) else {
+            continue;
+        };
+        for rule in field.rules {
+            match rule {
+                Rule::Required => property.min_length = Some(1),
+                Rule::MaxLength(max) => property.max_length = Some(*max),
+                Rule::Forbidden(chars) => {
+                    property.pattern = Some(format!("^[^{}]*$", regex_escape(chars)))
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+fn regex_escape(chars: &str) -> String {
+    chars
+        .chars()
+        .flat_map(|c| match c {
+            '\\' | ']' | '^' | '-' => vec!['\\', c],
+            _ => vec![c],
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+    use utoipa::openapi::{schema::Schema, ComponentsBuilder, RefOr};
+    use utoipa::ToSchema;
+
+    use crate::db::login::NewLogin;
+    use crate::db::project::{Error, FieldError};
+    use crate::db::user::User;
+    use crate::db::validate::{self, Validate};
+    use crate::db::workless::Workless;
+
+    #[test]
+    fn validate() {
+        let user = User {
+            account: "1foo".into(),
+            forename: " ".into(),
+            surname: "B".repeat(200),
+            role: "Demo".into(),
+        };
+        assert_eq!(
+            user.validate(),
+            Err(Error::InvalidUser(vec![
+                FieldError::new("account", "must start with a letter"),
+                FieldError::new("forename", "must not be empty"),
+                FieldError::new("surname", "must be at most 128 characters long"),
+            ]))
+        );
+
+        let workless = Workless {
+            account: "foo.bar".into(),
+            old_company: "bars".into(),
+            date_of_dismiss: NaiveDate::from_ymd_opt(1800, 1, 1).unwrap(),
+            ..Default::default()
+        };
+        assert_eq!(
+            workless.validate(),
+            Err(Error::InvalidWorkless(vec![FieldError::new(
+                "date_of_dismiss",
+                "must be between 1900-01-01 and 9999-12-31"
+            )]))
+        );
+
+        let login = NewLogin {
+            user: "foo:bar".into(),
+            password: "baz".into(),
+            ..Default::default()
+        };
+        assert_eq!(
+            login.validate(),
+            Err(Error::InvalidLogin(vec![FieldError::new(
+                "user",
+                "must not contain ':'"
+            )]))
+        );
+
+        let mut components = ComponentsBuilder::new().schema_from::<NewLogin>().build();
+        validate::document::<NewLogin>(&mut components);
+        let Some(RefOr::T(Schema::Object(schema))) = components.schemas.get(NewLogin::schema().0)
+        else {
+            panic!("missing schema");
+        };
+        let Some(RefOr::T(Schema::Object(user))) = schema.properties.get("user") else {
+            panic!("missing property");
+        };
+        assert_eq!(user.min_length, Some(1));
+        assert_eq!(user.max_length, Some(64));
+        assert_eq!(user.pattern.as_deref(), Some("^[^:]*$"));
+    }
+}
@@ -1,14 +1,21 @@
-use std::collections::HashSet;
-
-use crate::db::project::{DBIter, Database, Error, FromRow, Result};
-
-use chrono::NaiveDate;
+use crate::db::audit::{Audited, Entity};
+use crate::db::history::Version;
+use crate::db::page::{paginate, Page, Paging};
+use crate::db::project::{unknown_account, DBIter, Database, Error, FieldError, FromRow, Result};
+use crate::db::repository::{self, Repository};
+use crate::db::sort::{Sort, SortColumns};
+use crate::db::trash::Trashed;
+use crate::db::user;
+use crate::db::validate::{self, Field, Rule, Validate, Value};
+
+use chrono::{DateTime, NaiveDate, Utc};
+use rusqlite::ToSql;
 use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
 
 /// Data object for an workless.
-#[derive(Serialize, Deserialize, Debug, Clone, ToSchema)]
-#[cfg_attr(test, derive(PartialEq, Default))]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, ToSchema)]
+#[cfg_attr(test, derive(Default))]
 pub struct Workless {
     pub account: String,
     pub old_company: String,
@@ -18,12 +25,41 @@ pub struct Workless {
     pub total_time: String,
 }
 
-impl Workless {
-    pub fn is_valid(&self) -> bool {
-        !self.account.trim().is_empty()
-            && self.account.starts_with(char::is_alphabetic)
-            && !self.old_company.trim().is_empty()
-            && self.old_company.starts_with(char::is_alphanumeric)
+impl Validate for Workless {
+    const FIELDS: &'static [Field] = &[
+        Field::new("account", validate::ACCOUNT),
+        Field::new("old_company", COMPANY),
+        Field::new("date_of_dismiss", validate::DATE),
+        Field::new("new_company", validate::SHORT_TEXT),
+        Field::new("total_time", validate::SHORT_TEXT),
+    ];
+    fn fields(&self) -> Vec<Value<'_>> {
+        vec![
+            Value::Text(&self.account),
+            Value::Text(&self.old_company),
+            Value::Date(self.date_of_dismiss),
+            Value::Text(&self.new_company),
+            Value::Text(&self.total_time),
+        ]
+    }
+    fn invalid(details: Vec<FieldError>) -> Error {
+        Error::InvalidWorkless(details)
+    }
+}
+
+const COMPANY: &[Rule] = &[
+    Rule::Required,
+    Rule::StartsWithAlphanumeric,
+    Rule::MaxLength(128),
+];
+
+impl Audited for Workless {
+    const ENTITY: Entity = Entity::Workless;
+    fn key(&self) -> String {
+        format!(
+            "{}/{}/{}",
+            self.account, self.old_company, self.date_of_dismiss
+        )
     }
 }
 
@@ -40,9 +76,68 @@ impl FromRow for Workless {
     }
 }
 
+impl Repository for Workless {
+    const TABLE: &'static str = "workless";
+    const COLUMNS: &'static [&'static str] = &[
+        "account",
+        "old_company",
+        "date_of_dismiss",
+        "currently",
+        "new_company",
+        "total_time",
+    ];
+    const KEY: &'static [&'static str] = &["account", "old_company", "date_of_dismiss"];
+    const ORDER: &'static str = "account asc, date_of_dismiss asc";
+
+    /// Account, company (old) and date (of dismiss)
+    type Key = (String, String, NaiveDate);
+
+    fn id(&self) -> Self::Key {
+        (
+            self.account.clone(),
+            self.old_company.clone(),
+            self.date_of_dismiss,
+        )
+    }
+    fn bind(key: &Self::Key) -> Vec<&dyn ToSql> {
+        vec![&key.0, &key.1, &key.2]
+    }
+    fn values(&self) -> Vec<&dyn ToSql> {
+        vec![
+            &self.account,
+            &self.old_company,
+            &self.date_of_dismiss,
+            &self.currently,
+            &self.new_company,
+            &self.total_time,
+        ]
+    }
+    fn trimmed(&self) -> Workless {
+        Workless {
+            account: self.account.trim().into(),
+            old_company: self.old_company.trim().into(),
+            date_of_dismiss: self.date_of_dismiss,
+            currently: self.currently,
+            new_company: self.new_company.trim().into(),
+            total_time: self.total_time.trim().into(),
+        }
+    }
+    fn check(&self, db: &Database) -> Result<()> {
+        user::ensure_exists(db, &self.account)
+    }
+    fn map_err(e: rusqlite::Error) -> Error {
+        unknown_account(e)
+    }
+}
+
 /// Returns the workless with the given `account`, company (old) and `date` (of dismiss).
 pub fn fetch(db: &Database, account: &str, old_company: &str, date: NaiveDate) -> Result<Workless> {
-    Ok(db.con.query_row(
+    repository::fetch(db, &(account.into(), old_company.into(), date))
+}
+
+/// Returns all workless of the given `account`.
+pub fn of_account(db: &Database, account: &str) -> Result<Vec<Workless>> {
+    let mut stmt = db.con.prepare(
         "select \
         account, \
         old_company, \
@@ -52,69 +147,40 @@ pub fn fetch(db: &Database, account: &str, old_company: &str, date: NaiveDate) -
         total_time \
         \
         from workless \
-        where account=? and old_company=? and date_of_dismiss",
-        rusqlite::params![account, old_company, date],
-        Workless::from_row,
-    )?)
+        where account=? and deleted_at is null \
+        order by date_of_dismiss",
+    )?;
+    let rows = stmt.query([account])?;
+    DBIter::new(rows).collect()
 }
 
 /// Returns all dates (of dismiss) from the workless table without duplicates
 pub fn all_dates(db: &Database) -> Result<Vec<String>> {
-    let mut stmt = db.con.prepare(
+    repository::distinct(
+        db,
         "select \
         date_of_dismiss \
         from workless \
+        where deleted_at is null \
         order by date_of_dismiss desc",
-    )?;
-
-    let mut rows = stmt.query([])?;
-    let mut dates = Vec::new();
-    let mut seen_dates = HashSet::new();
-
-    while let Some(row) = rows.next()? {
-        let date: String = row.get(0).unwrap();
-
-        // Check if the date has already been seen
-        if seen_dates.contains(&date) {
-            continue; // Skip the duplicate date
-        }
-
-        dates.push(date.clone());
-        seen_dates.insert(date);
-    }
-
-    Ok(dates)
+        [],
+    )
 }
 
 /// Returns all roles from the workless table without duplicates
 pub fn all_roles(db: &Database, date: &str, name: &str) -> Result<Vec<String>> {
-    let mut stmt = db.con.prepare(
+    repository::distinct(
+        db,
         "SELECT \
         DISTINCT user.role \
         FROM workless \
         INNER JOIN user ON workless.account = user.account \
-        WHERE workless.date_of_dismiss like ?1 \
+        WHERE workless.deleted_at is null \
+        and workless.date_of_dismiss like ?1 \
         and workless.account like '%'||?2||'%' \
         ORDER BY user.role ASC",
-    )?;
-
-    let mut rows = stmt.query(rusqlite::params![date, name.trim()])?;
-    let mut roles = Vec::new();
-    let mut seen_roles = HashSet::new();
-
-    while let Some(row) = rows.next()? {
-        let role: String = row.get(0).unwrap();
-
-        // Check if the role has already been seen
-        if seen_roles.contains(&role) {
-            continue; // Skip the duplicate role
-        }
-
-        roles.push(role.clone());
-        seen_roles.insert(role);
-    }
-
-    Ok(roles)
+        rusqlite::params![date, name.trim()],
+    )
 }
 
 /// Searches with roles etc. from the workless table
@@ -123,29 +189,27 @@ pub fn search_role(
     name: &str,
     role: &str,
     date: &str,
-    limit: usize,
-) -> Result<Vec<Workless>> {
-    let mut stmt = db.con.prepare(
-        "SELECT workless.*
-        FROM workless
-        INNER JOIN user ON workless.account = user.account
-        WHERE workless.account LIKE '%' || ?1 || '%'
-        AND user.role LIKE ?2
-        AND workless.date_of_dismiss LIKE ?3
-        ORDER BY CASE
-            WHEN workless.account LIKE ?1 || '%' THEN 0
-            ELSE 1
-        END ASC, workless.account ASC
-        LIMIT ?4",
-    )?;
-
-    let rows = stmt.query(rusqlite::params![
-        name.trim(),
-        role.trim(),
-        date.trim(),
-        limit
-    ])?;
-    DBIter::new(rows).collect()
+    sort: &Sort,
+    paging: Paging,
+) -> Result<Page<Workless>> {
+    let order = sort.order_by(
+        "CASE WHEN workless.account LIKE ?1 || '%' THEN 0 ELSE 1 END ASC, workless.account ASC",
+    );
+    paginate(
+        db,
+        &format!(
+            "SELECT workless.*
+            FROM workless
+            INNER JOIN user ON workless.account = user.account
+            WHERE workless.deleted_at IS NULL
+            AND workless.account LIKE '%' || ?1 || '%'
+            AND user.role LIKE ?2
+            AND workless.date_of_dismiss LIKE ?3
+            {order}"
+        ),
+        rusqlite::params![name.trim(), role.trim(), date.trim()],
+        paging,
+    )
 }
 
 /// Parameters for the advanced search
@@ -168,58 +232,67 @@ impl<'a> WorklessSearch<'a> {
     }
 }
 
+/// Columns the workless searches can be sorted by, including the names of the user.
+pub const SORT_COLUMNS: SortColumns = &[
+    ("account", "workless.account"),
+    ("old_company", "workless.old_company"),
+    ("date_of_dismiss", "workless.date_of_dismiss"),
+    ("currently", "workless.currently"),
+    ("new_company", "workless.new_company"),
+    ("total_time", "workless.total_time"),
+    ("forename", "user.forename"),
+    ("surname", "user.surname"),
+    ("role", "user.role"),
+];
+
 /// Performes a simple workless search with the given `text`.
-pub fn search(db: &Database, params: WorklessSearch, limit: usize) -> Result<Vec<Workless>> {
-    let mut stmt = db.con.prepare(
-        "select \
-        account, \
-        old_company, \
-        date_of_dismiss, \
-        currently, \
-        new_company, \
-        total_time \
-        \
-        from workless \
-        where account like '%'||?1||'%' \
-        and old_company like '%'||?2||'%' \
-        and date_of_dismiss like ?3 \
-        order by case \
-            when account like ?1 || '%' then 0 \
-            else 1 \
-        end asc, account asc \
-        limit ?4",
-    )?;
-    let rows = stmt.query(rusqlite::params![
-        params.name.trim(),
-        params.old_company.trim(),
-        params.date.trim(),
-        limit
-    ])?;
-    DBIter::new(rows).collect()
+pub fn search(
+    db: &Database,
+    params: WorklessSearch,
+    sort: &Sort,
+    paging: Paging,
+) -> Result<Page<Workless>> {
+    let order = sort.order_by(
+        "case when workless.account like ?1 || '%' then 0 else 1 end asc, workless.account asc",
+    );
+    paginate(
+        db,
+        &format!(
+            "select \
+            workless.account, \
+            workless.old_company, \
+            workless.date_of_dismiss, \
+            workless.currently, \
+            workless.new_company, \
+            workless.total_time \
+            \
+            from workless \
+            join user on user.account = workless.account \
+            where workless.deleted_at is null \
+            and workless.account like '%'||?1||'%' \
+            and workless.old_company like '%'||?2||'%' \
+            and workless.date_of_dismiss like ?3 \
+            {order}"
+        ),
+        rusqlite::params![
+            params.name.trim(),
+            params.old_company.trim(),
+            params.date.trim()
+        ],
+        paging,
+    )
 }
 
 /// Adds a new date with presenters.
-pub fn add(db: &Database, workless: &Workless) -> Result<()> {
-    if !workless.is_valid() {
-        return Err(Error::InvalidWorkless);
-    }
-    db.con.execute(
-        "INSERT INTO workless VALUES (?, ?, ?, ?, ?, ?)",
-        rusqlite::params![
-            workless.account.trim(),
-            workless.old_company.trim(),
-            workless.date_of_dismiss,
-            workless.currently,
-            workless.new_company.trim(),
-            workless.total_time.trim()
-        ],
-    )?;
+pub fn add(db: &Database, actor: &str, workless: &Workless) -> Result<()> {
+    repository::add(db, actor, workless)?;
     Ok(())
 }
 
 /// Updates the Workless.
 pub fn update(
     db: &Database,
+    actor: &str,
     previous_account: &str,
     previos_old_company: &str,
     previous_date: NaiveDate,
@@ -227,99 +300,187 @@ pub fn update(
 ) -> Result<()> {
     let previous_account = previous_account.trim();
     let previos_old_company = previos_old_company.trim();
-    if previous_account.is_empty() || previos_old_company.is_empty() || !workless.is_valid() {
-        return Err(Error::InvalidWorkless);
-    }
-
-    let transaction = db.transaction()?;
-    // update date
-    transaction.execute(
-        "update workless set account=?, old_company=?, date_of_dismiss=?, currently=?, new_company=?, total_time=? where account=? and old_company=? and date_of_dismiss=?",
-        rusqlite::params![
-            workless.account.trim(),
-            workless.old_company.trim(),
-            workless.date_of_dismiss,
-            workless.currently,
-            workless.new_company.trim(),
-            workless.total_time.trim(),
+    if previous_account.is_empty() || previos_old_company.is_empty() {
+        return Err(Error::InvalidWorkless(empty_key(
             previous_account,
             previos_old_company,
-            previous_date,
-        ],
-    )?;
+        )));
+    }
+    let previous = (
+        previous_account.into(),
+        previos_old_company.into(),
+        previous_date,
+    );
+    repository::update(db, actor, &previous, workless)
+}
 
-    transaction.commit()?;
-    Ok(())
+/// Errors of the empty key fields.
+fn empty_key(account: &str, old_company: &str) -> Vec<FieldError> {
+    [("account", account), ("old_company", old_company)]
+        .into_iter()
+        .filter(|(_, value)| value.is_empty())
+        .map(|(field, _)| FieldError::new(field, "must not be empty"))
+        .collect()
 }
 
-/// Deletes the workless by account, old company and date.
-pub fn delete(db: &Database, account: &str, old_company: &str, date: NaiveDate) -> Result<()> {
+/// Moves the workless into the trash bin.
+pub fn delete(
+    db: &Database,
+    actor: &str,
+    account: &str,
+    old_company: &str,
+    date: NaiveDate,
+) -> Result<()> {
     let account = account.trim();
     let old_company = old_company.trim();
     if account.is_empty() || old_company.is_empty() {
-        return Err(Error::InvalidWorkless);
+        return Err(Error::InvalidWorkless(empty_key(account, old_company)));
     }
-    let transaction = db.transaction()?;
-    // remove date and presenters
-    transaction.execute(
-        "delete from workless where account=? and old_company=? and date_of_dismiss=?",
-        rusqlite::params![account, old_company, date],
-    )?;
-    transaction.commit()?;
+    repository::delete::<Workless>(db, actor, &(account.into(), old_company.into(), date))
+}
+
+/// Returns the workless in the trash bin, the most recently deleted first.
+pub fn trash(db: &Database, limit: usize) -> Result<Vec<Trashed<Workless>>> {
+    repository::trash(db, limit)
+}
+
+/// Restores the workless from the trash bin.
+///
+/// Fails with [`Error::UnknownAccount`] if its user is in the trash bin too.
+pub fn restore(
+    db: &Database,
+    actor: &str,
+    account: &str,
+    old_company: &str,
+    date: NaiveDate,
+) -> Result<()> {
+    let key = (account.trim().into(), old_company.trim().into(), date);
+    repository::restore::<Workless>(db, actor, &key)?;
     Ok(())
 }
 
+/// Permanently deletes the workless from the trash bin.
+pub fn purge(
+    db: &Database,
+    actor: &str,
+    account: &str,
+    old_company: &str,
+    date: NaiveDate,
+) -> Result<()> {
+    let key = (account.trim().into(), old_company.trim().into(), date);
+    repository::purge::<Workless>(db, actor, &key)
+}
+
+/// Returns all revisions of the workless, the oldest first.
+pub fn history(
+    db: &Database,
+    account: &str,
+    old_company: &str,
+    date: NaiveDate,
+) -> Result<Vec<Version<Workless>>> {
+    repository::history(db, &(account.into(), old_company.into(), date))
+}
+
+/// Returns the workless as they were at the given `time`.
+pub fn as_of(db: &Database, time: DateTime<Utc>, limit: usize) -> Result<Vec<Workless>> {
+    repository::as_of(db, time, limit)
+}
+
 #[cfg(test)]
 mod tests {
+    use crate::db::page::Paging;
+    use crate::db::sort::Sort;
     use chrono::NaiveDate;
 
-    use crate::db::project::{create, Database};
+    use crate::db::project::{create, Database, Error};
+    use crate::db::role::{self, Role, RoleKind};
+    use crate::db::user::{self, User};
     use crate::db::workless::{self, Workless};
+
     #[test]
     fn add_update_remove_workless() {
         let db = Database::memory().unwrap();
         create(&db).unwrap();
+        role::add(&db, "test", &Role::new("Demo", RoleKind::Other)).unwrap();
+
+        let user = User {
+            account: "foo.bar".into(),
+            forename: "Foo".into(),
+            surname: "Bar".into(),
+            role: "Demo".into(),
+        };
+        user::add(&db, "test", &user).unwrap();
 
         let workless = Workless {
             account: "foo.bar".into(),
             old_company: "bars".into(),
-            date_of_dismiss: NaiveDate::from_ymd_opt(2023, 06, 01).unwrap(),
+            date_of_dismiss: NaiveDate::from_ymd_opt(2023, 6, 1).unwrap(),
             currently: false,
             new_company: "fuzz".into(),
             total_time: "24h".into(),
         };
-        workless::add(&db, &workless).unwrap();
+        workless::add(&db, "test", &workless).unwrap();
+
+        let result = workless::add(
+            &db,
+            "test",
+            &Workless {
+                account: "nobody".into(),
+                ..workless.clone()
+            },
+        );
+        assert!(matches!(result, Err(Error::UnknownAccount)));
 
-        let result =
-            workless::search(&db, workless::WorklessSearch::new("%", "%", "%"), 200).unwrap();
+        let result = workless::search(
+            &db,
+            workless::WorklessSearch::new("%", "%", "%"),
+            &Sort::default(),
+            Paging::default(),
+        )
+        .unwrap()
+        .items;
         assert_eq!(result.len(), 1);
         assert_eq!(result[0], workless);
 
         workless::update(
             &db,
+            "test",
             &workless.account,
             &workless.old_company,
-            NaiveDate::from_ymd_opt(2023, 06, 01).unwrap(),
+            NaiveDate::from_ymd_opt(2023, 6, 1).unwrap(),
             &Workless {
                 total_time: "20h".into(),
                 ..workless.clone()
             },
         )
         .unwrap();
-        let result =
-            workless::search(&db, workless::WorklessSearch::new("%", "%", "%"), 200).unwrap();
+        let result = workless::search(
+            &db,
+            workless::WorklessSearch::new("%", "%", "%"),
+            &Sort::default(),
+            Paging::default(),
+        )
+        .unwrap()
+        .items;
         assert_eq!(result.len(), 1);
         assert_eq!(result[0].total_time, "20h".to_string());
 
         workless::delete(
             &db,
+            "test",
             &workless.account,
             &workless.old_company,
-            NaiveDate::from_ymd_opt(2023, 06, 01).unwrap(),
+            NaiveDate::from_ymd_opt(2023, 6, 1).unwrap(),
         )
         .unwrap();
-        let result =
-            workless::search(&db, workless::WorklessSearch::new("%", "%", "%"), 200).unwrap();
+        let result = workless::search(
+            &db,
+            workless::WorklessSearch::new("%", "%", "%"),
+            &Sort::default(),
+            Paging::default(),
+        )
+        .unwrap()
+        .items;
         assert_eq!(result.len(), 0);
     }
 }
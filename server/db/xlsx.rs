@@ -0,0 +1,168 @@
+use chrono::{Datelike, NaiveDate};
+use rust_xlsxwriter::{ExcelDateTime, Format, FormatBorder, Worksheet, XlsxError};
+
+use crate::db::criminal::Criminal;
+use crate::db::project::Result;
+use crate::db::repository::Repository;
+use crate::db::user::User;
+use crate::db::workless::Workless;
+
+/// Record that is written as row of a worksheet.
+///
+/// The worksheet is named like the table, its header row holds the columns.
+pub trait Sheet: Repository {
+    fn write(
+        &self,
+        sheet: &mut Worksheet,
+        row: u32,
+        date: &Format,
+    ) -> std::result::Result<(), XlsxError>;
+}
+
+impl Sheet for User {
+    fn write(
+        &self,
+        sheet: &mut Worksheet,
+        row: u32,
+        _date: &Format,
+    ) -> std::result::Result<(), XlsxError> {
+        sheet
+            .write(row, 0, self.account.as_str())?
+            .write(row, 1, self.forename.as_str())?
+            .write(row, 2, self.surname.as_str())?
+            .write(row, 3, self.role.as_str())?;
+        Ok(())
+    }
+}
+
+impl Sheet for Workless {
+    fn write(
+        &self,
+        sheet: &mut Worksheet,
+        row: u32,
+        date: &Format,
+    ) -> std::result::Result<(), XlsxError> {
+        sheet
+            .write(row, 0, self.account.as_str())?
+            .write(row, 1, self.old_company.as_str())?
+            .write_date_with_format(row, 2, excel_date(self.date_of_dismiss)?, date)?
+            .write(row, 3, self.currently)?
+            .write(row, 4, self.new_company.as_str())?
+            .write(row, 5, self.total_time.as_str())?;
+        Ok(())
+    }
+}
+
+impl Sheet for Criminal {
+    fn write(
+        &self,
+        sheet: &mut Worksheet,
+        row: u32,
+        _date: &Format,
+    ) -> std::result::Result<(), XlsxError> {
+        sheet.write(row, 0, self.id as f64)?;
+        let cells = [
+            &self.case_number,
+            &self.account,
+            &self.kind,
+            &self.accuser,
+            &self.police_consultant,
+            &self.lawyer_culprit,
+            &self.lawyer_accuser,
+            &self.facts,
+            &self.time_of_crime,
+            &self.location_of_crime,
+            &self.note,
+            &self.verdict,
+        ];
+        sheet
+            .write_row(row, 1, cells.map(String::as_str))?
+            .write(row, 13, self.status.as_str())?;
+        Ok(())
+    }
+}
+
+fn excel_date(date: NaiveDate) -> std::result::Result<ExcelDateTime, XlsxError> {
+    let year =
+        u16::try_from(date.year()).map_err(|_| XlsxError::DateTimeRangeError(date.to_string()))?;
+    ExcelDateTime::from_ymd(year, date.month() as u8, date.day() as u8)
+}
+
+/// Excel workbook with a worksheet per table.
+pub struct Workbook {
+    book: rust_xlsxwriter::Workbook,
+    header: Format,
+    date: Format,
+}
+
+impl Default for Workbook {
+    fn default() -> Self {
+        Self {
+            book: rust_xlsxwriter::Workbook::new(),
+            header: Format::new()
+                .set_bold()
+                .set_border_bottom(FormatBorder::Thin),
+            date: Format::new().set_num_format("yyyy-mm-dd"),
+        }
+    }
+}
+
+impl Workbook {
+    /// Adds a worksheet with the `items`, a styled header row and an autofilter.
+    pub fn add<T: Sheet>(&mut self, items: &[T]) -> Result<()> {
+        let sheet = self.book.add_worksheet();
+        sheet.set_name(T::TABLE)?;
+        sheet.write_row_with_format(0, 0, T::COLUMNS.iter().copied(), &self.header)?;
+        for (row, item) in (1..).zip(items) {
+            item.write(sheet, row, &self.date)?;
+        }
+        let last_row = u32::try_from(items.len()).map_err(|_| XlsxError::RowColumnLimitError)?;
+        sheet.autofilter(0, 0, last_row, T::COLUMNS.len() as u16 - 1)?;
+        sheet.set_freeze_panes(1, 0)?;
+        sheet.autofit();
+        Ok(())
+    }
+
+    /// Returns the contents of the xlsx file.
+    pub fn save(mut self) -> Result<Vec<u8>> {
+        Ok(self.book.save_to_buffer()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use crate::db::project::Error;
+    use crate::db::user::User;
+    use crate::db::workless::Workless;
+    use crate::db::xlsx::Workbook;
+
+    #[test]
+    fn workbook() {
+        let user = User {
+            account: "foo.bar".into(),
+            forename: "Foo".into(),
+            surname: "Bar".into(),
+            role: "Demo".into(),
+        };
+        let workless = Workless {
+            account: user.account.clone(),
+            old_company: "bars".into(),
+            date_of_dismiss: NaiveDate::from_ymd_opt(2023, 6, 1).unwrap(),
+            ..Default::default()
+        };
+        let mut book = Workbook::default();
+        book.add(&[user]).unwrap();
+        book.add(&[workless]).unwrap();
+        book.add::<User>(&[]).unwrap();
+        // the names of the worksheets have to be unique
+        assert_eq!(book.save().unwrap_err(), Error::InternalError);
+
+        let mut book = Workbook::default();
+        book.add::<User>(&[]).unwrap();
+        let data = book.save().unwrap();
+        // xlsx files are zip archives
+        assert!(data.starts_with(b"PK"));
+    }
+}
@@ -1,15 +1,22 @@
+mod config;
 pub mod db;
 mod server;
 
+use std::borrow::Cow;
 use std::env;
-use std::{borrow::Cow, path::Path};
+use std::path::PathBuf;
+use std::process;
+use std::time::Duration;
 
-use db::project::{fetch_logins, fetch_user_data, Database, Error, Result};
+use config::Config;
+use db::project::{fetch_logins, Database, Error, Pool, Result};
 
-use clap::Parser;
+use clap::{Parser, Subcommand};
 
 use log::{warn, Level, LevelFilter};
+use rocket::fairing::AdHoc;
 use rocket::serde::json::Json;
+use rocket::tokio::time;
 use rocket::{catch, catchers, response::Responder, routes, Build, Request, Response, Rocket};
 use serde::Serialize;
 use simplelog::{ConfigBuilder, WriteLogger};
@@ -17,25 +24,135 @@ use utoipa::openapi::security::{Http, HttpAuthScheme};
 use utoipa::{openapi::security::SecurityScheme, Modify, OpenApi};
 use utoipa_swagger_ui::SwaggerUi;
 
-use std::fs::OpenOptions;
+use std::fs::{File, OpenOptions};
+use std::io::BufReader;
 
 use crate::db::login::{NewLogin, Permission};
 
-#[derive(Parser)]
+/// Command line arguments, which override the config file.
+#[derive(Parser, Serialize)]
 #[command(author, version, about)]
 struct Args {
-    #[arg(short, long, default_value_t = 80)]
-    port: usize,
-    #[arg(short, long, default_value_t = String::from("0.0.0.0"))]
-    address: String,
+    /// TOML config file
+    #[arg(short, long, default_value = "schiller-db.toml")]
+    #[serde(skip)]
+    config: PathBuf,
+    #[arg(short, long)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    port: Option<u16>,
+    #[arg(short, long)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    address: Option<String>,
+    /// Project database
+    #[arg(long)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    database: Option<PathBuf>,
+    /// Minutes between the scheduled snapshots in the backups directory, 0 disables them
+    #[arg(long)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    backup_interval: Option<u64>,
+    /// Number of scheduled snapshots that are kept
+    #[arg(long)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    backup_retention: Option<usize>,
+    /// Seconds to wait for a free database connection
+    #[arg(long)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pool_timeout: Option<u64>,
+    #[command(subcommand)]
+    #[serde(skip)]
+    command: Option<Command>,
 }
 
-#[rocket::launch]
-fn rocket() -> Rocket<Build> {
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Writes a consistent snapshot of the database to the given file
+    Backup { path: PathBuf },
+    /// Replaces the contents of the database with the given backup
+    Restore { path: PathBuf },
+    /// Writes every record of the database as JSON to the given file
+    Dump { path: PathBuf },
+    /// Creates the database from the given JSON dump
+    Load { path: PathBuf },
+    /// Encrypts the plaintext database with the configured key
+    Encrypt,
+    /// Replaces the key of the encrypted database with SCHILLER_DB_NEW_KEY
+    Rekey,
+    /// Imports users from a file like benutzer.txt and prints a report
+    Import {
+        path: PathBuf,
+        /// Only report the changes without applying them
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+#[rocket::main]
+async fn main() {
+    let args = Args::parse();
+    let config = match Config::load(&args.config, &args) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Invalid configuration: {e}");
+            process::exit(1);
+        }
+    };
+    if let Some(command) = &args.command {
+        if let Err(e) = run(&config, command) {
+            eprintln!("{command:?} failed: {e}");
+            process::exit(1);
+        }
+        return;
+    }
+    if let Err(e) = rocket(config).launch().await {
+        eprintln!("{}", e.pretty_print());
+        process::exit(1);
+    }
+}
+
+/// Executes a command line subcommand instead of starting the server.
+fn run(config: &Config, command: &Command) -> Result<()> {
+    let key = config.key.as_deref();
+    let open = || Ok::<_, Error>(Database::open(Cow::from(&config.database), key)?.0);
+    match command {
+        Command::Backup { path } => db::backup::backup(&open()?, path),
+        Command::Restore { path } => db::backup::restore(&mut open()?, path),
+        Command::Dump { path } => {
+            let dump = db::dump::dump(&open()?)?;
+            serde_json::to_writer_pretty(File::create(path)?, &dump)?;
+            Ok(())
+        }
+        Command::Load { path } => {
+            let file = BufReader::new(File::open(path)?);
+            db::dump::load(&config.database, key, file)?;
+            Ok(())
+        }
+        Command::Encrypt => db::encryption::encrypt(&config.database, key.ok_or(Error::Arguments)?),
+        Command::Rekey => {
+            let new_key = env::var("SCHILLER_DB_NEW_KEY").map_err(|_| Error::Arguments)?;
+            db::encryption::rekey(&open()?, &new_key)
+        }
+        Command::Import { path, dry_run } => {
+            let file = BufReader::new(File::open(path)?);
+            let report = db::import::users(
+                &open()?,
+                db::audit::SYSTEM,
+                file,
+                "|",
+                &config.role_precedence,
+                *dry_run,
+            )?;
+            println!("{report}");
+            Ok(())
+        }
+    }
+}
+
+fn rocket(config: Config) -> Rocket<Build> {
     let file = OpenOptions::new()
         .create(true)
         .append(true)
-        .open("log.txt")
+        .open(&config.log)
         .unwrap();
 
     WriteLogger::init(
@@ -57,18 +174,38 @@ fn rocket() -> Rocket<Build> {
 
     warn!("Started Logging");
 
-    dotenv::from_filename("admin.env").ok();
+    dotenv::from_path(&config.admin_env).ok();
 
-    let path = Path::new("./schiller-db.db");
-    match Database::open(Cow::from(path)) {
-        Ok(db) => db.0,
-        Err(_) => {
-            let db = Database::create(Cow::from(path)).unwrap();
+    let path = config.database.as_path();
+    let key = config.key.as_deref();
+    match Database::open(Cow::from(path), key) {
+        Ok((_, migrated)) => {
+            if migrated {
+                warn!("Migrated database to version {}", db::project::VERSION);
+            }
+        }
+        Err(Error::FileNotFound) => {
+            let db = Database::create(Cow::from(path), key).unwrap();
             db::project::create(&db).unwrap();
-            fetch_user_data(&db, Cow::from(Path::new("./benutzer.txt")), "|").unwrap();
+            match File::open(&config.benutzer) {
+                Ok(file) => {
+                    let report = db::import::users(
+                        &db,
+                        db::audit::SYSTEM,
+                        BufReader::new(file),
+                        "|",
+                        &config.role_precedence,
+                        false,
+                    )
+                    .unwrap();
+                    warn!("Imported {:?}: {report}", config.benutzer);
+                }
+                Err(e) => warn!("Failed to import {:?}: {e}", config.benutzer),
+            }
             // Admin user
             db::login::add(
                 &db,
+                db::audit::SYSTEM,
                 NewLogin {
                     user: env::var("SCHILLER_DB_USER").unwrap(),
                     password: env::var("SCHILLER_DB_PASSWORD").unwrap(),
@@ -79,10 +216,17 @@ fn rocket() -> Rocket<Build> {
             )
             .unwrap();
             //Other Users
-            fetch_logins(&db, Cow::from(Path::new("./logins.txt")), "|").unwrap();
-            db
+            fetch_logins(&db, Cow::from(&config.logins), "|").unwrap();
         }
+        Err(e) => panic!("Failed to open the database: {e:?}"),
     };
+    let pool = db::project::pool(
+        Cow::from(path),
+        key,
+        config.pool_size,
+        Duration::from_secs(config.pool_timeout),
+    )
+    .unwrap();
 
     #[derive(OpenApi)]
     #[openapi(
@@ -90,10 +234,24 @@ fn rocket() -> Rocket<Build> {
             server::stats,
             server::fetch_user,
             server::search_user,
+            server::search_text_user,
             server::all_roles,
             server::add_user,
             server::update_user,
             server::delete_user,
+            server::trash_user,
+            server::restore_user,
+            server::purge_user,
+            server::history_user,
+            server::as_of_user,
+            server::import_users,
+            server::export_users,
+            server::import_users_csv,
+            server::roles,
+            server::fetch_role,
+            server::add_role,
+            server::update_role,
+            server::delete_role,
             server::fetch_workless,
             server::search_workless,
             server::search_workless_roles,
@@ -102,30 +260,69 @@ fn rocket() -> Rocket<Build> {
             server::add_workless,
             server::update_workless,
             server::delete_workless,
+            server::trash_workless,
+            server::restore_workless,
+            server::purge_workless,
+            server::history_workless,
+            server::as_of_workless,
+            server::export_workless,
+            server::import_workless_csv,
             server::fetch_criminal,
             server::all_accounts,
             server::all_roles_criminal,
             server::search_criminal,
+            server::search_text_criminal,
             server::search_criminal_roles,
             server::add_criminal,
             server::update_criminal,
+            server::transition_criminal,
+            server::transitions_criminal,
             server::delete_criminal,
+            server::trash_criminal,
+            server::restore_criminal,
+            server::purge_criminal,
+            server::history_criminal,
+            server::as_of_criminal,
+            server::certificate,
+            server::export_criminals,
+            server::import_criminals_csv,
+            server::fetch_hearing,
+            server::hearings_of_case,
+            server::docket,
+            server::export_hearings_ics,
+            server::add_hearing,
+            server::update_hearing,
+            server::delete_hearing,
             server::fetch_permission,
             server::add_login,
             server::update_login,
             server::delete_login,
             server::delete_all_logins,
+            server::search_audit,
+            server::backup,
+            server::restore,
+            server::dump,
         ),
         components(
-            schemas(db::user::User, db::workless::Workless, db::criminal::Criminal, db::login::NewLogin, db::login::Permission, db::login::Permissions, db::stats::Stats, db::project::Error)
+            schemas(db::user::User, db::role::Role, db::role::RoleKind, db::workless::Workless, db::criminal::Criminal, db::status::CaseStatus, db::status::Transition, db::hearing::Hearing, db::hearing::DocketEntry, db::fulltext::UserHit, db::fulltext::CriminalHit, db::login::NewLogin, db::login::Permission, db::login::Permissions, db::stats::Stats, db::project::Error, db::project::FieldError, db::audit::Audit, db::audit::Entity, db::audit::Operation, db::trash::TrashedUser, db::trash::TrashedWorkless, db::trash::TrashedCriminal, db::history::UserVersion, db::history::WorklessVersion, db::history::CriminalVersion, db::import::ImportReport, db::import::LineError, db::page::UserPage, db::page::WorklessPage, db::page::CriminalPage, db::page::UserHitPage, db::page::CriminalHitPage)
         ),
         tags(
             (name = "server", description = "Server management endpoints.")
         ),
-        modifiers(&SecurityAddon)
+        modifiers(&SecurityAddon, &ValidationAddon)
     )]
     struct ApiDoc;
 
+    #[cfg(feature = "xlsx")]
+    #[derive(OpenApi)]
+    #[openapi(paths(
+        server::export_users_xlsx,
+        server::export_workless_xlsx,
+        server::export_criminals_xlsx,
+        server::export_xlsx,
+    ))]
+    struct XlsxDoc;
+
     struct SecurityAddon;
 
     impl Modify for SecurityAddon {
@@ -138,13 +335,38 @@ fn rocket() -> Rocket<Build> {
         }
     }
 
-    let Args { port, address } = Args::parse();
+    struct ValidationAddon;
+
+    impl Modify for ValidationAddon {
+        fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+            let components = openapi.components.as_mut().unwrap();
+            db::validate::document::<db::user::User>(components);
+            db::validate::document::<db::role::Role>(components);
+            db::validate::document::<db::workless::Workless>(components);
+            db::validate::document::<db::criminal::Criminal>(components);
+            db::validate::document::<db::hearing::Hearing>(components);
+            db::validate::document::<db::login::NewLogin>(components);
+        }
+    }
+
     let figment = rocket::Config::figment()
-        .merge(("limits.json", 32768))
-        .merge(("address", address))
-        .merge(("port", port));
+        .merge(("limits.json", config.json_limit))
+        .merge(("address", &config.address))
+        .merge(("port", config.port));
 
-    rocket::custom(figment)
+    #[allow(unused_mut)]
+    let mut openapi = ApiDoc::openapi();
+    #[cfg(feature = "xlsx")]
+    openapi.merge(XlsxDoc::openapi());
+
+    let rocket = rocket::custom(figment)
+        .manage(pool)
+        .attach(scheduled_backups(
+            config.backups.clone(),
+            config.backup_interval,
+            config.backup_retention,
+        ))
+        .manage(config)
         .register(
             "/",
             catchers![
@@ -152,13 +374,14 @@ fn rocket() -> Rocket<Build> {
                 not_found,
                 unprocessable_entity,
                 internal_error,
+                service_unavailable,
                 exceeded_limit_413,
                 exceeded_limit_414
             ],
         )
         .mount(
             "/",
-            SwaggerUi::new("/swagger-ui/<_..>").url("/api-docs/openapi.json", ApiDoc::openapi()),
+            SwaggerUi::new("/swagger-ui/<_..>").url("/api-docs/openapi.json", openapi),
         )
         .mount(
             "/",
@@ -169,10 +392,24 @@ fn rocket() -> Rocket<Build> {
                 server::stats,
                 server::fetch_user,
                 server::search_user,
+                server::search_text_user,
                 server::all_roles,
                 server::add_user,
                 server::update_user,
                 server::delete_user,
+                server::trash_user,
+                server::restore_user,
+                server::purge_user,
+                server::history_user,
+                server::as_of_user,
+                server::import_users,
+                server::export_users,
+                server::import_users_csv,
+                server::roles,
+                server::fetch_role,
+                server::add_role,
+                server::update_role,
+                server::delete_role,
                 server::fetch_workless,
                 server::search_workless,
                 server::search_workless_roles,
@@ -181,21 +418,89 @@ fn rocket() -> Rocket<Build> {
                 server::add_workless,
                 server::update_workless,
                 server::delete_workless,
+                server::trash_workless,
+                server::restore_workless,
+                server::purge_workless,
+                server::history_workless,
+                server::as_of_workless,
+                server::export_workless,
+                server::import_workless_csv,
                 server::fetch_criminal,
                 server::all_accounts,
                 server::all_roles_criminal,
                 server::search_criminal,
+                server::search_text_criminal,
                 server::search_criminal_roles,
                 server::add_criminal,
                 server::update_criminal,
+                server::transition_criminal,
+                server::transitions_criminal,
                 server::delete_criminal,
+                server::trash_criminal,
+                server::restore_criminal,
+                server::purge_criminal,
+                server::history_criminal,
+                server::as_of_criminal,
+                server::certificate,
+                server::export_criminals,
+                server::import_criminals_csv,
+                server::fetch_hearing,
+                server::hearings_of_case,
+                server::docket,
+                server::export_hearings_ics,
+                server::add_hearing,
+                server::update_hearing,
+                server::delete_hearing,
                 server::fetch_permission,
                 server::add_login,
                 server::update_login,
                 server::delete_login,
                 server::delete_all_logins,
+                server::search_audit,
+                server::backup,
+                server::restore,
+                server::dump,
             ],
-        )
+        );
+    #[cfg(feature = "xlsx")]
+    let rocket = rocket.mount(
+        "/",
+        routes![
+            server::export_users_xlsx,
+            server::export_workless_xlsx,
+            server::export_criminals_xlsx,
+            server::export_xlsx,
+        ],
+    );
+    rocket
+}
+
+/// Writes a snapshot into `dir` every `interval` minutes.
+fn scheduled_backups(dir: PathBuf, interval: u64, retention: usize) -> AdHoc {
+    AdHoc::on_liftoff("Scheduled Backups", move |rocket| {
+        Box::pin(async move {
+            if interval == 0 {
+                return;
+            }
+            let db = server::Db::new(rocket.state::<Pool>().unwrap().clone());
+            rocket::tokio::spawn(async move {
+                let mut timer = time::interval(Duration::from_secs(interval * 60));
+                // the first tick completes immediately
+                timer.tick().await;
+                loop {
+                    timer.tick().await;
+                    let dir = dir.clone();
+                    let result = db
+                        .run(move |db| db::backup::snapshot(db, &dir, retention))
+                        .await;
+                    match result {
+                        Ok(path) => warn!("Created backup {path:?}"),
+                        Err(e) => warn!("Failed to create backup: {e}"),
+                    }
+                }
+            });
+        })
+    })
 }
 
 struct JsonWithHeaders<T: Serialize> {
@@ -214,7 +519,7 @@ impl<'r, T: Serialize> Responder<'r, 'static> for JsonWithHeaders<T> {
 }
 
 #[catch(401)]
-async fn unauthorized<'r>(_req: &Request<'_>) -> JsonWithHeaders<Result<()>> {
+async fn unauthorized(_req: &Request<'_>) -> JsonWithHeaders<Result<()>> {
     let json = Json(Err(Error::Unauthorized));
     let headers = vec![("WWW-Authenticate", "Basic realm=\"User Visible Realm\"")];
     JsonWithHeaders { headers, json }
@@ -235,6 +540,11 @@ async fn internal_error(_req: &Request<'_>) -> Json<Result<()>> {
     Json(Err(Error::InternalError))
 }
 
+#[catch(503)]
+async fn service_unavailable(_req: &Request<'_>) -> Json<Result<()>> {
+    Json(Err(Error::DatabaseUnavailable))
+}
+
 #[catch(413)]
 async fn exceeded_limit_413(_req: &Request<'_>) -> Json<Result<()>> {
     Json(Err(Error::ExceededLimit))
@@ -1,35 +1,50 @@
 use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
 use log::warn;
 use rocket::{
+    data::{ByteUnit, Data},
     delete,
     fs::NamedFile,
     get,
-    http::Status,
+    http::{ContentType, Status},
     outcome::Outcome,
     post, put,
     request::{self, FromRequest},
+    response::{self, Responder},
     serde::json::Json,
-    Request,
+    tokio::task,
+    Request, Response, State,
 };
 
-use std::{
-    borrow::Cow,
-    path::{Path, PathBuf},
-};
+use std::{borrow::Cow, fs, io::Cursor, path::PathBuf};
 use std::{env, marker::PhantomData};
 
+use crate::config::Config;
 use crate::db::{
     self,
     login::{NewLogin, Permissions},
-    user::UserSearch,
+    user::{OnDelete, UserSearch},
 };
-use chrono::NaiveDate;
+use chrono::{DateTime, Local, NaiveDate, Utc};
+use serde::Serialize;
 
+use db::audit::{Audit, AuditSearch, Entity};
+use db::certificate::Certificate;
 use db::criminal::{Criminal, CriminalSearch};
+use db::fulltext::Hit;
+use db::hearing::{DocketEntry, Hearing};
+use db::history::Version;
+use db::import::ImportReport;
 use db::login::{Login, Permission};
-use db::project::{Database, Error, Result};
+use db::page::{Page, Paging};
+use db::project::{Database, Error, FieldError, Pool, Result};
+use db::repository::Repository;
+use db::role::Role;
+use db::sort::Sort;
 use db::stats::Stats;
+use db::status::{CaseStatus, Transition};
+use db::trash::Trashed;
 use db::user::User;
+use db::validate::{MAX_DATE, MIN_DATE};
 use db::workless::{Workless, WorklessSearch};
 
 pub trait Access {
@@ -71,6 +86,18 @@ impl Access for CriminalWrite {
         matches!(l.access_criminal, Permission::Write)
     }
 }
+pub struct Authenticated {}
+impl Access for Authenticated {
+    fn check(_l: Login) -> bool {
+        true
+    }
+}
+pub struct Admin {}
+impl Access for Admin {
+    fn check(l: Login) -> bool {
+        env::var("SCHILLER_DB_USER").is_ok_and(|admin| admin == l.user)
+    }
+}
 
 pub struct Auth<P: Access> {
     pub user: String,
@@ -105,16 +132,27 @@ impl<'r, P: Access> FromRequest<'r> for Auth<P> {
 
         // lookup in database
 
-        let Ok((db, _)) = Database::open(Cow::from(Path::new("./schiller-db.db"))) else {
-            warn!("could not open Database");
-            return Outcome::Error((Status::Unauthorized, Error::Unauthorized));
+        let db = match request.guard::<Db>().await {
+            Outcome::Success(db) => db,
+            Outcome::Error(e) => return Outcome::Error(e),
+            Outcome::Forward(s) => return Outcome::Forward(s),
         };
-        let Ok(login) = db::login::fetch(&db, user) else {
-            warn!(
-                "missing auth credentials '{user}:{password}' from {:?}",
-                request.client_ip()
-            );
-            return Outcome::Error((Status::Unauthorized, Error::Unauthorized));
+        let login = {
+            let user = user.to_string();
+            db.run(move |db| db::login::fetch(db, &user)).await
+        };
+        let login = match login {
+            Ok(login) => login,
+            Err(Error::DatabaseUnavailable) => {
+                return Outcome::Error((Status::ServiceUnavailable, Error::DatabaseUnavailable));
+            }
+            Err(_) => {
+                warn!(
+                    "missing auth credentials '{user}:{password}' from {:?}",
+                    request.client_ip()
+                );
+                return Outcome::Error((Status::Unauthorized, Error::Unauthorized));
+            }
         };
 
         // checking password
@@ -140,21 +178,68 @@ impl<'r, P: Access> FromRequest<'r> for Auth<P> {
     }
 }
 
+/// Pooled database connection for the request handlers.
+///
+/// The blocking SQLite calls are executed on the blocking thread pool
+/// instead of the async executor.
+pub struct Db(Pool);
+
+impl Db {
+    pub fn new(pool: Pool) -> Self {
+        Self(pool)
+    }
+
+    /// Runs `f` with a connection from the pool.
+    ///
+    /// Fails with [`Error::DatabaseUnavailable`] if no connection becomes free in time.
+    pub async fn run<T, F>(&self, f: F) -> Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&Database) -> Result<T> + Send + 'static,
+    {
+        let pool = self.0.clone();
+        task::spawn_blocking(move || {
+            let db = pool.get().map_err(|e| {
+                warn!("no database connection available: {e}");
+                Error::DatabaseUnavailable
+            })?;
+            f(&db)
+        })
+        .await
+        .map_err(|e| {
+            warn!("database task failed: {e}");
+            Error::InternalError
+        })?
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Db {
+    type Error = Error;
+
+    async fn from_request(request: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+        match request.rocket().state::<Pool>() {
+            Some(pool) => Outcome::Success(Db(pool.clone())),
+            None => Outcome::Error((Status::InternalServerError, Error::InternalError)),
+        }
+    }
+}
+
 #[get("/")]
-pub async fn index() -> Option<NamedFile> {
-    let path = Path::new("build").join("index.html");
+pub async fn index(config: &State<Config>) -> Option<NamedFile> {
+    let path = config.static_dir.join("index.html");
     NamedFile::open(path).await.ok()
 }
 
 #[get("/login")]
-pub async fn login() -> Option<NamedFile> {
-    let path = Path::new("build").join("login.html");
+pub async fn login(config: &State<Config>) -> Option<NamedFile> {
+    let path = config.static_dir.join("login.html");
     NamedFile::open(path).await.ok()
 }
 
 #[get("/<path..>")]
-pub async fn static_files(path: PathBuf) -> Option<NamedFile> {
-    let path = Path::new("build").join(path);
+pub async fn static_files(config: &State<Config>, path: PathBuf) -> Option<NamedFile> {
+    let path = config.static_dir.join(path);
     NamedFile::open(path).await.ok()
 }
 
@@ -162,23 +247,23 @@ pub async fn static_files(path: PathBuf) -> Option<NamedFile> {
     responses(
         (status = 200, description = "Got Stats", body = Stats),
         (status = 401, description = "Unauthorized to view Stats", body = Error, example = json!({"Err": Error::Unauthorized})),
+        (status = 503, description = "The database is unavailable", body = Error, example = json!({"Err": Error::DatabaseUnavailable})),
     ),
     security (
         ("authorization" = []),
     )
 )]
 #[get("/api/stats")]
-pub async fn stats(_auth: Auth<UserReadOnly>) -> Json<Result<Stats>> {
-    let db = Database::open(Cow::from(Path::new("./schiller-db.db")))
-        .unwrap()
-        .0;
-    Json(db::stats::fetch(&db))
+pub async fn stats(_auth: Auth<UserReadOnly>, db: Db) -> JsonResult<Stats> {
+    JsonResult(db.run(db::stats::fetch).await)
 }
 
 #[utoipa::path(
     responses(
         (status = 200, description = "Got a User by a specific id", body = User),
         (status = 401, description = "Unauthorized to fetch a User", body = Error, example = json!({"Err": Error::Unauthorized})),
+        (status = 404, description = "User not found", body = Error, example = json!({"Err": Error::NothingFound})),
+        (status = 503, description = "The database is unavailable", body = Error, example = json!({"Err": Error::DatabaseUnavailable})),
     ),
     params(
         ("id", description = "The unique user id")
@@ -188,82 +273,137 @@ pub async fn stats(_auth: Auth<UserReadOnly>) -> Json<Result<Stats>> {
     )
 )]
 #[get("/api/user/fetch/<id>")]
-pub async fn fetch_user(_auth: Auth<UserReadOnly>, id: &str) -> Json<Result<User>> {
-    let db = Database::open(Cow::from(Path::new("./schiller-db.db")))
-        .unwrap()
-        .0;
-    Json(db::user::fetch(&db, id))
+pub async fn fetch_user(_auth: Auth<UserReadOnly>, db: Db, id: String) -> JsonResult<User> {
+    JsonResult(db.run(move |db| db::user::fetch(db, &id)).await)
 }
 
 #[utoipa::path(
     responses(
-        (status = 200, description = "Searched all Users", body = Vec<User>),
+        (status = 200, description = "Searched all Users", body = UserPage),
         (status = 401, description = "Unauthorized to search all Users", body = Error, example = json!({"Err": Error::Unauthorized})),
+        (status = 503, description = "The database is unavailable", body = Error, example = json!({"Err": Error::DatabaseUnavailable})),
+    ),
+    params(
+        ("offset", description = "Position of the page in the results"),
+        ("limit", description = "Size of the page, at most 1000"),
+        ("sort", description = "Comma separated columns, descending with a leading `-`"),
     ),
     security (
         ("authorization" = []),
     )
 )]
-#[get("/api/user/search?<name>&<role>&<limit>")]
+#[get("/api/user/search?<name>&<role>&<sort>&<offset>&<limit>")]
 pub async fn search_user(
     _auth: Auth<UserReadOnly>,
-    name: Option<&str>,
-    role: Option<&str>,
+    db: Db,
+    name: Option<String>,
+    role: Option<String>,
+    sort: Option<String>,
+    offset: Option<usize>,
+    limit: Option<usize>,
+) -> JsonResult<Page<User>> {
+    JsonResult(
+        db.run(move |db| {
+            let sort = Sort::parse(sort.as_deref().unwrap_or_default(), db::user::SORT_COLUMNS)?;
+            db::user::search(
+                db,
+                UserSearch::new(
+                    name.as_deref().unwrap_or_default(),
+                    role.as_deref().unwrap_or("%"),
+                ),
+                &sort,
+                Paging::new(offset, limit),
+            )
+        })
+        .await,
+    )
+}
+
+#[utoipa::path(
+    responses(
+        (status = 200, description = "Searched the names and roles of all Users by relevance", body = UserHitPage),
+        (status = 401, description = "Unauthorized to search all Users", body = Error, example = json!({"Err": Error::Unauthorized})),
+        (status = 503, description = "The database is unavailable", body = Error, example = json!({"Err": Error::DatabaseUnavailable})),
+    ),
+    params(
+        ("offset", description = "Position of the page in the results"),
+        ("limit", description = "Size of the page, at most 1000"),
+        ("sort", description = "Comma separated columns, descending with a leading `-`"),
+        ("text", description = "The words to search for"),
+    ),
+    security (
+        ("authorization" = []),
+    )
+)]
+#[get("/api/user/search_text?<text>&<sort>&<offset>&<limit>")]
+pub async fn search_text_user(
+    _auth: Auth<UserReadOnly>,
+    db: Db,
+    text: String,
+    sort: Option<String>,
+    offset: Option<usize>,
     limit: Option<usize>,
-) -> Json<Result<Vec<User>>> {
-    let db = Database::open(Cow::from(Path::new("./schiller-db.db")))
-        .unwrap()
-        .0;
-    Json(db::user::search(
-        &db,
-        UserSearch::new(name.unwrap_or_default(), role.unwrap_or("%")),
-        limit.unwrap_or(200),
-    ))
+) -> JsonResult<Page<Hit<User>>> {
+    JsonResult(
+        db.run(move |db| {
+            let sort = Sort::parse(sort.as_deref().unwrap_or_default(), db::user::SORT_COLUMNS)?;
+            db::user::search_text(db, &text, &sort, Paging::new(offset, limit))
+        })
+        .await,
+    )
 }
 
 #[utoipa::path(
     responses(
         (status = 200, description = "Got all Roles", body = Vec<String>),
         (status = 401, description = "Unauthorized to get all Roles", body = Error, example = json!({"Err": Error::Unauthorized})),
+        (status = 503, description = "The database is unavailable", body = Error, example = json!({"Err": Error::DatabaseUnavailable})),
     ),
     security (
         ("authorization" = []),
     )
 )]
 #[get("/api/user/all_roles?<name>")]
-pub async fn all_roles(_auth: Auth<UserReadOnly>, name: Option<&str>) -> Json<Result<Vec<String>>> {
-    let db = Database::open(Cow::from(Path::new("./schiller-db.db")))
-        .unwrap()
-        .0;
-    Json(db::user::all_roles(&db, name.unwrap_or("")))
+pub async fn all_roles(
+    _auth: Auth<UserReadOnly>,
+    db: Db,
+    name: Option<String>,
+) -> JsonResult<Vec<String>> {
+    JsonResult(
+        db.run(move |db| db::user::all_roles(db, name.as_deref().unwrap_or("")))
+            .await,
+    )
 }
 
 #[utoipa::path(
     request_body = User,
     responses(
         (status = 200, description = "Add a User sended successfully"),
+        (status = 400, description = "The User is invalid", body = Error, example = json!({"Err": Error::InvalidUser(vec![FieldError::new("account", "must not be empty")])})),
         (status = 401, description = "Unauthorized to add a User", body = Error, example = json!({"Err": Error::Unauthorized})),
+        (status = 409, description = "User already exists or is in the trash bin", body = Error, example = json!({"Err": Error::AlreadyExists})),
         (status = 422, description = "The Json is parsed in a wrong format", body = Error, example = json!({"Err": Error::UnprocessableEntity})),
+        (status = 503, description = "The database is unavailable", body = Error, example = json!({"Err": Error::DatabaseUnavailable})),
     ),
     security (
         ("authorization" = []),
     )
 )]
 #[post("/api/user", format = "json", data = "<user>")]
-pub async fn add_user(auth: Auth<UserWrite>, user: Json<User>) -> Json<Result<()>> {
+pub async fn add_user(auth: Auth<UserWrite>, db: Db, user: Json<User>) -> JsonResult<()> {
     warn!("POST /user with data {user:?}: {}", auth.user);
-    let db = Database::open(Cow::from(Path::new("./schiller-db.db")))
-        .unwrap()
-        .0;
-    Json(db::user::add(&db, &user))
+    JsonResult(db.run(move |db| db::user::add(db, &auth.user, &user)).await)
 }
 
 #[utoipa::path(
     request_body = User,
     responses(
         (status = 200, description = "Update a User sended successfully"),
+        (status = 400, description = "The User is invalid", body = Error, example = json!({"Err": Error::InvalidUser(vec![FieldError::new("account", "must not be empty")])})),
         (status = 401, description = "Unauthorized to update a User", body = Error, example = json!({"Err": Error::Unauthorized})),
+        (status = 409, description = "User already exists or is in the trash bin", body = Error, example = json!({"Err": Error::AlreadyExists})),
         (status = 422, description = "The Json is parsed in a wrong format", body = Error, example = json!({"Err": Error::UnprocessableEntity})),
+        (status = 503, description = "The database is unavailable", body = Error, example = json!({"Err": Error::DatabaseUnavailable})),
     ),
     params(
         ("id", description = "The unique user id")
@@ -273,39 +413,490 @@ pub async fn add_user(auth: Auth<UserWrite>, user: Json<User>) -> Json<Result<()
     )
 )]
 #[put("/api/user/<id>", format = "json", data = "<user>")]
-pub async fn update_user(auth: Auth<UserWrite>, user: Json<User>, id: &str) -> Json<Result<()>> {
+pub async fn update_user(
+    auth: Auth<UserWrite>,
+    db: Db,
+    user: Json<User>,
+    id: String,
+) -> JsonResult<()> {
     warn!("PUT /user/{id} with data {user:?}: {}", auth.user);
-    let db = Database::open(Cow::from(Path::new("./schiller-db.db")))
-        .unwrap()
-        .0;
-    Json(db::user::update(&db, id, &user))
+    JsonResult(
+        db.run(move |db| db::user::update(db, &auth.user, &id, &user))
+            .await,
+    )
 }
 
 #[utoipa::path(
     responses(
         (status = 200, description = "User delete sended successfully"),
+        (status = 400, description = "The admin can not be deleted", body = Error, example = json!({"Err": Error::InvalidUser(vec![FieldError::new("account", "the admin can not be deleted")])})),
         (status = 401, description = "Unauthorized to delete Users", body = Error, example = json!({"Err": Error::Unauthorized})),
+        (status = 409, description = "The User still has entries", body = Error, example = json!({"Err": Error::UserReferenced})),
+        (status = 503, description = "The database is unavailable", body = Error, example = json!({"Err": Error::DatabaseUnavailable})),
     ),
     params(
-        ("id", description = "The unique user id")
+        ("id", description = "The unique user id"),
+        ("restrict", description = "Refuse to delete a User with workless or criminal entries instead of deleting them too")
     ),
     security(
         ("authorization" = []),
     )
 )]
-#[delete("/api/user/<id>")]
-pub async fn delete_user(auth: Auth<UserWrite>, id: &str) -> Json<Result<()>> {
+#[delete("/api/user/<id>?<restrict>")]
+pub async fn delete_user(
+    auth: Auth<UserWrite>,
+    db: Db,
+    id: String,
+    restrict: Option<bool>,
+) -> JsonResult<()> {
     warn!("DELETE /user/{id}: {}", auth.user);
-    let db = Database::open(Cow::from(Path::new("./schiller-db.db")))
-        .unwrap()
-        .0;
-    Json(db::user::delete(&db, id))
+
+    // the login of the user can not be used anymore
+    if env::var("SCHILLER_DB_USER").is_ok_and(|admin| admin == id.trim()) {
+        warn!("unable to delete admin '{id}'");
+        return JsonResult(Err(Error::InvalidUser(vec![FieldError::new(
+            "account",
+            "the admin can not be deleted",
+        )])));
+    }
+
+    let on_delete = match restrict {
+        Some(true) => OnDelete::Restrict,
+        _ => OnDelete::Cascade,
+    };
+    JsonResult(
+        db.run(move |db| db::user::delete(db, &auth.user, &id, on_delete))
+            .await,
+    )
+}
+
+#[utoipa::path(
+    responses(
+        (status = 200, description = "Got the Users in the trash bin, the most recently deleted first", body = Vec<TrashedUser>),
+        (status = 401, description = "Unauthorized to view the deleted Users", body = Error, example = json!({"Err": Error::Unauthorized})),
+        (status = 503, description = "The database is unavailable", body = Error, example = json!({"Err": Error::DatabaseUnavailable})),
+    ),
+    params(
+        ("limit", description = "Number of results, at most 1000"),
+    ),
+    security(
+        ("authorization" = []),
+    )
+)]
+#[get("/api/user/trash?<limit>")]
+pub async fn trash_user(
+    _auth: Auth<UserWrite>,
+    db: Db,
+    limit: Option<usize>,
+) -> JsonResult<Vec<Trashed<User>>> {
+    JsonResult(
+        db.run(move |db| db::user::trash(db, Paging::new(None, limit).limit))
+            .await,
+    )
+}
+
+#[utoipa::path(
+    responses(
+        (status = 200, description = "User restored from the trash bin"),
+        (status = 401, description = "Unauthorized to restore Users", body = Error, example = json!({"Err": Error::Unauthorized})),
+        (status = 404, description = "User not in the trash bin", body = Error, example = json!({"Err": Error::NothingFound})),
+        (status = 503, description = "The database is unavailable", body = Error, example = json!({"Err": Error::DatabaseUnavailable})),
+    ),
+    params(
+        ("id", description = "The unique user id"),
+    ),
+    security(
+        ("authorization" = []),
+    )
+)]
+#[post("/api/user/<id>/restore")]
+pub async fn restore_user(auth: Auth<UserWrite>, db: Db, id: String) -> JsonResult<()> {
+    warn!("POST /user/{id}/restore: {}", auth.user);
+    JsonResult(
+        db.run(move |db| db::user::restore(db, &auth.user, &id))
+            .await,
+    )
+}
+
+#[utoipa::path(
+    responses(
+        (status = 200, description = "User permanently deleted from the trash bin"),
+        (status = 401, description = "Unauthorized to purge Users", body = Error, example = json!({"Err": Error::Unauthorized})),
+        (status = 404, description = "User not in the trash bin", body = Error, example = json!({"Err": Error::NothingFound})),
+        (status = 503, description = "The database is unavailable", body = Error, example = json!({"Err": Error::DatabaseUnavailable})),
+    ),
+    params(
+        ("id", description = "The unique user id"),
+    ),
+    security(
+        ("authorization" = []),
+    )
+)]
+#[delete("/api/user/<id>/purge")]
+pub async fn purge_user(auth: Auth<UserWrite>, db: Db, id: String) -> JsonResult<()> {
+    warn!("DELETE /user/{id}/purge: {}", auth.user);
+    JsonResult(db.run(move |db| db::user::purge(db, &auth.user, &id)).await)
+}
+
+#[utoipa::path(
+    responses(
+        (status = 200, description = "Got all revisions of a User, the oldest first", body = Vec<UserVersion>),
+        (status = 401, description = "Unauthorized to view the history of Users", body = Error, example = json!({"Err": Error::Unauthorized})),
+        (status = 503, description = "The database is unavailable", body = Error, example = json!({"Err": Error::DatabaseUnavailable})),
+    ),
+    params(
+        ("id", description = "The unique user id"),
+    ),
+    security(
+        ("authorization" = []),
+    )
+)]
+#[get("/api/user/history/<id>")]
+pub async fn history_user(
+    _auth: Auth<UserReadOnly>,
+    db: Db,
+    id: String,
+) -> JsonResult<Vec<Version<User>>> {
+    JsonResult(db.run(move |db| db::user::history(db, &id)).await)
+}
+
+#[utoipa::path(
+    responses(
+        (status = 200, description = "Got the Users as they were at the given time", body = Vec<User>),
+        (status = 400, description = "The timestamp is invalid", body = Error, example = json!({"Err": Error::InvalidDate})),
+        (status = 401, description = "Unauthorized to view the history of Users", body = Error, example = json!({"Err": Error::Unauthorized})),
+        (status = 503, description = "The database is unavailable", body = Error, example = json!({"Err": Error::DatabaseUnavailable})),
+    ),
+    params(
+        ("time", description = "RFC 3339 timestamp"),
+        ("limit", description = "Number of results, at most 1000"),
+    ),
+    security(
+        ("authorization" = []),
+    )
+)]
+#[get("/api/user/as_of?<time>&<limit>")]
+pub async fn as_of_user(
+    _auth: Auth<UserReadOnly>,
+    db: Db,
+    time: String,
+    limit: Option<usize>,
+) -> JsonResult<Vec<User>> {
+    let time = match timestamp(&time) {
+        Ok(time) => time,
+        Err(e) => return JsonResult(Err(e)),
+    };
+    JsonResult(
+        db.run(move |db| db::user::as_of(db, time, Paging::new(None, limit).limit))
+            .await,
+    )
+}
+
+/// Maximum size of an uploaded user import.
+const IMPORT_LIMIT: ByteUnit = ByteUnit::Mebibyte(8);
+
+#[utoipa::path(
+    request_body(content = String, description = "Lines of 'account|forename|surname|role'", content_type = "text/plain"),
+    responses(
+        (status = 200, description = "Imported the valid lines, the report lists the rejected ones", body = ImportReport),
+        (status = 401, description = "Unauthorized to import Users", body = Error, example = json!({"Err": Error::Unauthorized})),
+        (status = 413, description = "The import is too large", body = Error, example = json!({"Err": Error::ExceededLimit})),
+        (status = 503, description = "The database is unavailable", body = Error, example = json!({"Err": Error::DatabaseUnavailable})),
+    ),
+    params(
+        ("dry_run", description = "Only report the changes without applying them"),
+    ),
+    security(
+        ("authorization" = []),
+    )
+)]
+#[post("/api/user/import?<dry_run>", data = "<data>")]
+pub async fn import_users(
+    auth: Auth<UserWrite>,
+    db: Db,
+    config: &State<Config>,
+    dry_run: Option<bool>,
+    data: Data<'_>,
+) -> JsonResult<ImportReport> {
+    let dry_run = dry_run.unwrap_or(false);
+    warn!("POST /user/import?dry_run={dry_run}: {}", auth.user);
+    let text = match upload(data).await {
+        Ok(text) => text,
+        Err(e) => return JsonResult(Err(e)),
+    };
+    let role_precedence = config.role_precedence.clone();
+    JsonResult(
+        db.run(move |db| {
+            db::import::users(db, &auth.user, &text[..], "|", &role_precedence, dry_run)
+        })
+        .await,
+    )
+}
+
+/// Reads an uploaded import.
+async fn upload(data: Data<'_>) -> Result<Vec<u8>> {
+    match data.open(IMPORT_LIMIT).into_bytes().await {
+        Ok(bytes) if bytes.is_complete() => Ok(bytes.into_inner()),
+        Ok(_) => Err(Error::ExceededLimit),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// CSV table to download.
+fn csv_download<T: Repository>(name: &str, page: Result<Page<T>>) -> Result<Download> {
+    Ok(Download {
+        name: format!("{name}-{}.csv", Local::now().format("%Y%m%d-%H%M%S")),
+        content_type: ContentType::CSV,
+        data: db::export::csv(&page?.items)?,
+    })
+}
+
+#[utoipa::path(
+    responses(
+        (status = 200, description = "Exported the Users matching the search as CSV", content_type = "text/csv"),
+        (status = 401, description = "Unauthorized to export Users", body = Error, example = json!({"Err": Error::Unauthorized})),
+        (status = 503, description = "The database is unavailable", body = Error, example = json!({"Err": Error::DatabaseUnavailable})),
+    ),
+    params(
+        ("name", description = "Like the search"),
+        ("role", description = "Like the search"),
+    ),
+    security(
+        ("authorization" = []),
+    )
+)]
+#[get("/api/user/csv?<name>&<role>")]
+pub async fn export_users(
+    _auth: Auth<UserReadOnly>,
+    db: Db,
+    name: Option<String>,
+    role: Option<String>,
+) -> std::result::Result<Download, JsonResult<()>> {
+    let users = db
+        .run(move |db| {
+            db::user::search(
+                db,
+                UserSearch::new(
+                    name.as_deref().unwrap_or_default(),
+                    role.as_deref().unwrap_or("%"),
+                ),
+                &Sort::default(),
+                Paging::all(),
+            )
+        })
+        .await;
+    csv_download("users", users).map_err(|e| JsonResult(Err(e)))
+}
+
+/// Excel workbook to download.
+#[cfg(feature = "xlsx")]
+fn xlsx_download(name: &str, book: Result<db::xlsx::Workbook>) -> Result<Download> {
+    Ok(Download {
+        name: format!("{name}-{}.xlsx", Local::now().format("%Y%m%d-%H%M%S")),
+        content_type: ContentType::new(
+            "application",
+            "vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+        ),
+        data: book?.save()?,
+    })
+}
+
+#[cfg(feature = "xlsx")]
+#[utoipa::path(
+    responses(
+        (status = 200, description = "Exported the Users matching the search as Excel workbook", content_type = "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet"),
+        (status = 401, description = "Unauthorized to export Users", body = Error, example = json!({"Err": Error::Unauthorized})),
+        (status = 413, description = "Too many Users for a worksheet", body = Error, example = json!({"Err": Error::ExceededLimit})),
+        (status = 503, description = "The database is unavailable", body = Error, example = json!({"Err": Error::DatabaseUnavailable})),
+    ),
+    params(
+        ("name", description = "Like the search"),
+        ("role", description = "Like the search"),
+    ),
+    security(
+        ("authorization" = []),
+    )
+)]
+#[get("/api/user/xlsx?<name>&<role>")]
+pub async fn export_users_xlsx(
+    _auth: Auth<UserReadOnly>,
+    db: Db,
+    name: Option<String>,
+    role: Option<String>,
+) -> std::result::Result<Download, JsonResult<()>> {
+    let book = db
+        .run(move |db| {
+            let mut book = db::xlsx::Workbook::default();
+            book.add(
+                &db::user::search(
+                    db,
+                    UserSearch::new(
+                        name.as_deref().unwrap_or_default(),
+                        role.as_deref().unwrap_or("%"),
+                    ),
+                    &Sort::default(),
+                    Paging::all(),
+                )?
+                .items,
+            )?;
+            Ok(book)
+        })
+        .await;
+    xlsx_download("users", book).map_err(|e| JsonResult(Err(e)))
+}
+
+#[utoipa::path(
+    request_body(content = String, description = "CSV table with a header row naming the User fields", content_type = "text/csv"),
+    responses(
+        (status = 200, description = "Imported all rows, or none if the report lists rejected rows", body = ImportReport),
+        (status = 400, description = "The CSV is malformed", body = Error, example = json!({"Err": Error::InvalidFormat})),
+        (status = 401, description = "Unauthorized to import Users", body = Error, example = json!({"Err": Error::Unauthorized})),
+        (status = 413, description = "The import is too large", body = Error, example = json!({"Err": Error::ExceededLimit})),
+        (status = 503, description = "The database is unavailable", body = Error, example = json!({"Err": Error::DatabaseUnavailable})),
+    ),
+    params(
+        ("dry_run", description = "Only report the changes without applying them"),
+    ),
+    security(
+        ("authorization" = []),
+    )
+)]
+#[post("/api/user/csv?<dry_run>", data = "<data>")]
+pub async fn import_users_csv(
+    auth: Auth<UserWrite>,
+    db: Db,
+    dry_run: Option<bool>,
+    data: Data<'_>,
+) -> JsonResult<ImportReport> {
+    let dry_run = dry_run.unwrap_or(false);
+    warn!("POST /user/csv?dry_run={dry_run}: {}", auth.user);
+    let data = match upload(data).await {
+        Ok(data) => data,
+        Err(e) => return JsonResult(Err(e)),
+    };
+    JsonResult(
+        db.run(move |db| db::import::users_csv(db, &auth.user, &data[..], dry_run))
+            .await,
+    )
+}
+
+#[utoipa::path(
+    responses(
+        (status = 200, description = "Got all Roles with their metadata", body = Vec<Role>),
+        (status = 401, description = "Unauthorized to get the Roles", body = Error, example = json!({"Err": Error::Unauthorized})),
+        (status = 503, description = "The database is unavailable", body = Error, example = json!({"Err": Error::DatabaseUnavailable})),
+    ),
+    security (
+        ("authorization" = []),
+    )
+)]
+#[get("/api/role")]
+pub async fn roles(_auth: Auth<UserReadOnly>, db: Db) -> JsonResult<Vec<Role>> {
+    JsonResult(db.run(db::role::all).await)
+}
+
+#[utoipa::path(
+    responses(
+        (status = 200, description = "Got a Role", body = Role),
+        (status = 401, description = "Unauthorized to fetch a Role", body = Error, example = json!({"Err": Error::Unauthorized})),
+        (status = 404, description = "Role not found", body = Error, example = json!({"Err": Error::NothingFound})),
+        (status = 503, description = "The database is unavailable", body = Error, example = json!({"Err": Error::DatabaseUnavailable})),
+    ),
+    params(
+        ("name", description = "The name of the role, regardless of the case")
+    ),
+    security (
+        ("authorization" = []),
+    )
+)]
+#[get("/api/role/fetch/<name>")]
+pub async fn fetch_role(_auth: Auth<UserReadOnly>, db: Db, name: String) -> JsonResult<Role> {
+    JsonResult(db.run(move |db| db::role::fetch(db, &name)).await)
+}
+
+#[utoipa::path(
+    request_body = Role,
+    responses(
+        (status = 200, description = "Add a Role sended successfully"),
+        (status = 400, description = "The Role is invalid", body = Error, example = json!({"Err": Error::InvalidRole(vec![FieldError::new("parent", "unknown role")])})),
+        (status = 401, description = "Unauthorized to add a Role", body = Error, example = json!({"Err": Error::Unauthorized})),
+        (status = 409, description = "Role already exists", body = Error, example = json!({"Err": Error::AlreadyExists})),
+        (status = 422, description = "The Json is parsed in a wrong format", body = Error, example = json!({"Err": Error::UnprocessableEntity})),
+        (status = 503, description = "The database is unavailable", body = Error, example = json!({"Err": Error::DatabaseUnavailable})),
+    ),
+    security (
+        ("authorization" = []),
+    )
+)]
+#[post("/api/role", format = "json", data = "<role>")]
+pub async fn add_role(auth: Auth<UserWrite>, db: Db, role: Json<Role>) -> JsonResult<()> {
+    warn!("POST /role with data {role:?}: {}", auth.user);
+    JsonResult(db.run(move |db| db::role::add(db, &auth.user, &role)).await)
+}
+
+#[utoipa::path(
+    request_body = Role,
+    responses(
+        (status = 200, description = "Update a Role sended successfully, renaming it also renames it for its Users"),
+        (status = 400, description = "The Role is invalid", body = Error, example = json!({"Err": Error::InvalidRole(vec![FieldError::new("parent", "must not contain the role itself")])})),
+        (status = 401, description = "Unauthorized to update a Role", body = Error, example = json!({"Err": Error::Unauthorized})),
+        (status = 404, description = "Role not found", body = Error, example = json!({"Err": Error::NothingFound})),
+        (status = 409, description = "Role already exists", body = Error, example = json!({"Err": Error::AlreadyExists})),
+        (status = 422, description = "The Json is parsed in a wrong format", body = Error, example = json!({"Err": Error::UnprocessableEntity})),
+        (status = 503, description = "The database is unavailable", body = Error, example = json!({"Err": Error::DatabaseUnavailable})),
+    ),
+    params(
+        ("name", description = "The name of the role, regardless of the case")
+    ),
+    security (
+        ("authorization" = []),
+    )
+)]
+#[put("/api/role/<name>", format = "json", data = "<role>")]
+pub async fn update_role(
+    auth: Auth<UserWrite>,
+    db: Db,
+    role: Json<Role>,
+    name: String,
+) -> JsonResult<()> {
+    warn!("PUT /role/{name} with data {role:?}: {}", auth.user);
+    JsonResult(
+        db.run(move |db| db::role::update(db, &auth.user, &name, &role))
+            .await,
+    )
+}
+
+#[utoipa::path(
+    responses(
+        (status = 200, description = "Role delete sended successfully"),
+        (status = 401, description = "Unauthorized to delete Roles", body = Error, example = json!({"Err": Error::Unauthorized})),
+        (status = 404, description = "Role not found", body = Error, example = json!({"Err": Error::NothingFound})),
+        (status = 409, description = "The Role is still assigned to Users or has child Roles", body = Error, example = json!({"Err": Error::RoleReferenced})),
+        (status = 503, description = "The database is unavailable", body = Error, example = json!({"Err": Error::DatabaseUnavailable})),
+    ),
+    params(
+        ("name", description = "The name of the role, regardless of the case")
+    ),
+    security(
+        ("authorization" = []),
+    )
+)]
+#[delete("/api/role/<name>")]
+pub async fn delete_role(auth: Auth<UserWrite>, db: Db, name: String) -> JsonResult<()> {
+    warn!("DELETE /role/{name}: {}", auth.user);
+    JsonResult(
+        db.run(move |db| db::role::delete(db, &auth.user, &name))
+            .await,
+    )
 }
 
 #[utoipa::path(
     responses(
         (status = 200, description = "Got a Workless by a specific account, old company and date", body = Workless),
+        (status = 400, description = "The date is invalid", body = Error, example = json!({"Err": Error::InvalidDate})),
         (status = 401, description = "Unauthorized to fetch a Workless", body = Error, example = json!({"Err": Error::Unauthorized})),
+        (status = 404, description = "Workless not found", body = Error, example = json!({"Err": Error::NothingFound})),
+        (status = 503, description = "The database is unavailable", body = Error, example = json!({"Err": Error::DatabaseUnavailable})),
     ),
     params(
         ("account", description = "The unique user account"),
@@ -319,103 +910,137 @@ pub async fn delete_user(auth: Auth<UserWrite>, id: &str) -> Json<Result<()>> {
 #[get("/api/workless/fetch/<account>/<old_company>/<date>")]
 pub async fn fetch_workless(
     _auth: Auth<WorklessReadOnly>,
-    account: &str,
-    old_company: &str,
-    date: &str,
-) -> Json<Result<Workless>> {
-    let db = Database::open(Cow::from(Path::new("./schiller-db.db")))
-        .unwrap()
-        .0;
-    let date = match NaiveDate::parse_from_str(date, "%Y-%m-%d") {
+    db: Db,
+    account: String,
+    old_company: String,
+    date: String,
+) -> JsonResult<Workless> {
+    let date = match NaiveDate::parse_from_str(&date, "%Y-%m-%d") {
         Ok(date) => date,
         Err(_) => {
-            return Json(Err(Error::InvalidDate));
+            return JsonResult(Err(Error::InvalidDate));
         }
     };
-    Json(db::workless::fetch(&db, account, old_company, date))
+    JsonResult(
+        db.run(move |db| db::workless::fetch(db, &account, &old_company, date))
+            .await,
+    )
 }
 
 #[utoipa::path(
     responses(
-        (status = 200, description = "Searched all Workless", body = Vec<Workless>),
+        (status = 200, description = "Searched all Workless", body = WorklessPage),
         (status = 401, description = "Unauthorized to search all Workless", body = Error, example = json!({"Err": Error::Unauthorized})),
+        (status = 503, description = "The database is unavailable", body = Error, example = json!({"Err": Error::DatabaseUnavailable})),
+    ),
+    params(
+        ("offset", description = "Position of the page in the results"),
+        ("limit", description = "Size of the page, at most 1000"),
+        ("sort", description = "Comma separated columns, descending with a leading `-`"),
     ),
     security (
         ("authorization" = []),
     )
 )]
-#[get("/api/workless/search?<name>&<old_company>&<date>&<limit>")]
+#[get("/api/workless/search?<name>&<old_company>&<date>&<sort>&<offset>&<limit>")]
+#[allow(clippy::too_many_arguments)]
 pub async fn search_workless(
     _auth: Auth<WorklessReadOnly>,
-    name: Option<&str>,
-    old_company: Option<&str>,
-    date: Option<&str>,
+    db: Db,
+    name: Option<String>,
+    old_company: Option<String>,
+    date: Option<String>,
+    sort: Option<String>,
+    offset: Option<usize>,
     limit: Option<usize>,
-) -> Json<Result<Vec<Workless>>> {
-    let db = Database::open(Cow::from(Path::new("./schiller-db.db")))
-        .unwrap()
-        .0;
-    Json(db::workless::search(
-        &db,
-        WorklessSearch::new(
-            name.unwrap_or_default(),
-            old_company.unwrap_or("%"),
-            date.unwrap_or("%"),
-        ),
-        limit.unwrap_or(200),
-    ))
+) -> JsonResult<Page<Workless>> {
+    JsonResult(
+        db.run(move |db| {
+            let sort = Sort::parse(
+                sort.as_deref().unwrap_or_default(),
+                db::workless::SORT_COLUMNS,
+            )?;
+            db::workless::search(
+                db,
+                WorklessSearch::new(
+                    name.as_deref().unwrap_or_default(),
+                    old_company.as_deref().unwrap_or("%"),
+                    date.as_deref().unwrap_or("%"),
+                ),
+                &sort,
+                Paging::new(offset, limit),
+            )
+        })
+        .await,
+    )
 }
 
 #[utoipa::path(
     responses(
-        (status = 200, description = "Searched all Workless by roles", body = Vec<Workless>),
+        (status = 200, description = "Searched all Workless by roles", body = WorklessPage),
         (status = 401, description = "Unauthorized to search all Workless by roles", body = Error, example = json!({"Err": Error::Unauthorized})),
+        (status = 503, description = "The database is unavailable", body = Error, example = json!({"Err": Error::DatabaseUnavailable})),
+    ),
+    params(
+        ("offset", description = "Position of the page in the results"),
+        ("limit", description = "Size of the page, at most 1000"),
+        ("sort", description = "Comma separated columns, descending with a leading `-`"),
     ),
     security (
         ("authorization" = []),
     )
 )]
-#[get("/api/workless/search_role?<name>&<date>&<role>&<limit>")]
+#[get("/api/workless/search_role?<name>&<date>&<role>&<sort>&<offset>&<limit>")]
+#[allow(clippy::too_many_arguments)]
 pub async fn search_workless_roles(
     _auth: Auth<WorklessReadOnly>,
-    name: Option<&str>,
-    date: Option<&str>,
-    role: Option<&str>,
+    db: Db,
+    name: Option<String>,
+    date: Option<String>,
+    role: Option<String>,
+    sort: Option<String>,
+    offset: Option<usize>,
     limit: Option<usize>,
-) -> Json<Result<Vec<Workless>>> {
-    let db = Database::open(Cow::from(Path::new("./schiller-db.db")))
-        .unwrap()
-        .0;
-    Json(db::workless::search_role(
-        &db,
-        name.unwrap_or(""),
-        role.unwrap_or("%"),
-        date.unwrap_or("%"),
-        limit.unwrap_or(9999),
-    ))
+) -> JsonResult<Page<Workless>> {
+    JsonResult(
+        db.run(move |db| {
+            let sort = Sort::parse(
+                sort.as_deref().unwrap_or_default(),
+                db::workless::SORT_COLUMNS,
+            )?;
+            db::workless::search_role(
+                db,
+                name.as_deref().unwrap_or(""),
+                role.as_deref().unwrap_or("%"),
+                date.as_deref().unwrap_or("%"),
+                &sort,
+                Paging::new(offset, limit),
+            )
+        })
+        .await,
+    )
 }
 
 #[utoipa::path(
     responses(
         (status = 200, description = "Got all Dates", body = Vec<String>),
         (status = 401, description = "Unauthorized to get all Dates", body = Error, example = json!({"Err": Error::Unauthorized})),
+        (status = 503, description = "The database is unavailable", body = Error, example = json!({"Err": Error::DatabaseUnavailable})),
     ),
     security (
         ("authorization" = []),
     )
 )]
 #[get("/api/workless/all_dates")]
-pub async fn all_dates(_auth: Auth<WorklessReadOnly>) -> Json<Result<Vec<String>>> {
-    let db = Database::open(Cow::from(Path::new("./schiller-db.db")))
-        .unwrap()
-        .0;
-    Json(db::workless::all_dates(&db))
+pub async fn all_dates(_auth: Auth<WorklessReadOnly>, db: Db) -> JsonResult<Vec<String>> {
+    JsonResult(db.run(db::workless::all_dates).await)
 }
 
 #[utoipa::path(
     responses(
         (status = 200, description = "Got all Roles by Date", body = Vec<String>),
         (status = 401, description = "Unauthorized to get all Roles by Date", body = Error, example = json!({"Err": Error::Unauthorized})),
+        (status = 503, description = "The database is unavailable", body = Error, example = json!({"Err": Error::DatabaseUnavailable})),
     ),
     security (
         ("authorization" = []),
@@ -424,45 +1049,58 @@ pub async fn all_dates(_auth: Auth<WorklessReadOnly>) -> Json<Result<Vec<String>
 #[get("/api/workless/all_roles?<date>&<name>")]
 pub async fn all_roles_workless(
     _auth: Auth<WorklessReadOnly>,
-    date: Option<&str>,
-    name: Option<&str>,
-) -> Json<Result<Vec<String>>> {
-    let db = Database::open(Cow::from(Path::new("./schiller-db.db")))
-        .unwrap()
-        .0;
-    Json(db::workless::all_roles(
-        &db,
-        date.unwrap_or("%"),
-        name.unwrap_or(""),
-    ))
+    db: Db,
+    date: Option<String>,
+    name: Option<String>,
+) -> JsonResult<Vec<String>> {
+    JsonResult(
+        db.run(move |db| {
+            db::workless::all_roles(
+                db,
+                date.as_deref().unwrap_or("%"),
+                name.as_deref().unwrap_or(""),
+            )
+        })
+        .await,
+    )
 }
 
 #[utoipa::path(
     request_body = Workless,
     responses(
         (status = 200, description = "Add an Workless sended successfully"),
+        (status = 400, description = "The account does not exist", body = Error, example = json!({"Err": Error::UnknownAccount})),
         (status = 401, description = "Unauthorized to add a Workless", body = Error, example = json!({"Err": Error::Unauthorized})),
+        (status = 409, description = "Workless already exists or is in the trash bin", body = Error, example = json!({"Err": Error::AlreadyExists})),
         (status = 422, description = "The Json is parsed in a wrong format", body = Error, example = json!({"Err": Error::UnprocessableEntity})),
+        (status = 503, description = "The database is unavailable", body = Error, example = json!({"Err": Error::DatabaseUnavailable})),
     ),
     security (
         ("authorization" = []),
     )
 )]
 #[post("/api/workless", format = "json", data = "<workless>")]
-pub async fn add_workless(auth: Auth<WorklessWrite>, workless: Json<Workless>) -> Json<Result<()>> {
+pub async fn add_workless(
+    auth: Auth<WorklessWrite>,
+    db: Db,
+    workless: Json<Workless>,
+) -> JsonResult<()> {
     warn!("POST /workless with data {workless:?}: {}", auth.user);
-    let db = Database::open(Cow::from(Path::new("./schiller-db.db")))
-        .unwrap()
-        .0;
-    Json(db::workless::add(&db, &workless))
+    JsonResult(
+        db.run(move |db| db::workless::add(db, &auth.user, &workless))
+            .await,
+    )
 }
 
 #[utoipa::path(
     request_body = Workless,
     responses (
         (status = 200, description = "Update an Workless sended successfully"),
+        (status = 400, description = "The date is invalid", body = Error, example = json!({"Err": Error::InvalidDate})),
         (status = 401, description = "Unauthorized to update an Workless", body = Error, example = json!({"Err": Error::Unauthorized})),
+        (status = 409, description = "Workless already exists or is in the trash bin", body = Error, example = json!({"Err": Error::AlreadyExists})),
         (status = 422, description = "The Json is parsed in a wrong format", body = Error, example = json!({"Err": Error::UnprocessableEntity})),
+        (status = 503, description = "The database is unavailable", body = Error, example = json!({"Err": Error::DatabaseUnavailable})),
     ),
     params(
         ("previous_account", description = "The unique user account"),
@@ -480,37 +1118,43 @@ pub async fn add_workless(auth: Auth<WorklessWrite>, workless: Json<Workless>) -
 )]
 pub async fn update_workless(
     auth: Auth<WorklessWrite>,
+    db: Db,
     workless: Json<Workless>,
-    previous_account: &str,
-    previous_old_company: &str,
-    previous_date: &str,
-) -> Json<Result<()>> {
+    previous_account: String,
+    previous_old_company: String,
+    previous_date: String,
+) -> JsonResult<()> {
     warn!(
         "PUT /workless/{previous_account}/{previous_old_company}/{previous_date} with data {workless:?}: {}",
         auth.user
     );
-    let db = Database::open(Cow::from(Path::new("./schiller-db.db")))
-        .unwrap()
-        .0;
-    let previous_date = match NaiveDate::parse_from_str(previous_date, "%Y-%m-%d") {
+    let previous_date = match NaiveDate::parse_from_str(&previous_date, "%Y-%m-%d") {
         Ok(previous_date) => previous_date,
         Err(_) => {
-            return Json(Err(Error::InvalidDate));
+            return JsonResult(Err(Error::InvalidDate));
         }
     };
-    Json(db::workless::update(
-        &db,
-        previous_account,
-        previous_old_company,
-        previous_date,
-        &workless,
-    ))
+    JsonResult(
+        db.run(move |db| {
+            db::workless::update(
+                db,
+                &auth.user,
+                &previous_account,
+                &previous_old_company,
+                previous_date,
+                &workless,
+            )
+        })
+        .await,
+    )
 }
 
 #[utoipa::path(
     responses(
         (status = 200, description = "Workless delete sended successfully"),
+        (status = 400, description = "The date is invalid", body = Error, example = json!({"Err": Error::InvalidDate})),
         (status = 401, description = "Unauthorized to delete Workless", body = Error, example = json!({"Err": Error::Unauthorized})),
+        (status = 503, description = "The database is unavailable", body = Error, example = json!({"Err": Error::DatabaseUnavailable})),
     ),
     params(
         ("account", description = "The unique user account"),
@@ -524,69 +1168,364 @@ pub async fn update_workless(
 #[delete("/api/workless/<account>/<old_company>/<date>")]
 pub async fn delete_workless(
     auth: Auth<WorklessWrite>,
-    account: &str,
-    old_company: &str,
-    date: &str,
-) -> Json<Result<()>> {
+    db: Db,
+    account: String,
+    old_company: String,
+    date: String,
+) -> JsonResult<()> {
     warn!("DELETE /workless/{account}/{date}: {}", auth.user);
-    let db = Database::open(Cow::from(Path::new("./schiller-db.db")))
-        .unwrap()
-        .0;
-    let date = match NaiveDate::parse_from_str(date, "%Y-%m-%d") {
+    let date = match NaiveDate::parse_from_str(&date, "%Y-%m-%d") {
         Ok(date) => date,
         Err(_) => {
-            return Json(Err(Error::InvalidDate));
+            return JsonResult(Err(Error::InvalidDate));
         }
     };
-    Json(db::workless::delete(&db, account, old_company, date))
+    JsonResult(
+        db.run(move |db| db::workless::delete(db, &auth.user, &account, &old_company, date))
+            .await,
+    )
 }
 
 #[utoipa::path(
     responses(
-        (status = 200, description = "Got a Criminal by a specific account and kind", body = Criminal),
-        (status = 401, description = "Unauthorized to fetch a Criminal", body = Error, example = json!({"Err": Error::Unauthorized})),
+        (status = 200, description = "Got the Workless in the trash bin, the most recently deleted first", body = Vec<TrashedWorkless>),
+        (status = 401, description = "Unauthorized to view the deleted Workless", body = Error, example = json!({"Err": Error::Unauthorized})),
+        (status = 503, description = "The database is unavailable", body = Error, example = json!({"Err": Error::DatabaseUnavailable})),
     ),
     params(
-        ("account", description = "The unique user account"),
-        ("kind", description = "The kind of the crime"),
+        ("limit", description = "Number of results, at most 1000"),
     ),
-    security (
+    security(
         ("authorization" = []),
     )
 )]
-#[get("/api/criminal/fetch/<account>/<kind>")]
-pub async fn fetch_criminal(
-    _auth: Auth<CriminalReadOnly>,
-    account: &str,
-    kind: &str,
-) -> Json<Result<Criminal>> {
-    let db = Database::open(Cow::from(Path::new("./schiller-db.db")))
-        .unwrap()
-        .0;
-    Json(db::criminal::fetch(&db, account, kind))
+#[get("/api/workless/trash?<limit>")]
+pub async fn trash_workless(
+    _auth: Auth<WorklessWrite>,
+    db: Db,
+    limit: Option<usize>,
+) -> JsonResult<Vec<Trashed<Workless>>> {
+    JsonResult(
+        db.run(move |db| db::workless::trash(db, Paging::new(None, limit).limit))
+            .await,
+    )
 }
 
 #[utoipa::path(
     responses(
-        (status = 200, description = "Got all Kinds", body = Vec<String>),
-        (status = 401, description = "Unauthorized to get all Kinds", body = Error, example = json!({"Err": Error::Unauthorized})),
+        (status = 200, description = "Workless restored from the trash bin"),
+        (status = 400, description = "The date is invalid", body = Error, example = json!({"Err": Error::InvalidDate})),
+        (status = 401, description = "Unauthorized to restore Workless", body = Error, example = json!({"Err": Error::Unauthorized})),
+        (status = 404, description = "Workless not in the trash bin", body = Error, example = json!({"Err": Error::NothingFound})),
+        (status = 503, description = "The database is unavailable", body = Error, example = json!({"Err": Error::DatabaseUnavailable})),
     ),
-    security (
-        ("authorization" = []),
+    params(
+        ("account", description = "The unique user account"),
+        ("old_company", description = "The old company"),
+        ("date", description = "The date"),
+    ),
+    security(
+        ("authorization" = []),
+    )
+)]
+#[post("/api/workless/<account>/<old_company>/<date>/restore")]
+pub async fn restore_workless(
+    auth: Auth<WorklessWrite>,
+    db: Db,
+    account: String,
+    old_company: String,
+    date: String,
+) -> JsonResult<()> {
+    warn!(
+        "POST /workless/{account}/{old_company}/{date}/restore: {}",
+        auth.user
+    );
+    let date = match NaiveDate::parse_from_str(&date, "%Y-%m-%d") {
+        Ok(date) => date,
+        Err(_) => {
+            return JsonResult(Err(Error::InvalidDate));
+        }
+    };
+    JsonResult(
+        db.run(move |db| db::workless::restore(db, &auth.user, &account, &old_company, date))
+            .await,
+    )
+}
+
+#[utoipa::path(
+    responses(
+        (status = 200, description = "Workless permanently deleted from the trash bin"),
+        (status = 400, description = "The date is invalid", body = Error, example = json!({"Err": Error::InvalidDate})),
+        (status = 401, description = "Unauthorized to purge Workless", body = Error, example = json!({"Err": Error::Unauthorized})),
+        (status = 404, description = "Workless not in the trash bin", body = Error, example = json!({"Err": Error::NothingFound})),
+        (status = 503, description = "The database is unavailable", body = Error, example = json!({"Err": Error::DatabaseUnavailable})),
+    ),
+    params(
+        ("account", description = "The unique user account"),
+        ("old_company", description = "The old company"),
+        ("date", description = "The date"),
+    ),
+    security(
+        ("authorization" = []),
+    )
+)]
+#[delete("/api/workless/<account>/<old_company>/<date>/purge")]
+pub async fn purge_workless(
+    auth: Auth<WorklessWrite>,
+    db: Db,
+    account: String,
+    old_company: String,
+    date: String,
+) -> JsonResult<()> {
+    warn!(
+        "DELETE /workless/{account}/{old_company}/{date}/purge: {}",
+        auth.user
+    );
+    let date = match NaiveDate::parse_from_str(&date, "%Y-%m-%d") {
+        Ok(date) => date,
+        Err(_) => {
+            return JsonResult(Err(Error::InvalidDate));
+        }
+    };
+    JsonResult(
+        db.run(move |db| db::workless::purge(db, &auth.user, &account, &old_company, date))
+            .await,
+    )
+}
+
+#[utoipa::path(
+    responses(
+        (status = 200, description = "Got all revisions of a Workless, the oldest first", body = Vec<WorklessVersion>),
+        (status = 400, description = "The date is invalid", body = Error, example = json!({"Err": Error::InvalidDate})),
+        (status = 401, description = "Unauthorized to view the history of Workless", body = Error, example = json!({"Err": Error::Unauthorized})),
+        (status = 503, description = "The database is unavailable", body = Error, example = json!({"Err": Error::DatabaseUnavailable})),
+    ),
+    params(
+        ("account", description = "The unique user account"),
+        ("old_company", description = "The old company"),
+        ("date", description = "The date"),
+    ),
+    security(
+        ("authorization" = []),
+    )
+)]
+#[get("/api/workless/history/<account>/<old_company>/<date>")]
+pub async fn history_workless(
+    _auth: Auth<WorklessReadOnly>,
+    db: Db,
+    account: String,
+    old_company: String,
+    date: String,
+) -> JsonResult<Vec<Version<Workless>>> {
+    let date = match NaiveDate::parse_from_str(&date, "%Y-%m-%d") {
+        Ok(date) => date,
+        Err(_) => {
+            return JsonResult(Err(Error::InvalidDate));
+        }
+    };
+    JsonResult(
+        db.run(move |db| db::workless::history(db, &account, &old_company, date))
+            .await,
+    )
+}
+
+#[utoipa::path(
+    responses(
+        (status = 200, description = "Got the Workless as they were at the given time", body = Vec<Workless>),
+        (status = 400, description = "The timestamp is invalid", body = Error, example = json!({"Err": Error::InvalidDate})),
+        (status = 401, description = "Unauthorized to view the history of Workless", body = Error, example = json!({"Err": Error::Unauthorized})),
+        (status = 503, description = "The database is unavailable", body = Error, example = json!({"Err": Error::DatabaseUnavailable})),
+    ),
+    params(
+        ("time", description = "RFC 3339 timestamp"),
+        ("limit", description = "Number of results, at most 1000"),
+    ),
+    security(
+        ("authorization" = []),
+    )
+)]
+#[get("/api/workless/as_of?<time>&<limit>")]
+pub async fn as_of_workless(
+    _auth: Auth<WorklessReadOnly>,
+    db: Db,
+    time: String,
+    limit: Option<usize>,
+) -> JsonResult<Vec<Workless>> {
+    let time = match timestamp(&time) {
+        Ok(time) => time,
+        Err(e) => return JsonResult(Err(e)),
+    };
+    JsonResult(
+        db.run(move |db| db::workless::as_of(db, time, Paging::new(None, limit).limit))
+            .await,
+    )
+}
+
+#[utoipa::path(
+    responses(
+        (status = 200, description = "Exported the Workless matching the search as CSV", content_type = "text/csv"),
+        (status = 401, description = "Unauthorized to export Workless", body = Error, example = json!({"Err": Error::Unauthorized})),
+        (status = 503, description = "The database is unavailable", body = Error, example = json!({"Err": Error::DatabaseUnavailable})),
+    ),
+    params(
+        ("name", description = "Like the search"),
+        ("old_company", description = "Like the search"),
+        ("date", description = "Like the search"),
+    ),
+    security(
+        ("authorization" = []),
+    )
+)]
+#[get("/api/workless/csv?<name>&<old_company>&<date>")]
+pub async fn export_workless(
+    _auth: Auth<WorklessReadOnly>,
+    db: Db,
+    name: Option<String>,
+    old_company: Option<String>,
+    date: Option<String>,
+) -> std::result::Result<Download, JsonResult<()>> {
+    let workless = db
+        .run(move |db| {
+            db::workless::search(
+                db,
+                WorklessSearch::new(
+                    name.as_deref().unwrap_or_default(),
+                    old_company.as_deref().unwrap_or("%"),
+                    date.as_deref().unwrap_or("%"),
+                ),
+                &Sort::default(),
+                Paging::all(),
+            )
+        })
+        .await;
+    csv_download("workless", workless).map_err(|e| JsonResult(Err(e)))
+}
+
+#[cfg(feature = "xlsx")]
+#[utoipa::path(
+    responses(
+        (status = 200, description = "Exported the Workless matching the search as Excel workbook", content_type = "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet"),
+        (status = 401, description = "Unauthorized to export Workless", body = Error, example = json!({"Err": Error::Unauthorized})),
+        (status = 413, description = "Too many Workless for a worksheet", body = Error, example = json!({"Err": Error::ExceededLimit})),
+        (status = 503, description = "The database is unavailable", body = Error, example = json!({"Err": Error::DatabaseUnavailable})),
+    ),
+    params(
+        ("name", description = "Like the search"),
+        ("old_company", description = "Like the search"),
+        ("date", description = "Like the search"),
+    ),
+    security(
+        ("authorization" = []),
+    )
+)]
+#[get("/api/workless/xlsx?<name>&<old_company>&<date>")]
+pub async fn export_workless_xlsx(
+    _auth: Auth<WorklessReadOnly>,
+    db: Db,
+    name: Option<String>,
+    old_company: Option<String>,
+    date: Option<String>,
+) -> std::result::Result<Download, JsonResult<()>> {
+    let book = db
+        .run(move |db| {
+            let mut book = db::xlsx::Workbook::default();
+            book.add(
+                &db::workless::search(
+                    db,
+                    WorklessSearch::new(
+                        name.as_deref().unwrap_or_default(),
+                        old_company.as_deref().unwrap_or("%"),
+                        date.as_deref().unwrap_or("%"),
+                    ),
+                    &Sort::default(),
+                    Paging::all(),
+                )?
+                .items,
+            )?;
+            Ok(book)
+        })
+        .await;
+    xlsx_download("workless", book).map_err(|e| JsonResult(Err(e)))
+}
+
+#[utoipa::path(
+    request_body(content = String, description = "CSV table with a header row naming the Workless fields", content_type = "text/csv"),
+    responses(
+        (status = 200, description = "Imported all rows, or none if the report lists rejected rows", body = ImportReport),
+        (status = 400, description = "The CSV is malformed", body = Error, example = json!({"Err": Error::InvalidFormat})),
+        (status = 401, description = "Unauthorized to import Workless", body = Error, example = json!({"Err": Error::Unauthorized})),
+        (status = 413, description = "The import is too large", body = Error, example = json!({"Err": Error::ExceededLimit})),
+        (status = 503, description = "The database is unavailable", body = Error, example = json!({"Err": Error::DatabaseUnavailable})),
+    ),
+    params(
+        ("dry_run", description = "Only report the changes without applying them"),
+    ),
+    security(
+        ("authorization" = []),
+    )
+)]
+#[post("/api/workless/csv?<dry_run>", data = "<data>")]
+pub async fn import_workless_csv(
+    auth: Auth<WorklessWrite>,
+    db: Db,
+    dry_run: Option<bool>,
+    data: Data<'_>,
+) -> JsonResult<ImportReport> {
+    let dry_run = dry_run.unwrap_or(false);
+    warn!("POST /workless/csv?dry_run={dry_run}: {}", auth.user);
+    let data = match upload(data).await {
+        Ok(data) => data,
+        Err(e) => return JsonResult(Err(e)),
+    };
+    JsonResult(
+        db.run(move |db| db::import::workless_csv(db, &auth.user, &data[..], dry_run))
+            .await,
+    )
+}
+
+#[utoipa::path(
+    responses(
+        (status = 200, description = "Got a Criminal case by its id", body = Criminal),
+        (status = 401, description = "Unauthorized to fetch a Criminal", body = Error, example = json!({"Err": Error::Unauthorized})),
+        (status = 404, description = "Criminal not found", body = Error, example = json!({"Err": Error::NothingFound})),
+        (status = 503, description = "The database is unavailable", body = Error, example = json!({"Err": Error::DatabaseUnavailable})),
+    ),
+    params(
+        ("id", description = "The id of the case"),
+    ),
+    security (
+        ("authorization" = []),
+    )
+)]
+#[get("/api/criminal/fetch/<id>")]
+pub async fn fetch_criminal(
+    _auth: Auth<CriminalReadOnly>,
+    db: Db,
+    id: i64,
+) -> JsonResult<Criminal> {
+    JsonResult(db.run(move |db| db::criminal::fetch(db, id)).await)
+}
+
+#[utoipa::path(
+    responses(
+        (status = 200, description = "Got all Kinds", body = Vec<String>),
+        (status = 401, description = "Unauthorized to get all Kinds", body = Error, example = json!({"Err": Error::Unauthorized})),
+        (status = 503, description = "The database is unavailable", body = Error, example = json!({"Err": Error::DatabaseUnavailable})),
+    ),
+    security (
+        ("authorization" = []),
     )
 )]
 #[get("/api/criminal/all_accounts")]
-pub async fn all_accounts(_auth: Auth<CriminalReadOnly>) -> Json<Result<Vec<String>>> {
-    let db = Database::open(Cow::from(Path::new("./schiller-db.db")))
-        .unwrap()
-        .0;
-    Json(db::criminal::all_accounts(&db))
+pub async fn all_accounts(_auth: Auth<CriminalReadOnly>, db: Db) -> JsonResult<Vec<String>> {
+    JsonResult(db.run(db::criminal::all_accounts).await)
 }
 
 #[utoipa::path(
     responses(
         (status = 200, description = "Got all Roles by Criminal", body = Vec<String>),
         (status = 401, description = "Unauthorized to get all Roles by Criminal", body = Error, example = json!({"Err": Error::Unauthorized})),
+        (status = 503, description = "The database is unavailable", body = Error, example = json!({"Err": Error::DatabaseUnavailable})),
     ),
     security (
         ("authorization" = []),
@@ -595,163 +1534,838 @@ pub async fn all_accounts(_auth: Auth<CriminalReadOnly>) -> Json<Result<Vec<Stri
 #[get("/api/criminal/all_roles?<name>")]
 pub async fn all_roles_criminal(
     _auth: Auth<CriminalReadOnly>,
-    name: Option<&str>,
-) -> Json<Result<Vec<String>>> {
-    let db = Database::open(Cow::from(Path::new("./schiller-db.db")))
-        .unwrap()
-        .0;
-    Json(db::criminal::all_roles(&db, name.unwrap_or("")))
+    db: Db,
+    name: Option<String>,
+) -> JsonResult<Vec<String>> {
+    JsonResult(
+        db.run(move |db| db::criminal::all_roles(db, name.as_deref().unwrap_or("")))
+            .await,
+    )
 }
 
 #[utoipa::path(
     responses(
-        (status = 200, description = "Searched all criminals", body = Vec<Criminal>),
+        (status = 200, description = "Searched all criminals", body = CriminalPage),
         (status = 401, description = "Unauthorized to search all criminals", body = Error, example = json!({"Err": Error::Unauthorized})),
+        (status = 503, description = "The database is unavailable", body = Error, example = json!({"Err": Error::DatabaseUnavailable})),
+    ),
+    params(
+        ("offset", description = "Position of the page in the results"),
+        ("limit", description = "Size of the page, at most 1000"),
+        ("status", description = "Only cases with this status, like `charged`"),
+        ("sort", description = "Comma separated columns, descending with a leading `-`"),
     ),
     security (
         ("authorization" = []),
     )
 )]
-#[get("/api/criminal/search?<name>&<account>&<kind>&<limit>")]
+#[get("/api/criminal/search?<name>&<account>&<kind>&<status>&<sort>&<offset>&<limit>")]
+#[allow(clippy::too_many_arguments)]
 pub async fn search_criminal(
     _auth: Auth<CriminalReadOnly>,
-    name: Option<&str>,
-    account: Option<&str>,
-    kind: Option<&str>,
+    db: Db,
+    name: Option<String>,
+    account: Option<String>,
+    kind: Option<String>,
+    status: Option<String>,
+    sort: Option<String>,
+    offset: Option<usize>,
     limit: Option<usize>,
-) -> Json<Result<Vec<Criminal>>> {
-    let db = Database::open(Cow::from(Path::new("./schiller-db.db")))
-        .unwrap()
-        .0;
-    Json(db::criminal::search(
-        &db,
-        CriminalSearch::new(
-            name.unwrap_or_default(),
-            account.unwrap_or("%"),
-            kind.unwrap_or("%"),
-        ),
-        limit.unwrap_or(200),
-    ))
+) -> JsonResult<Page<Criminal>> {
+    JsonResult(
+        db.run(move |db| {
+            let sort = Sort::parse(
+                sort.as_deref().unwrap_or_default(),
+                db::criminal::SORT_COLUMNS,
+            )?;
+            let status = status.as_deref().map(str::parse).transpose()?;
+            db::criminal::search(
+                db,
+                CriminalSearch {
+                    status,
+                    ..CriminalSearch::new(
+                        name.as_deref().unwrap_or_default(),
+                        account.as_deref().unwrap_or("%"),
+                        kind.as_deref().unwrap_or("%"),
+                    )
+                },
+                &sort,
+                Paging::new(offset, limit),
+            )
+        })
+        .await,
+    )
+}
+
+#[utoipa::path(
+    responses(
+        (status = 200, description = "Searched all texts of the criminals by relevance", body = CriminalHitPage),
+        (status = 401, description = "Unauthorized to search all criminals", body = Error, example = json!({"Err": Error::Unauthorized})),
+        (status = 503, description = "The database is unavailable", body = Error, example = json!({"Err": Error::DatabaseUnavailable})),
+    ),
+    params(
+        ("offset", description = "Position of the page in the results"),
+        ("limit", description = "Size of the page, at most 1000"),
+        ("sort", description = "Comma separated columns, descending with a leading `-`"),
+        ("text", description = "The words to search for"),
+    ),
+    security (
+        ("authorization" = []),
+    )
+)]
+#[get("/api/criminal/search_text?<text>&<sort>&<offset>&<limit>")]
+pub async fn search_text_criminal(
+    _auth: Auth<CriminalReadOnly>,
+    db: Db,
+    text: String,
+    sort: Option<String>,
+    offset: Option<usize>,
+    limit: Option<usize>,
+) -> JsonResult<Page<Hit<Criminal>>> {
+    JsonResult(
+        db.run(move |db| {
+            let sort = Sort::parse(
+                sort.as_deref().unwrap_or_default(),
+                db::criminal::SORT_COLUMNS,
+            )?;
+            db::criminal::search_text(db, &text, &sort, Paging::new(offset, limit))
+        })
+        .await,
+    )
 }
 
 #[utoipa::path(
     responses(
-        (status = 200, description = "Searched all Criminals by roles", body = Vec<Criminal>),
+        (status = 200, description = "Searched all Criminals by roles", body = CriminalPage),
         (status = 401, description = "Unauthorized to search all Criminals by roles", body = Error, example = json!({"Err": Error::Unauthorized})),
+        (status = 503, description = "The database is unavailable", body = Error, example = json!({"Err": Error::DatabaseUnavailable})),
+    ),
+    params(
+        ("offset", description = "Position of the page in the results"),
+        ("limit", description = "Size of the page, at most 1000"),
+        ("sort", description = "Comma separated columns, descending with a leading `-`"),
     ),
     security (
         ("authorization" = []),
     )
 )]
-#[get("/api/criminal/search_role?<name>&<role>&<limit>")]
+#[get("/api/criminal/search_role?<name>&<role>&<sort>&<offset>&<limit>")]
 pub async fn search_criminal_roles(
     _auth: Auth<CriminalReadOnly>,
-    name: Option<&str>,
-    role: Option<&str>,
+    db: Db,
+    name: Option<String>,
+    role: Option<String>,
+    sort: Option<String>,
+    offset: Option<usize>,
     limit: Option<usize>,
-) -> Json<Result<Vec<Criminal>>> {
-    let db = Database::open(Cow::from(Path::new("./schiller-db.db")))
-        .unwrap()
-        .0;
-    Json(db::criminal::search_role(
-        &db,
-        name.unwrap_or(""),
-        role.unwrap_or("%"),
-        limit.unwrap_or(9999),
-    ))
+) -> JsonResult<Page<Criminal>> {
+    JsonResult(
+        db.run(move |db| {
+            let sort = Sort::parse(
+                sort.as_deref().unwrap_or_default(),
+                db::criminal::SORT_COLUMNS,
+            )?;
+            db::criminal::search_role(
+                db,
+                name.as_deref().unwrap_or(""),
+                role.as_deref().unwrap_or("%"),
+                &sort,
+                Paging::new(offset, limit),
+            )
+        })
+        .await,
+    )
 }
 
 #[utoipa::path(
     request_body = Criminal,
     responses(
-        (status = 200, description = "Add a criminal sended successfully"),
+        (status = 200, description = "Added the criminal case, with its id and case number", body = Criminal),
+        (status = 400, description = "The account does not exist", body = Error, example = json!({"Err": Error::UnknownAccount})),
         (status = 401, description = "Unauthorized to add a criminals", body = Error, example = json!({"Err": Error::Unauthorized})),
+        (status = 409, description = "Criminal already exists", body = Error, example = json!({"Err": Error::AlreadyExists})),
         (status = 422, description = "The Json is parsed in a wrong format", body = Error, example = json!({"Err": Error::UnprocessableEntity})),
+        (status = 503, description = "The database is unavailable", body = Error, example = json!({"Err": Error::DatabaseUnavailable})),
     ),
     security (
         ("authorization" = []),
     )
 )]
 #[post("/api/criminal", format = "json", data = "<criminal>")]
-pub async fn add_criminal(auth: Auth<CriminalWrite>, criminal: Json<Criminal>) -> Json<Result<()>> {
+pub async fn add_criminal(
+    auth: Auth<CriminalWrite>,
+    db: Db,
+    criminal: Json<Criminal>,
+) -> JsonResult<Criminal> {
     warn!("POST /criminal with data {criminal:?}: {}", auth.user);
-    let db = Database::open(Cow::from(Path::new("./schiller-db.db")))
-        .unwrap()
-        .0;
-    Json(db::criminal::add(&db, &criminal))
+    JsonResult(
+        db.run(move |db| db::criminal::add(db, &auth.user, &criminal))
+            .await,
+    )
 }
 
 #[utoipa::path(
     request_body = Criminal,
     responses(
-        (status = 200, description = "Update a workless sended successfully"),
-        (status = 401, description = "Unauthorized to update a workless", body = Error, example = json!({"Err": Error::Unauthorized})),
+        (status = 200, description = "Update a criminal case sended successfully, its id and case number are kept"),
+        (status = 400, description = "The criminal case is invalid", body = Error, example = json!({"Err": Error::InvalidCriminal(vec![FieldError::new("kind", "must not be empty")])})),
+        (status = 401, description = "Unauthorized to update a criminal case", body = Error, example = json!({"Err": Error::Unauthorized})),
+        (status = 404, description = "Criminal not found", body = Error, example = json!({"Err": Error::NothingFound})),
         (status = 422, description = "The Json is parsed in a wrong format", body = Error, example = json!({"Err": Error::UnprocessableEntity})),
+        (status = 503, description = "The database is unavailable", body = Error, example = json!({"Err": Error::DatabaseUnavailable})),
     ),
     params(
-        ("previous_account", description = "The unique user account"),
-        ("previous_kind", description = "The kind of the crime"),
+        ("id", description = "The id of the case"),
     ),
     security (
         ("authorization" = []),
     )
 )]
-#[put(
-    "/api/criminal/<previous_account>/<previous_kind>",
-    format = "json",
-    data = "<criminal>"
-)]
+#[put("/api/criminal/<id>", format = "json", data = "<criminal>")]
 pub async fn update_criminal(
     auth: Auth<CriminalWrite>,
-    previous_account: &str,
-    previous_kind: &str,
+    db: Db,
+    id: i64,
     criminal: Json<Criminal>,
-) -> Json<Result<()>> {
+) -> JsonResult<()> {
+    warn!("PUT /criminal/{id} with data {criminal:?}: {}", auth.user);
+    JsonResult(
+        db.run(move |db| db::criminal::update(db, &auth.user, id, &criminal))
+            .await,
+    )
+}
+
+#[utoipa::path(
+    request_body = CaseStatus,
+    responses(
+        (status = 200, description = "Changed the status of the criminal case", body = Criminal),
+        (status = 401, description = "Unauthorized to update a criminal case", body = Error, example = json!({"Err": Error::Unauthorized})),
+        (status = 404, description = "Criminal not found", body = Error, example = json!({"Err": Error::NothingFound})),
+        (status = 409, description = "The case can not change to this status", body = Error, example = json!({"Err": Error::InvalidTransition(CaseStatus::Reported, CaseStatus::Verdict)})),
+        (status = 422, description = "The Json is parsed in a wrong format", body = Error, example = json!({"Err": Error::UnprocessableEntity})),
+        (status = 503, description = "The database is unavailable", body = Error, example = json!({"Err": Error::DatabaseUnavailable})),
+    ),
+    params(
+        ("id", description = "The id of the case"),
+    ),
+    security (
+        ("authorization" = []),
+    )
+)]
+#[put("/api/criminal/<id>/status", format = "json", data = "<status>")]
+pub async fn transition_criminal(
+    auth: Auth<CriminalWrite>,
+    db: Db,
+    id: i64,
+    status: Json<CaseStatus>,
+) -> JsonResult<Criminal> {
     warn!(
-        "PUT /criminal/{previous_account}/{previous_kind} with data {criminal:?}: {}",
+        "PUT /criminal/{id}/status with data {status:?}: {}",
         auth.user
     );
-    let db = Database::open(Cow::from(Path::new("./schiller-db.db")))
-        .unwrap()
-        .0;
-    Json(db::criminal::update(
-        &db,
-        previous_account,
-        previous_kind,
-        &criminal,
-    ))
+    JsonResult(
+        db.run(move |db| db::criminal::transition(db, &auth.user, id, *status))
+            .await,
+    )
+}
+
+#[utoipa::path(
+    responses(
+        (status = 200, description = "Got the status changes of a Criminal, the oldest first", body = Vec<Transition>),
+        (status = 401, description = "Unauthorized to fetch a Criminal", body = Error, example = json!({"Err": Error::Unauthorized})),
+        (status = 404, description = "Criminal not found", body = Error, example = json!({"Err": Error::NothingFound})),
+        (status = 503, description = "The database is unavailable", body = Error, example = json!({"Err": Error::DatabaseUnavailable})),
+    ),
+    params(
+        ("id", description = "The id of the case"),
+    ),
+    security(
+        ("authorization" = []),
+    )
+)]
+#[get("/api/criminal/transitions/<id>")]
+pub async fn transitions_criminal(
+    _auth: Auth<CriminalReadOnly>,
+    db: Db,
+    id: i64,
+) -> JsonResult<Vec<Transition>> {
+    JsonResult(db.run(move |db| db::criminal::transitions(db, id)).await)
 }
 
 #[utoipa::path(
     responses(
         (status = 200, description = "Criminal delete sended successfully"),
         (status = 401, description = "Unauthorized to delete Criminal", body = Error, example = json!({"Err": Error::Unauthorized})),
+        (status = 404, description = "Criminal not found", body = Error, example = json!({"Err": Error::NothingFound})),
+        (status = 503, description = "The database is unavailable", body = Error, example = json!({"Err": Error::DatabaseUnavailable})),
+    ),
+    params(
+        ("id", description = "The id of the case"),
+    ),
+    security(
+        ("authorization" = []),
+    )
+)]
+#[delete("/api/criminal/<id>")]
+pub async fn delete_criminal(auth: Auth<CriminalWrite>, db: Db, id: i64) -> JsonResult<()> {
+    warn!("DELETE /criminal/{id}: {}", auth.user);
+    JsonResult(
+        db.run(move |db| db::criminal::delete(db, &auth.user, id))
+            .await,
+    )
+}
+
+#[utoipa::path(
+    responses(
+        (status = 200, description = "Got the Criminals in the trash bin, the most recently deleted first", body = Vec<TrashedCriminal>),
+        (status = 401, description = "Unauthorized to view the deleted Criminals", body = Error, example = json!({"Err": Error::Unauthorized})),
+        (status = 503, description = "The database is unavailable", body = Error, example = json!({"Err": Error::DatabaseUnavailable})),
+    ),
+    params(
+        ("limit", description = "Number of results, at most 1000"),
+    ),
+    security(
+        ("authorization" = []),
+    )
+)]
+#[get("/api/criminal/trash?<limit>")]
+pub async fn trash_criminal(
+    _auth: Auth<CriminalWrite>,
+    db: Db,
+    limit: Option<usize>,
+) -> JsonResult<Vec<Trashed<Criminal>>> {
+    JsonResult(
+        db.run(move |db| db::criminal::trash(db, Paging::new(None, limit).limit))
+            .await,
+    )
+}
+
+#[utoipa::path(
+    responses(
+        (status = 200, description = "Criminal restored from the trash bin"),
+        (status = 401, description = "Unauthorized to restore Criminals", body = Error, example = json!({"Err": Error::Unauthorized})),
+        (status = 404, description = "Criminal not in the trash bin", body = Error, example = json!({"Err": Error::NothingFound})),
+        (status = 503, description = "The database is unavailable", body = Error, example = json!({"Err": Error::DatabaseUnavailable})),
+    ),
+    params(
+        ("id", description = "The id of the case"),
+    ),
+    security(
+        ("authorization" = []),
+    )
+)]
+#[post("/api/criminal/<id>/restore")]
+pub async fn restore_criminal(auth: Auth<CriminalWrite>, db: Db, id: i64) -> JsonResult<()> {
+    warn!("POST /criminal/{id}/restore: {}", auth.user);
+    JsonResult(
+        db.run(move |db| db::criminal::restore(db, &auth.user, id))
+            .await,
+    )
+}
+
+#[utoipa::path(
+    responses(
+        (status = 200, description = "Criminal permanently deleted from the trash bin"),
+        (status = 401, description = "Unauthorized to purge Criminals", body = Error, example = json!({"Err": Error::Unauthorized})),
+        (status = 404, description = "Criminal not in the trash bin", body = Error, example = json!({"Err": Error::NothingFound})),
+        (status = 503, description = "The database is unavailable", body = Error, example = json!({"Err": Error::DatabaseUnavailable})),
+    ),
+    params(
+        ("id", description = "The id of the case"),
+    ),
+    security(
+        ("authorization" = []),
+    )
+)]
+#[delete("/api/criminal/<id>/purge")]
+pub async fn purge_criminal(auth: Auth<CriminalWrite>, db: Db, id: i64) -> JsonResult<()> {
+    warn!("DELETE /criminal/{id}/purge: {}", auth.user);
+    JsonResult(
+        db.run(move |db| db::criminal::purge(db, &auth.user, id))
+            .await,
+    )
+}
+
+#[utoipa::path(
+    responses(
+        (status = 200, description = "Got all revisions of a Criminal, the oldest first", body = Vec<CriminalVersion>),
+        (status = 401, description = "Unauthorized to view the history of Criminals", body = Error, example = json!({"Err": Error::Unauthorized})),
+        (status = 503, description = "The database is unavailable", body = Error, example = json!({"Err": Error::DatabaseUnavailable})),
+    ),
+    params(
+        ("id", description = "The id of the case"),
+    ),
+    security(
+        ("authorization" = []),
+    )
+)]
+#[get("/api/criminal/history/<id>")]
+pub async fn history_criminal(
+    _auth: Auth<CriminalReadOnly>,
+    db: Db,
+    id: i64,
+) -> JsonResult<Vec<Version<Criminal>>> {
+    JsonResult(db.run(move |db| db::criminal::history(db, id)).await)
+}
+
+#[utoipa::path(
+    responses(
+        (status = 200, description = "Got the Criminals as they were at the given time", body = Vec<Criminal>),
+        (status = 400, description = "The timestamp is invalid", body = Error, example = json!({"Err": Error::InvalidDate})),
+        (status = 401, description = "Unauthorized to view the history of Criminals", body = Error, example = json!({"Err": Error::Unauthorized})),
+        (status = 503, description = "The database is unavailable", body = Error, example = json!({"Err": Error::DatabaseUnavailable})),
+    ),
+    params(
+        ("time", description = "RFC 3339 timestamp"),
+        ("limit", description = "Number of results, at most 1000"),
+    ),
+    security(
+        ("authorization" = []),
+    )
+)]
+#[get("/api/criminal/as_of?<time>&<limit>")]
+pub async fn as_of_criminal(
+    _auth: Auth<CriminalReadOnly>,
+    db: Db,
+    time: String,
+    limit: Option<usize>,
+) -> JsonResult<Vec<Criminal>> {
+    let time = match timestamp(&time) {
+        Ok(time) => time,
+        Err(e) => return JsonResult(Err(e)),
+    };
+    JsonResult(
+        db.run(move |db| db::criminal::as_of(db, time, Paging::new(None, limit).limit))
+            .await,
+    )
+}
+
+#[utoipa::path(
+    responses(
+        (status = 200, description = "Got the certificate of good conduct of the user as PDF", content_type = "application/pdf"),
+        (status = 401, description = "Unauthorized to issue certificates", body = Error, example = json!({"Err": Error::Unauthorized})),
+        (status = 404, description = "User not found", body = Error, example = json!({"Err": Error::NothingFound})),
+        (status = 503, description = "The database is unavailable", body = Error, example = json!({"Err": Error::DatabaseUnavailable})),
     ),
     params(
         ("account", description = "The unique user account"),
-        ("kind", description = "The kind of the crime"),
     ),
     security(
         ("authorization" = []),
     )
 )]
-#[delete("/api/criminal/<account>/<kind>")]
-pub async fn delete_criminal(
+#[get("/api/criminal/certificate/<account>")]
+pub async fn certificate(
+    auth: Auth<CriminalReadOnly>,
+    db: Db,
+    account: String,
+) -> std::result::Result<Download, JsonResult<()>> {
+    let certificate = db
+        .run(move |db| Certificate::new(db, &account, &auth.user))
+        .await;
+    match certificate {
+        Ok(certificate) => Ok(Download {
+            name: format!(
+                "fuehrungszeugnis-{}-{}.pdf",
+                certificate.user.account,
+                certificate.issued_at.format("%Y%m%d")
+            ),
+            content_type: ContentType::PDF,
+            data: certificate.pdf(),
+        }),
+        Err(e) => Err(JsonResult(Err(e))),
+    }
+}
+
+#[utoipa::path(
+    responses(
+        (status = 200, description = "Exported the Criminals matching the search as CSV", content_type = "text/csv"),
+        (status = 401, description = "Unauthorized to export Criminals", body = Error, example = json!({"Err": Error::Unauthorized})),
+        (status = 503, description = "The database is unavailable", body = Error, example = json!({"Err": Error::DatabaseUnavailable})),
+    ),
+    params(
+        ("name", description = "Like the search"),
+        ("account", description = "Like the search"),
+        ("kind", description = "Like the search"),
+    ),
+    security(
+        ("authorization" = []),
+    )
+)]
+#[get("/api/criminal/csv?<name>&<account>&<kind>")]
+pub async fn export_criminals(
+    _auth: Auth<CriminalReadOnly>,
+    db: Db,
+    name: Option<String>,
+    account: Option<String>,
+    kind: Option<String>,
+) -> std::result::Result<Download, JsonResult<()>> {
+    let criminals = db
+        .run(move |db| {
+            db::criminal::search(
+                db,
+                CriminalSearch::new(
+                    name.as_deref().unwrap_or_default(),
+                    account.as_deref().unwrap_or("%"),
+                    kind.as_deref().unwrap_or("%"),
+                ),
+                &Sort::default(),
+                Paging::all(),
+            )
+        })
+        .await;
+    csv_download("criminals", criminals).map_err(|e| JsonResult(Err(e)))
+}
+
+#[cfg(feature = "xlsx")]
+#[utoipa::path(
+    responses(
+        (status = 200, description = "Exported the Criminals matching the search as Excel workbook", content_type = "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet"),
+        (status = 401, description = "Unauthorized to export Criminals", body = Error, example = json!({"Err": Error::Unauthorized})),
+        (status = 413, description = "Too many Criminals for a worksheet", body = Error, example = json!({"Err": Error::ExceededLimit})),
+        (status = 503, description = "The database is unavailable", body = Error, example = json!({"Err": Error::DatabaseUnavailable})),
+    ),
+    params(
+        ("name", description = "Like the search"),
+        ("account", description = "Like the search"),
+        ("kind", description = "Like the search"),
+    ),
+    security(
+        ("authorization" = []),
+    )
+)]
+#[get("/api/criminal/xlsx?<name>&<account>&<kind>")]
+pub async fn export_criminals_xlsx(
+    _auth: Auth<CriminalReadOnly>,
+    db: Db,
+    name: Option<String>,
+    account: Option<String>,
+    kind: Option<String>,
+) -> std::result::Result<Download, JsonResult<()>> {
+    let book = db
+        .run(move |db| {
+            let mut book = db::xlsx::Workbook::default();
+            book.add(
+                &db::criminal::search(
+                    db,
+                    CriminalSearch::new(
+                        name.as_deref().unwrap_or_default(),
+                        account.as_deref().unwrap_or("%"),
+                        kind.as_deref().unwrap_or("%"),
+                    ),
+                    &Sort::default(),
+                    Paging::all(),
+                )?
+                .items,
+            )?;
+            Ok(book)
+        })
+        .await;
+    xlsx_download("criminals", book).map_err(|e| JsonResult(Err(e)))
+}
+
+#[cfg(feature = "xlsx")]
+#[utoipa::path(
+    responses(
+        (status = 200, description = "Exported every table the login may read as worksheet of an Excel workbook", content_type = "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet"),
+        (status = 401, description = "Unauthorized", body = Error, example = json!({"Err": Error::Unauthorized})),
+        (status = 413, description = "A table is too large for a worksheet", body = Error, example = json!({"Err": Error::ExceededLimit})),
+        (status = 503, description = "The database is unavailable", body = Error, example = json!({"Err": Error::DatabaseUnavailable})),
+    ),
+    security(
+        ("authorization" = []),
+    )
+)]
+#[get("/api/xlsx")]
+pub async fn export_xlsx(
+    auth: Auth<Authenticated>,
+    db: Db,
+) -> std::result::Result<Download, JsonResult<()>> {
+    let book = db
+        .run(move |db| {
+            let permissions = db::login::fetch_permission(db, &auth.user)?;
+            let mut book = db::xlsx::Workbook::default();
+            if permissions.access_user != Permission::None {
+                book.add(
+                    &db::user::search(
+                        db,
+                        UserSearch::new("", "%"),
+                        &Sort::default(),
+                        Paging::all(),
+                    )?
+                    .items,
+                )?;
+            }
+            if permissions.access_workless != Permission::None {
+                book.add(
+                    &db::workless::search(
+                        db,
+                        WorklessSearch::new("", "%", "%"),
+                        &Sort::default(),
+                        Paging::all(),
+                    )?
+                    .items,
+                )?;
+            }
+            if permissions.access_criminal != Permission::None {
+                book.add(
+                    &db::criminal::search(
+                        db,
+                        CriminalSearch::new("", "%", "%"),
+                        &Sort::default(),
+                        Paging::all(),
+                    )?
+                    .items,
+                )?;
+            }
+            Ok(book)
+        })
+        .await;
+    xlsx_download("schiller-db", book).map_err(|e| JsonResult(Err(e)))
+}
+
+#[utoipa::path(
+    request_body(content = String, description = "CSV table with a header row naming the Criminal fields", content_type = "text/csv"),
+    responses(
+        (status = 200, description = "Imported all rows, or none if the report lists rejected rows", body = ImportReport),
+        (status = 400, description = "The CSV is malformed", body = Error, example = json!({"Err": Error::InvalidFormat})),
+        (status = 401, description = "Unauthorized to import Criminals", body = Error, example = json!({"Err": Error::Unauthorized})),
+        (status = 413, description = "The import is too large", body = Error, example = json!({"Err": Error::ExceededLimit})),
+        (status = 503, description = "The database is unavailable", body = Error, example = json!({"Err": Error::DatabaseUnavailable})),
+    ),
+    params(
+        ("dry_run", description = "Only report the changes without applying them"),
+    ),
+    security(
+        ("authorization" = []),
+    )
+)]
+#[post("/api/criminal/csv?<dry_run>", data = "<data>")]
+pub async fn import_criminals_csv(
+    auth: Auth<CriminalWrite>,
+    db: Db,
+    dry_run: Option<bool>,
+    data: Data<'_>,
+) -> JsonResult<ImportReport> {
+    let dry_run = dry_run.unwrap_or(false);
+    warn!("POST /criminal/csv?dry_run={dry_run}: {}", auth.user);
+    let data = match upload(data).await {
+        Ok(data) => data,
+        Err(e) => return JsonResult(Err(e)),
+    };
+    JsonResult(
+        db.run(move |db| db::import::criminals_csv(db, &auth.user, &data[..], dry_run))
+            .await,
+    )
+}
+
+#[utoipa::path(
+    responses(
+        (status = 200, description = "Got the Hearing", body = Hearing),
+        (status = 401, description = "Unauthorized to fetch a Hearing", body = Error, example = json!({"Err": Error::Unauthorized})),
+        (status = 404, description = "Hearing not found", body = Error, example = json!({"Err": Error::NothingFound})),
+        (status = 503, description = "The database is unavailable", body = Error, example = json!({"Err": Error::DatabaseUnavailable})),
+    ),
+    params(
+        ("id", description = "The id of the hearing"),
+    ),
+    security (
+        ("authorization" = []),
+    )
+)]
+#[get("/api/hearing/fetch/<id>")]
+pub async fn fetch_hearing(_auth: Auth<CriminalReadOnly>, db: Db, id: i64) -> JsonResult<Hearing> {
+    JsonResult(db.run(move |db| db::hearing::fetch(db, id)).await)
+}
+
+#[utoipa::path(
+    responses(
+        (status = 200, description = "Got the Hearings of a Criminal, the earliest first", body = Vec<Hearing>),
+        (status = 401, description = "Unauthorized to fetch Hearings", body = Error, example = json!({"Err": Error::Unauthorized})),
+        (status = 404, description = "Criminal not found", body = Error, example = json!({"Err": Error::NothingFound})),
+        (status = 503, description = "The database is unavailable", body = Error, example = json!({"Err": Error::DatabaseUnavailable})),
+    ),
+    params(
+        ("id", description = "The id of the case"),
+    ),
+    security (
+        ("authorization" = []),
+    )
+)]
+#[get("/api/hearing/case/<id>")]
+pub async fn hearings_of_case(
+    _auth: Auth<CriminalReadOnly>,
+    db: Db,
+    id: i64,
+) -> JsonResult<Vec<Hearing>> {
+    JsonResult(db.run(move |db| db::hearing::of_case(db, id)).await)
+}
+
+#[utoipa::path(
+    responses(
+        (status = 200, description = "Got the Hearings of the day, ordered by room and time", body = Vec<DocketEntry>),
+        (status = 400, description = "The date is invalid", body = Error, example = json!({"Err": Error::InvalidDate})),
+        (status = 401, description = "Unauthorized to view the docket", body = Error, example = json!({"Err": Error::Unauthorized})),
+        (status = 503, description = "The database is unavailable", body = Error, example = json!({"Err": Error::DatabaseUnavailable})),
+    ),
+    params(
+        ("day", description = "The day, like `2026-07-01`"),
+        ("room", description = "Only the hearings in this room"),
+    ),
+    security (
+        ("authorization" = []),
+    )
+)]
+#[get("/api/hearing/docket?<day>&<room>")]
+pub async fn docket(
+    _auth: Auth<CriminalReadOnly>,
+    db: Db,
+    day: String,
+    room: Option<String>,
+) -> JsonResult<Vec<DocketEntry>> {
+    JsonResult(
+        db.run(move |db| {
+            let day = date(&day)?;
+            db::hearing::docket(db, day, day, room.as_deref())
+        })
+        .await,
+    )
+}
+
+#[utoipa::path(
+    responses(
+        (status = 200, description = "Exported the Hearings as iCalendar", content_type = "text/calendar"),
+        (status = 400, description = "The date is invalid", body = Error, example = json!({"Err": Error::InvalidDate})),
+        (status = 401, description = "Unauthorized to export Hearings", body = Error, example = json!({"Err": Error::Unauthorized})),
+        (status = 503, description = "The database is unavailable", body = Error, example = json!({"Err": Error::DatabaseUnavailable})),
+    ),
+    params(
+        ("from", description = "First day, like `2026-07-01`, by default all hearings"),
+        ("to", description = "Last day, by default the first one"),
+        ("room", description = "Only the hearings in this room"),
+    ),
+    security (
+        ("authorization" = []),
+    )
+)]
+#[get("/api/hearing/ics?<from>&<to>&<room>")]
+pub async fn export_hearings_ics(
+    _auth: Auth<CriminalReadOnly>,
+    db: Db,
+    from: Option<String>,
+    to: Option<String>,
+    room: Option<String>,
+) -> std::result::Result<Download, JsonResult<()>> {
+    let data = db
+        .run(move |db| {
+            let from = from.as_deref().map(date).transpose()?;
+            let to = to.as_deref().map(date).transpose()?;
+            let (from, to) = match (from, to) {
+                (Some(from), to) => (from, to.unwrap_or(from)),
+                (None, to) => (MIN_DATE, to.unwrap_or(MAX_DATE)),
+            };
+            db::hearing::ics(&db::hearing::docket(db, from, to, room.as_deref())?)
+        })
+        .await;
+    match data {
+        Ok(data) => Ok(Download {
+            name: format!("hearings-{}.ics", Local::now().format("%Y%m%d-%H%M%S")),
+            content_type: ContentType::Calendar,
+            data,
+        }),
+        Err(e) => Err(JsonResult(Err(e))),
+    }
+}
+
+#[utoipa::path(
+    request_body = Hearing,
+    responses(
+        (status = 200, description = "Scheduled the Hearing, with its id", body = Hearing),
+        (status = 400, description = "The Hearing is invalid", body = Error, example = json!({"Err": Error::InvalidHearing(vec![FieldError::new("ends_at", "must be after the start")])})),
+        (status = 401, description = "Unauthorized to schedule Hearings", body = Error, example = json!({"Err": Error::Unauthorized})),
+        (status = 404, description = "Criminal not found", body = Error, example = json!({"Err": Error::NothingFound})),
+        (status = 409, description = "The room or a person is already booked", body = Error, example = json!({"Err": Error::DoubleBooked(vec![FieldError::new("room", "already booked by the hearing 1 of 2026-0001 from 2026-07-01 09:00 to 2026-07-01 10:00")])})),
+        (status = 422, description = "The Json is parsed in a wrong format", body = Error, example = json!({"Err": Error::UnprocessableEntity})),
+        (status = 503, description = "The database is unavailable", body = Error, example = json!({"Err": Error::DatabaseUnavailable})),
+    ),
+    security (
+        ("authorization" = []),
+    )
+)]
+#[post("/api/hearing", format = "json", data = "<hearing>")]
+pub async fn add_hearing(
+    auth: Auth<CriminalWrite>,
+    db: Db,
+    hearing: Json<Hearing>,
+) -> JsonResult<Hearing> {
+    warn!("POST /hearing with data {hearing:?}: {}", auth.user);
+    JsonResult(
+        db.run(move |db| db::hearing::add(db, &auth.user, &hearing))
+            .await,
+    )
+}
+
+#[utoipa::path(
+    request_body = Hearing,
+    responses(
+        (status = 200, description = "Rescheduled the Hearing, it stays with its case"),
+        (status = 400, description = "The Hearing is invalid", body = Error, example = json!({"Err": Error::InvalidHearing(vec![FieldError::new("room", "must not be empty")])})),
+        (status = 401, description = "Unauthorized to reschedule Hearings", body = Error, example = json!({"Err": Error::Unauthorized})),
+        (status = 404, description = "Hearing not found", body = Error, example = json!({"Err": Error::NothingFound})),
+        (status = 409, description = "The room or a person is already booked", body = Error, example = json!({"Err": Error::DoubleBooked(vec![FieldError::new("judge", "already booked by the hearing 1 of 2026-0001 from 2026-07-01 09:00 to 2026-07-01 10:00")])})),
+        (status = 422, description = "The Json is parsed in a wrong format", body = Error, example = json!({"Err": Error::UnprocessableEntity})),
+        (status = 503, description = "The database is unavailable", body = Error, example = json!({"Err": Error::DatabaseUnavailable})),
+    ),
+    params(
+        ("id", description = "The id of the hearing"),
+    ),
+    security (
+        ("authorization" = []),
+    )
+)]
+#[put("/api/hearing/<id>", format = "json", data = "<hearing>")]
+pub async fn update_hearing(
     auth: Auth<CriminalWrite>,
-    account: &str,
-    kind: &str,
-) -> Json<Result<()>> {
-    warn!("DELETE /criminal/{account}/{kind}: {}", auth.user);
-    let db = Database::open(Cow::from(Path::new("./schiller-db.db")))
-        .unwrap()
-        .0;
-    Json(db::criminal::delete(&db, account, kind))
+    db: Db,
+    id: i64,
+    hearing: Json<Hearing>,
+) -> JsonResult<()> {
+    warn!("PUT /hearing/{id} with data {hearing:?}: {}", auth.user);
+    JsonResult(
+        db.run(move |db| db::hearing::update(db, &auth.user, id, &hearing))
+            .await,
+    )
+}
+
+#[utoipa::path(
+    responses(
+        (status = 200, description = "Cancelled the Hearing"),
+        (status = 401, description = "Unauthorized to cancel Hearings", body = Error, example = json!({"Err": Error::Unauthorized})),
+        (status = 404, description = "Hearing not found", body = Error, example = json!({"Err": Error::NothingFound})),
+        (status = 503, description = "The database is unavailable", body = Error, example = json!({"Err": Error::DatabaseUnavailable})),
+    ),
+    params(
+        ("id", description = "The id of the hearing"),
+    ),
+    security(
+        ("authorization" = []),
+    )
+)]
+#[delete("/api/hearing/<id>")]
+pub async fn delete_hearing(auth: Auth<CriminalWrite>, db: Db, id: i64) -> JsonResult<()> {
+    warn!("DELETE /hearing/{id}: {}", auth.user);
+    JsonResult(
+        db.run(move |db| db::hearing::delete(db, &auth.user, id))
+            .await,
+    )
 }
 
 #[utoipa::path(
     responses(
         (status = 200, description = "Got a Permissions by a specific user", body = Permissions),
         (status = 401, description = "Unauthorized to fetch Permissions", body = Error, example = json!({"Err": Error::Unauthorized})),
+        (status = 404, description = "Login not found", body = Error, example = json!({"Err": Error::NothingFound})),
+        (status = 503, description = "The database is unavailable", body = Error, example = json!({"Err": Error::DatabaseUnavailable})),
     ),
     params(
         ("user", description = "The unique user"),
@@ -761,72 +2375,88 @@ pub async fn delete_criminal(
     )
 )]
 #[get("/api/login/fetch/<user>")]
-pub async fn fetch_permission(_auth: Auth<UserReadOnly>, user: &str) -> Json<Result<Permissions>> {
-    let db = Database::open(Cow::from(Path::new("./schiller-db.db")))
-        .unwrap()
-        .0;
-    Json(db::login::fetch_permission(&db, user))
+pub async fn fetch_permission(
+    _auth: Auth<UserReadOnly>,
+    db: Db,
+    user: String,
+) -> JsonResult<Permissions> {
+    JsonResult(
+        db.run(move |db| db::login::fetch_permission(db, &user))
+            .await,
+    )
 }
 
 #[utoipa::path(
     request_body = NewLogin,
     responses(
         (status = 200, description = "Add a Login sended successfully"),
+        (status = 400, description = "The Login is invalid", body = Error, example = json!({"Err": Error::InvalidLogin(vec![FieldError::new("user", "must not contain ':'")])})),
         (status = 401, description = "Unauthorized to add a Logins", body = Error, example = json!({"Err": Error::Unauthorized})),
+        (status = 409, description = "Login already exists", body = Error, example = json!({"Err": Error::AlreadyExists})),
         (status = 422, description = "The Json is parsed in a wrong format", body = Error, example = json!({"Err": Error::UnprocessableEntity})),
+        (status = 503, description = "The database is unavailable", body = Error, example = json!({"Err": Error::DatabaseUnavailable})),
     ),
     security (
         ("authorization" = []),
     )
 )]
 #[post("/api/login", format = "json", data = "<login>")]
-pub async fn add_login(auth: Auth<UserWrite>, login: Json<NewLogin>) -> Json<Result<()>> {
+pub async fn add_login(auth: Auth<UserWrite>, db: Db, login: Json<NewLogin>) -> JsonResult<()> {
     warn!("POST /login with data {login:?}: {}", auth.user);
-    let db = Database::open(Cow::from(Path::new("./schiller-db.db")))
-        .unwrap()
-        .0;
-    Json(db::login::add(&db, login.into_inner()))
+    JsonResult(
+        db.run(move |db| db::login::add(db, &auth.user, login.into_inner()))
+            .await,
+    )
 }
 
 #[utoipa::path(
     request_body = NewLogin,
     responses(
         (status = 200, description = "Update a login sended successfully"),
+        (status = 400, description = "The Login is invalid", body = Error, example = json!({"Err": Error::InvalidLogin(vec![FieldError::new("user", "unknown user")])})),
         (status = 401, description = "Unauthorized to update a login", body = Error, example = json!({"Err": Error::Unauthorized})),
         (status = 422, description = "The Json is parsed in a wrong format", body = Error, example = json!({"Err": Error::UnprocessableEntity})),
+        (status = 503, description = "The database is unavailable", body = Error, example = json!({"Err": Error::DatabaseUnavailable})),
     ),
     security (
         ("authorization" = []),
     )
 )]
 #[put("/api/login", format = "json", data = "<login>")]
-pub async fn update_login(auth: Auth<UserReadOnly>, login: Json<NewLogin>) -> Json<Result<()>> {
+pub async fn update_login(
+    auth: Auth<UserReadOnly>,
+    db: Db,
+    login: Json<NewLogin>,
+) -> JsonResult<()> {
     warn!("PUT /login with data {login:?}: {}", auth.user);
-    let db = Database::open(Cow::from(Path::new("./schiller-db.db")))
-        .unwrap()
-        .0;
 
-    if db::login::fetch(&db, &login.user).is_err() {
-        warn!("invalid user of login json");
-        return Json(Err(Error::InvalidLogin));
-    }
+    JsonResult(
+        db.run(move |db| {
+            if db::login::fetch(db, &login.user).is_err() {
+                warn!("invalid user of login json");
+                return Err(Error::InvalidLogin(vec![FieldError::new(
+                    "user",
+                    "unknown user",
+                )]));
+            }
 
-    if auth.user != login.user {
-        warn!("missing permissions to change user password: {}", auth.user);
-        return Json(Err(Error::Unauthorized));
-    }
+            if auth.user != login.user {
+                warn!("missing permissions to change user password: {}", auth.user);
+                return Err(Error::Unauthorized);
+            }
 
-    Json(db::login::update(
-        &db,
-        &login.clone().into_inner().user,
-        &login.into_inner().password,
-    ))
+            db::login::update(db, &auth.user, &login.user, &login.password)
+        })
+        .await,
+    )
 }
 
 #[utoipa::path(
     responses(
         (status = 200, description = "Login delete sended successfully"),
+        (status = 400, description = "The admin can not be deleted", body = Error, example = json!({"Err": Error::InvalidUser(vec![FieldError::new("user", "the admin can not be deleted")])})),
         (status = 401, description = "Unauthorized to delete Login", body = Error, example = json!({"Err": Error::Unauthorized})),
+        (status = 503, description = "The database is unavailable", body = Error, example = json!({"Err": Error::DatabaseUnavailable})),
     ),
     params(
         ("user", description = "The unique user"),
@@ -836,46 +2466,306 @@ pub async fn update_login(auth: Auth<UserReadOnly>, login: Json<NewLogin>) -> Js
     )
 )]
 #[delete("/api/login/<user>")]
-pub async fn delete_login(auth: Auth<UserWrite>, user: &str) -> Json<Result<()>> {
+pub async fn delete_login(auth: Auth<UserWrite>, db: Db, user: String) -> JsonResult<()> {
     warn!("DELETE /login/{user}: {}", auth.user);
-    let user = user.trim();
+    let user = user.trim().to_string();
 
-    if user == env::var("SCHILLER_DB_USER").unwrap() {
+    if env::var("SCHILLER_DB_USER").is_ok_and(|admin| admin == user) {
         warn!("unable to delete admin '{user}'");
-        return Json(Err(Error::InvalidUser));
+        return JsonResult(Err(Error::InvalidUser(vec![FieldError::new(
+            "user",
+            "the admin can not be deleted",
+        )])));
     }
 
-    let db = Database::open(Cow::from(Path::new("./schiller-db.db")))
-        .unwrap()
-        .0;
-    Json(db::login::delete(&db, user))
+    JsonResult(
+        db.run(move |db| db::login::delete(db, &auth.user, &user))
+            .await,
+    )
 }
 
 #[utoipa::path(
     responses(
         (status = 200, description = "All Logins delete sended successfully"),
         (status = 401, description = "Unauthorized to delete All Logins", body = Error, example = json!({"Err": Error::Unauthorized})),
+        (status = 503, description = "The database is unavailable", body = Error, example = json!({"Err": Error::DatabaseUnavailable})),
     ),
     security(
         ("authorization" = []),
     )
 )]
 #[delete("/api/all_logins")]
-pub async fn delete_all_logins(auth: Auth<UserWrite>) -> Json<Result<()>> {
+pub async fn delete_all_logins(auth: Auth<UserWrite>, db: Db) -> JsonResult<()> {
     warn!("DELETE /all_logins: {}", auth.user);
 
-    let db = Database::open(Cow::from(Path::new("./schiller-db.db")))
-        .unwrap()
-        .0;
+    JsonResult(
+        db.run(move |db| {
+            let users = db::login::all_logins(db)?;
 
-    let users = db::login::all_logins(&db).unwrap();
+            for user in &users {
+                if env::var("SCHILLER_DB_USER").is_ok_and(|admin| admin == *user) {
+                    warn!("unable to delete admin '{}'", user);
+                } else {
+                    db::login::delete(db, &auth.user, user)?;
+                }
+            }
+            Ok(())
+        })
+        .await,
+    )
+}
 
-    for user in &users {
-        if *user == env::var("SCHILLER_DB_USER").unwrap() {
-            warn!("unable to delete admin '{}'", user);
-        } else {
-            db::login::delete(&db, user).unwrap();
-        }
+#[utoipa::path(
+    responses(
+        (status = 200, description = "Searched the audit trail of all readable tables, the newest changes first", body = Vec<Audit>),
+        (status = 400, description = "The entity or a timestamp is invalid", body = Error, example = json!({"Err": Error::InvalidDate})),
+        (status = 401, description = "Unauthorized to search the audit trail", body = Error, example = json!({"Err": Error::Unauthorized})),
+        (status = 503, description = "The database is unavailable", body = Error, example = json!({"Err": Error::DatabaseUnavailable})),
+    ),
+    params(
+        ("entity", description = "Only changes of this table: user, workless, criminal, login, role or hearing"),
+        ("key", description = "Only changes of this record, e.g. 'account/id' of a criminal, or all records of an account"),
+        ("actor", description = "Only changes made by this login"),
+        ("from", description = "Only changes at or after this RFC 3339 timestamp"),
+        ("to", description = "Only changes at or before this RFC 3339 timestamp"),
+        ("limit", description = "Number of results, at most 1000"),
+    ),
+    security(
+        ("authorization" = []),
+    )
+)]
+#[get("/api/audit?<entity>&<key>&<actor>&<from>&<to>&<limit>")]
+#[allow(clippy::too_many_arguments)]
+pub async fn search_audit(
+    auth: Auth<Authenticated>,
+    db: Db,
+    entity: Option<String>,
+    key: Option<String>,
+    actor: Option<String>,
+    from: Option<String>,
+    to: Option<String>,
+    limit: Option<usize>,
+) -> JsonResult<Vec<Audit>> {
+    JsonResult(
+        db.run(move |db| {
+            let entity = entity.as_deref().map(str::parse::<Entity>).transpose()?;
+            let from = from.as_deref().map(timestamp).transpose()?;
+            let to = to.as_deref().map(timestamp).transpose()?;
+
+            // only the tables the login is allowed to read
+            let permissions = db::login::fetch_permission(db, &auth.user)?;
+            let entities = [
+                (Entity::User, permissions.access_user),
+                (Entity::Workless, permissions.access_workless),
+                (Entity::Criminal, permissions.access_criminal),
+                (Entity::Login, permissions.access_user),
+                (Entity::Role, permissions.access_user),
+                (Entity::Hearing, permissions.access_criminal),
+            ]
+            .into_iter()
+            .filter(|&(e, p)| p != Permission::None && entity.is_none_or(|entity| entity == e))
+            .map(|(e, _)| e)
+            .collect::<Vec<_>>();
+
+            db::audit::search(
+                db,
+                AuditSearch {
+                    entities: &entities,
+                    key: key.as_deref(),
+                    actor: actor.as_deref(),
+                    from,
+                    to,
+                },
+                Paging::new(None, limit).limit,
+            )
+        })
+        .await,
+    )
+}
+
+/// Parses an RFC 3339 timestamp of a query parameter.
+fn timestamp(value: &str) -> Result<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(value)
+        .map(|t| t.with_timezone(&Utc))
+        .map_err(|_| Error::InvalidDate)
+}
+
+/// Parses a day like `2026-07-01`.
+fn date(value: &str) -> Result<NaiveDate> {
+    NaiveDate::parse_from_str(value, "%Y-%m-%d").map_err(|_| Error::InvalidDate)
+}
+
+/// JSON result of an operation, answered with the HTTP status of its error.
+pub struct JsonResult<T>(pub Result<T>);
+
+impl<'r, T: Serialize> Responder<'r, 'static> for JsonResult<T> {
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'static> {
+        let status = match &self.0 {
+            Ok(_) => Status::Ok,
+            Err(e) => status(e),
+        };
+        Response::build_from(Json(self.0).respond_to(request)?)
+            .status(status)
+            .ok()
+    }
+}
+
+/// HTTP status of an operation error.
+pub fn status(error: &Error) -> Status {
+    match error {
+        Error::NothingFound | Error::PageNotFound => Status::NotFound,
+        Error::AlreadyExists
+        | Error::Trashed
+        | Error::UserReferenced
+        | Error::RoleReferenced
+        | Error::InvalidTransition(..)
+        | Error::DoubleBooked(_) => Status::Conflict,
+        Error::Arguments
+        | Error::InvalidFormat
+        | Error::InvalidUser(_)
+        | Error::InvalidLogin(_)
+        | Error::InvalidWorkless(_)
+        | Error::InvalidCriminal(_)
+        | Error::InvalidRole(_)
+        | Error::InvalidHearing(_)
+        | Error::InvalidDate
+        | Error::InvalidKind
+        | Error::InvalidSort(_)
+        | Error::UnknownAccount
+        | Error::UnknownRole
+        // uploaded backups
+        | Error::NoProject
+        | Error::UnsupportedProjectVersion
+        | Error::InvalidKey => Status::BadRequest,
+        Error::Unauthorized => Status::Unauthorized,
+        Error::ExceededLimit => Status::PayloadTooLarge,
+        Error::UnprocessableEntity => Status::UnprocessableEntity,
+        Error::DatabaseUnavailable => Status::ServiceUnavailable,
+        Error::Logic
+        | Error::FileNotFound
+        | Error::FileOpen
+        | Error::SQL(_)
+        | Error::Network
+        | Error::InternalError
+        | Error::EncryptionUnsupported => Status::InternalServerError,
     }
-    Json(Ok(()))
+}
+
+/// File download with a suggested file name.
+pub struct Download {
+    pub name: String,
+    pub content_type: ContentType,
+    pub data: Vec<u8>,
+}
+
+impl<'r> Responder<'r, 'static> for Download {
+    fn respond_to(self, _request: &'r Request<'_>) -> response::Result<'static> {
+        Response::build()
+            .header(self.content_type)
+            .raw_header(
+                "Content-Disposition",
+                format!("attachment; filename=\"{}\"", self.name),
+            )
+            .sized_body(self.data.len(), Cursor::new(self.data))
+            .ok()
+    }
+}
+
+#[utoipa::path(
+    responses(
+        (status = 200, description = "Got every record of the database as JSON dump", content_type = "application/json"),
+        (status = 401, description = "Unauthorized to dump the database", body = Error, example = json!({"Err": Error::Unauthorized})),
+        (status = 503, description = "The database is unavailable", body = Error, example = json!({"Err": Error::DatabaseUnavailable})),
+    ),
+    security (
+        ("authorization" = []),
+    )
+)]
+#[get("/api/dump")]
+pub async fn dump(auth: Auth<Admin>, db: Db) -> std::result::Result<Download, JsonResult<()>> {
+    warn!("GET /dump: {}", auth.user);
+    let data = db
+        .run(|db| Ok(serde_json::to_vec_pretty(&db::dump::dump(db)?)?))
+        .await;
+    match data {
+        Ok(data) => Ok(Download {
+            name: format!("schiller-db-{}.json", Local::now().format("%Y%m%d-%H%M%S")),
+            content_type: ContentType::JSON,
+            data,
+        }),
+        Err(e) => Err(JsonResult(Err(e))),
+    }
+}
+
+/// Maximum size of an uploaded backup.
+const BACKUP_LIMIT: ByteUnit = ByteUnit::Gibibyte(1);
+
+#[utoipa::path(
+    responses(
+        (status = 200, description = "Got a consistent snapshot of the database", content_type = "application/octet-stream"),
+        (status = 401, description = "Unauthorized to backup the database", body = Error, example = json!({"Err": Error::Unauthorized})),
+        (status = 503, description = "The database is unavailable", body = Error, example = json!({"Err": Error::DatabaseUnavailable})),
+    ),
+    security (
+        ("authorization" = []),
+    )
+)]
+#[get("/api/backup")]
+pub async fn backup(auth: Auth<Admin>, db: Db) -> std::result::Result<Download, JsonResult<()>> {
+    warn!("GET /backup: {}", auth.user);
+    let data = db
+        .run(|db| {
+            let dir = tempfile::tempdir()?;
+            let path = dir.path().join("schiller-db.db");
+            db::backup::backup(db, &path)?;
+            Ok(fs::read(path)?)
+        })
+        .await;
+    match data {
+        Ok(data) => Ok(Download {
+            name: format!("schiller-db-{}.db", Local::now().format("%Y%m%d-%H%M%S")),
+            content_type: ContentType::Binary,
+            data,
+        }),
+        Err(e) => Err(JsonResult(Err(e))),
+    }
+}
+
+#[utoipa::path(
+    request_body(content = Vec<u8>, description = "A backup of the database", content_type = "application/octet-stream"),
+    responses(
+        (status = 200, description = "Restored the database from the backup"),
+        (status = 400, description = "The backup is invalid", body = Error, example = json!({"Err": Error::InvalidFormat})),
+        (status = 401, description = "Unauthorized to restore the database", body = Error, example = json!({"Err": Error::Unauthorized})),
+        (status = 413, description = "The backup is too large", body = Error, example = json!({"Err": Error::ExceededLimit})),
+        (status = 503, description = "The database is unavailable", body = Error, example = json!({"Err": Error::DatabaseUnavailable})),
+    ),
+    security (
+        ("authorization" = []),
+    )
+)]
+#[post("/api/restore", data = "<data>")]
+pub async fn restore(auth: Auth<Admin>, db: Db, data: Data<'_>) -> JsonResult<()> {
+    warn!("POST /restore: {}", auth.user);
+    let dir = match tempfile::tempdir() {
+        Ok(dir) => dir,
+        Err(e) => return JsonResult(Err(e.into())),
+    };
+    let path = dir.path().join("restore.db");
+    match data.open(BACKUP_LIMIT).into_file(&path).await {
+        Ok(file) if file.is_complete() => {}
+        Ok(_) => return JsonResult(Err(Error::ExceededLimit)),
+        Err(e) => return JsonResult(Err(e.into())),
+    }
+
+    JsonResult(
+        db.run(move |db| {
+            // the restore needs exclusive access to the connection
+            let (mut target, _) = Database::open(Cow::from(db.path()), db.key())?;
+            let result = db::backup::restore(&mut target, &path);
+            drop(dir);
+            result
+        })
+        .await,
+    )
 }